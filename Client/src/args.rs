@@ -1,4 +1,7 @@
-use clap::Parser; 
+use clap::Parser;
+use ruggine_client::Config;
+use ruggine_common::{Codec, Lang};
+use std::time::Duration;
 
 /*
 Modulo Args: gestisce il parsing degli argomenti da linea di comando per il client.
@@ -16,4 +19,56 @@ pub struct Args {
     /// Nickname (se omesso, verrà richiesto all'avvio e ritentato se rifiutato)
     #[arg(long)]
     pub nick: Option<String>,
+
+    /// Connessione al server tramite TLS
+    #[arg(long)]
+    pub tls: bool,
+
+    /// Certificato della CA (PEM) con cui verificare il server, es. per certificati autofirmati
+    #[arg(long, requires = "tls")]
+    pub ca: Option<String>,
+
+    /// Nome atteso nel certificato del server (default: host di --server)
+    #[arg(long, requires = "tls")]
+    pub tls_name: Option<String>,
+
+    /// Crea un nuovo account invece di accedere a uno esistente
+    #[arg(long)]
+    pub new_account: bool,
+
+    /// Intervallo in secondi tra i ping automatici al server
+    #[arg(long, default_value_t = 15)]
+    pub heartbeat: u64,
+
+    /// Secondi senza risposte dal server dopo cui la connessione è considerata persa
+    #[arg(long, default_value_t = 45)]
+    pub timeout: u64,
+
+    /// Formato dei messaggi: json oppure msgpack (binario, usato solo se il server lo supporta)
+    #[arg(long, default_value_t = Codec::Json)]
+    pub codec: Codec,
+
+    /// Dimensione massima in byte di un messaggio, in lettura e in scrittura
+    #[arg(long, default_value_t = ruggine_common::MAX_FRAME_LEN)]
+    pub max_frame: usize,
+
+    /// Lingua dell'interfaccia: it oppure en (default: da RUGGINE_LANG, LC_ALL o LANG)
+    #[arg(long)]
+    pub lang: Option<Lang>,
+}
+
+impl Args {
+    // Parametri di connessione per la libreria client
+    pub fn config(&self) -> Config {
+        Config {
+            server: self.server.clone(),
+            tls: self.tls,
+            ca: self.ca.clone(),
+            tls_name: self.tls_name.clone(),
+            codec: self.codec,
+            max_frame: self.max_frame,
+            heartbeat: Duration::from_secs(self.heartbeat.max(1)),
+            timeout: Duration::from_secs(self.timeout.max(1)),
+        }
+    }
 }
@@ -0,0 +1,114 @@
+/*
+Modulo Catalog: testi del client (libreria e interfaccia testuale) in italiano e inglese.
+Ogni voce è (chiave, italiano, inglese); `{}` indica i parametri, nell'ordine.
+Si usa con la macro t!("chiave", parametri...), che sceglie la lingua impostata all'avvio.
+*/
+
+use ruggine_common::i18n::Catalog;
+
+pub static CATALOG: Catalog = &[
+    // menu dei comandi
+    ("help.title", "============================= MENU COMANDI ================================", "============================= COMMAND MENU ================================"),
+    ("help.help", "/help (o /)                  visualizza questo menu dettagliato", "/help (or /)                 show this detailed menu"),
+    ("help.create", "/create <name>               crea un nuovo gruppo con nome <name>", "/create <name>               create a new group called <name>"),
+    ("help.invite", "/invite <group> <nick> [t]   invita <nick> nel gruppo <group>; [t] durata del codice (es. 90, 30m, 2h, 7d)", "/invite <group> <nick> [t]   invite <nick> to group <group>; [t] code lifetime (e.g. 90, 30m, 2h, 7d)"),
+    ("help.link", "/link <group> [t] [n]        crea un codice aperto a tutti, valido per [n] ingressi ([t] - = default)", "/link <group> [t] [n]        create a code anyone can use, valid for [n] joins ([t] - = default)"),
+    ("help.invites", "/invites <group>             mostra gli inviti ancora validi del gruppo", "/invites <group>             show the group's pending invites"),
+    ("help.revoke", "/revoke <code>               revoca il codice invito <code>", "/revoke <code>               revoke invite code <code>"),
+    ("help.join", "/join <group> <code>         unisciti al gruppo <group> con il codice <code>", "/join <group> <code>         join group <group> with invite code <code>"),
+    ("help.leave", "/leave <group>               esci dal gruppo <group>", "/leave <group>               leave group <group>"),
+    ("help.kick", "/kick <group> <nick>         espelli l'utente <nick> dal gruppo <group>", "/kick <group> <nick>         remove user <nick> from group <group>"),
+    ("help.promote", "/promote <group> <nick>      rendi <nick> amministratore del gruppo <group>", "/promote <group> <nick>      make <nick> an admin of group <group>"),
+    ("help.demote", "/demote <group> <nick>       togli a <nick> il ruolo di amministratore", "/demote <group> <nick>       remove the admin role from <nick>"),
+    ("help.transfer", "/transfer <group> <nick>     cedi a <nick> la proprietà del gruppo <group>", "/transfer <group> <nick>     hand ownership of group <group> to <nick>"),
+    ("help.rename", "/rename <group> <name>       cambia il nome del gruppo <group> in <name>", "/rename <group> <name>       rename group <group> to <name>"),
+    ("help.users", "/users                       mostra tutti gli utenti connessi", "/users                       show all connected users"),
+    ("help.groups", "/groups                      mostra i gruppi di appartenenza", "/groups                      show the groups you belong to"),
+    ("help.msg", "/msg <group> <text>          invia il messaggio <text> al gruppo <group>", "/msg <group> <text>          send message <text> to group <group>"),
+    ("help.dm", "/dm <nick> <text>            invia il messaggio privato <text> all'utente <nick>", "/dm <nick> <text>            send direct message <text> to user <nick>"),
+    ("help.history", "/history <group> [n] [id]    mostra gli ultimi [n] messaggi del gruppo (prima di [id])", "/history <group> [n] [id]    show the last [n] messages of the group (before [id])"),
+    ("help.op", "/op <azione> <nick> [...]    moderazione, solo operatori: kick|ban|unban|banip|unbanip|mute|unmute", "/op <action> <nick> [...]    moderation, operators only: kick|ban|unban|banip|unbanip|mute|unmute"),
+    ("help.quit", "/quit                        esci dal client", "/quit                        exit the client"),
+    // errori di sintassi dei comandi
+    ("usage", "[error] uso: {}", "[error] usage: {}"),
+    ("usage.bad_command", "[error] comando errato", "[error] unknown command"),
+    ("msg.too_long", "[error] messaggio troppo lungo, non inviato (massimo {} byte)", "[error] message too long, not sent (max {} bytes)"),
+    // messaggi ricevuti dal server
+    ("msg.registered", "[server] registrazione: ok={} {}", "[server] registration: ok={} {}"),
+    ("msg.invite_code_from", "[server] codice invito per il gruppo '{}': {} da {}", "[server] invite code for group '{}': {} from {}"),
+    ("msg.invite_code", "[server] codice invito per il gruppo '{}': {}", "[server] invite code for group '{}': {}"),
+    ("msg.invite_expires", "{} (scade il {})", "{} (expires {})"),
+    ("msg.invite_link", "[server] link di invito per il gruppo '{}': {} (usi: {})", "[server] invite link for group '{}': {} (uses: {})"),
+    ("msg.invite_unlimited", "illimitati", "unlimited"),
+    ("msg.invites_count", "[server] {} inviti nel gruppo '{}'", "[server] {} invites in group '{}'"),
+    ("invites.empty", "[server] nessun invito in attesa nel gruppo '{}'", "[server] no pending invites in group '{}'"),
+    ("invites.header", "[server] inviti del gruppo '{}':", "[server] invites of group '{}':"),
+    ("invites.line", "[server]   {}  {}  da {}  usi {}  scadenza {}", "[server]   {}  {}  by {}  uses {}  expires {}"),
+    ("invites.link", "link aperto", "open link"),
+    ("invites.never", "nessuna", "never"),
+    ("msg.joined", "[server] sei entrato nel gruppo '{}'", "[server] you joined group '{}'"),
+    ("msg.left", "[server] sei uscito dal gruppo '{}'", "[server] you left group '{}'"),
+    ("msg.groups", "[server] Gruppi di appartenenza: {}", "[server] Your groups: {}"),
+    ("msg.users", "[server] Utenti: {}", "[server] Users: {}"),
+    ("msg.welcome", "[server] protocollo v{} (funzionalità: {})", "[server] protocol v{} (features: {})"),
+    ("msg.global", "[globale] <{}> {}", "[global] <{}> {}"),
+    ("msg.group_created", "[server] gruppo '{}' creato correttamente!", "[server] group '{}' created!"),
+    ("msg.group_renamed", "[server] {} ha rinominato il gruppo '{}' in '{}'", "[server] {} renamed group '{}' to '{}'"),
+    ("msg.shutdown", "[server] {}: connessione chiusa dal server", "[server] {}: connection closed by the server"),
+    ("msg.shutdown_reconnect", "[server] {}: nuovo tentativo di connessione tra {} secondi", "[server] {}: reconnecting in {} seconds"),
+    ("msg.history_count", "[storico] {} messaggi dal gruppo '{}'", "[history] {} messages from group '{}'"),
+    ("history.empty", "[storico] nessun messaggio nel gruppo '{}'", "[history] no messages in group '{}'"),
+    ("history.header", "[storico] ───── cronologia del gruppo '{}' ─────", "[history] ───── history of group '{}' ─────"),
+    ("history.more", "[storico] ... messaggi precedenti: /history {} <n> {}", "[history] ... older messages: /history {} <n> {}"),
+    ("history.line", "[storico] {} [{}] <{}> {}", "[history] {} [{}] <{}> {}"),
+    ("history.footer", "[storico] ───── fine cronologia ─────", "[history] ───── end of history ─────"),
+    // accesso
+    ("login.ask_new_account", "Vuoi creare un nuovo account?", "Do you want to create a new account?"),
+    ("login.password", "Password: ", "Password: "),
+    ("login.confirm_password", "Conferma password: ", "Confirm password: "),
+    ("login.password_mismatch", "Le password non coincidono.", "Passwords do not match."),
+    ("login.ok", "[server] utente {} loggato correttamente", "[server] user {} logged in"),
+    ("login.menu_hint", "[server] Per visualizzare il menu invia '/' ", "[server] Send '/' to show the menu"),
+    ("login.rejected", "[server] Accesso rifiutato: {}", "[server] Login rejected: {}"),
+    ("login.unknown_reason", "motivo sconosciuto", "unknown reason"),
+    ("login.unexpected", "[server] risposta inattesa durante la registrazione: {}", "[server] unexpected reply during login: {}"),
+    ("login.parse_failed", "Parse della risposta di registrazione fallito: {}", "Could not parse the login reply: {}"),
+    ("login.closed", "Connessione chiusa durante la registrazione", "Connection closed during login"),
+    ("prompt.nick", "Scegli un nickname: ", "Choose a nickname: "),
+    ("prompt.nick_empty", "Il nickname non può essere vuoto.", "The nickname cannot be empty."),
+    ("prompt.password_empty", "La password non può essere vuota.", "The password cannot be empty."),
+    ("prompt.yes_no", "{} [s/N]: ", "{} [y/N]: "),
+    // connessione
+    ("conn.rejected", "Il server ha rifiutato la connessione: {}", "The server refused the connection: {}"),
+    ("conn.closed_negotiation", "Connessione chiusa durante la negoziazione del protocollo", "Connection closed during protocol negotiation"),
+    ("conn.closed_resume", "Connessione chiusa durante la ripresa della sessione", "Connection closed while resuming the session"),
+    ("conn.bad_tls_name", "nome del server non valido per TLS: {}", "invalid server name for TLS: {}"),
+    ("conn.tls_failed", "handshake TLS fallito", "TLS handshake failed"),
+    ("conn.unexpected_reply", "risposta inattesa dal server: {}", "unexpected reply from the server: {}"),
+    ("conn.no_reply", "nessuna risposta dal server alla richiesta {}", "no reply from the server to request {}"),
+    ("conn.lost", "[error] connessione al server persa (CTRL+C per uscire)", "[error] connection to the server lost (CTRL+C to exit)"),
+    ("conn.reconnecting", "[error] connessione al server persa: riconnessione in corso...", "[error] connection to the server lost: reconnecting..."),
+    ("conn.reconnected", "[server] riconnesso: sessione ripristinata", "[server] reconnected: session restored"),
+    ("conn.session_expired", "[error] sessione scaduta: accedi di nuovo", "[error] session expired: please log in again"),
+    // interfaccia
+    ("ui.prompt_lost", "[connessione persa] > ", "[connection lost] > "),
+    ("ui.exiting", "Uscita dal client...", "Exiting the client..."),
+    ("ui.bye", "{} ti sei disconnesso correttamente", "{} you have been disconnected"),
+    ("ui.error_for", "[error] ({}) {}", "[error] ({}) {}"),
+    ("ui.error", "[error] {}", "[error] {}"),
+];
+
+// Testo della chiave nella lingua del client, con i parametri al posto di `{}`.
+// Esportata perché la usa anche il binario dell'interfaccia testuale
+#[doc(hidden)]
+#[macro_export]
+macro_rules! t {
+    ($key:literal $(, $arg:expr)* $(,)?) => {
+        ruggine_common::i18n::translate(
+            $crate::catalog::CATALOG,
+            $key,
+            &[$(&$arg as &dyn std::fmt::Display),*],
+        )
+    };
+}
+pub use t;
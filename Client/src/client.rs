@@ -0,0 +1,507 @@
+/*
+Modulo Client: API asincrona per parlare con un server Ruggine, usata dall'interfaccia testuale,
+dai bot e dai test di integrazione.
+`Connection` è una connessione già negoziata in attesa dell'accesso; dopo l'accesso `start`
+restituisce il `RuggineClient`, con un metodo per ogni comando, e lo Stream `Events` dei messaggi
+ricevuti. Un task in background legge dal server, invia i ping periodici e, se la connessione cade,
+riprende la sessione con attesa crescente tra i tentativi.
+*/
+
+use futures::Stream;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+use ruggine_common::{
+    ClientToServer, Envelope, ErrorCode, InviteEntry, ModAction, ServerToClient, FEATURE_RESUME,
+};
+
+use crate::catalog::t;
+use crate::config::Config;
+use crate::net::{self, ServerReader, ServerWriter};
+use crate::requests::Requests;
+
+// Attesa massima tra due tentativi di riconnessione
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+// Evento ricevuto dallo Stream del client
+#[derive(Debug, Clone)]
+pub enum Event {
+    // Messaggio del server; `request` è l'id del comando a cui risponde (None se non è una risposta diretta)
+    Message {
+        msg: ServerToClient,
+        request: Option<u64>,
+    },
+    // Connessione persa: si tenta di riprendere la sessione
+    Reconnecting,
+    // Sessione ripresa su una nuova connessione
+    Reconnected,
+    // Connessione persa senza una sessione da riprendere; lo Stream termina
+    Disconnected,
+    // Il server ha rifiutato la ripresa della sessione; lo Stream termina
+    SessionExpired,
+}
+
+// Connessione negoziata, prima dell'accesso
+pub struct Connection {
+    config: Config,
+    reader: ServerReader,
+    writer: ServerWriter,
+    features: Vec<String>,
+    nick: String,
+    session: Option<String>,
+}
+
+impl Connection {
+    // Si collega al server (TCP o TLS) e negozia versione e funzionalità del protocollo
+    pub async fn open(config: &Config) -> anyhow::Result<Self> {
+        let (mut reader, mut writer) = net::connect(config).await?;
+        let features = net::negotiate(config, &mut writer, &mut reader).await?;
+        Ok(Self {
+            config: config.clone(),
+            reader,
+            writer,
+            features,
+            nick: String::new(),
+            session: None,
+        })
+    }
+
+    // Funzionalità concordate con il server nel Welcome
+    pub fn features(&self) -> &[String] {
+        &self.features
+    }
+
+    // Accesso con un account esistente. Se il server rifiuta, l'errore contiene l'ErrorCode
+    // (recuperabile con `downcast_ref`) e si può riprovare sulla stessa connessione
+    pub async fn login(&mut self, nick: &str, password: &str) -> anyhow::Result<()> {
+        let request = ClientToServer::Login {
+            nick: nick.to_string(),
+            password: password.to_string(),
+        };
+        self.authenticate(nick, request).await
+    }
+
+    // Creazione di un nuovo account, con accesso immediato
+    pub async fn create_account(&mut self, nick: &str, password: &str) -> anyhow::Result<()> {
+        let request = ClientToServer::CreateAccount {
+            nick: nick.to_string(),
+            password: password.to_string(),
+        };
+        self.authenticate(nick, request).await
+    }
+
+    async fn authenticate(&mut self, nick: &str, request: ClientToServer) -> anyhow::Result<()> {
+        net::send(&mut self.writer, &request).await?;
+        let frame = match self.reader.next_frame().await? {
+            Some(frame) => frame,
+            None => anyhow::bail!(t!("login.closed")),
+        };
+        match self.reader.codec().decode::<ServerToClient>(&frame) {
+            Ok(ServerToClient::Registered {
+                ok: true, session, ..
+            }) => {
+                self.nick = nick.to_string();
+                // senza la funzionalità "resume" non si tenta la ripresa della sessione
+                self.session =
+                    session.filter(|_| self.features.iter().any(|f| f == FEATURE_RESUME));
+                Ok(())
+            }
+            // Il motivo è il codice dell'errore; in mancanza si usa il campo reason
+            Ok(ServerToClient::Registered { reason, error, .. }) => match error {
+                Some(code) => Err(code.into()),
+                None => anyhow::bail!(reason.unwrap_or_else(|| t!("login.unknown_reason"))),
+            },
+            Ok(other) => anyhow::bail!(t!("login.unexpected", format!("{:?}", other))),
+            Err(e) => anyhow::bail!(t!("login.parse_failed", e)),
+        }
+    }
+
+    // Avvia i task in background e restituisce il client e lo Stream degli eventi
+    pub fn start(self) -> (RuggineClient, Events) {
+        let writer = Arc::new(Mutex::new(self.writer));
+        let requests = Arc::new(Requests::default());
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+
+        let read_task = tokio::spawn(read_loop(
+            self.config.clone(),
+            self.reader,
+            Arc::clone(&writer),
+            Arc::clone(&requests),
+            events_tx,
+            self.nick.clone(),
+            self.session,
+        ));
+        let heartbeat_task = tokio::spawn(heartbeat(
+            self.config.heartbeat,
+            Arc::clone(&writer),
+            Arc::clone(&requests),
+        ));
+
+        let client = RuggineClient {
+            config: self.config,
+            nick: self.nick,
+            writer,
+            requests,
+            tasks: vec![read_task, heartbeat_task],
+        };
+        (client, Events { rx: events_rx })
+    }
+}
+
+// Client autenticato. I comandi senza risposta certa restituiscono l'id della richiesta, che il
+// server ripete nelle risposte dirette (errori compresi) ricevute come Event::Message;
+// le interrogazioni (list_users, list_groups, ping) attendono la risposta.
+// Alla distruzione del client i task in background vengono fermati
+pub struct RuggineClient {
+    config: Config,
+    nick: String,
+    writer: Arc<Mutex<ServerWriter>>,
+    requests: Arc<Requests>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl RuggineClient {
+    // Nickname con cui si è effettuato l'accesso
+    pub fn nick(&self) -> &str {
+        &self.nick
+    }
+
+    // Id da usare con `send_with_id`, per associare le risposte prima ancora di inviare il comando
+    pub fn next_id(&self) -> u64 {
+        self.requests.next_id()
+    }
+
+    pub async fn send_with_id(&self, id: u64, msg: ClientToServer) -> anyhow::Result<()> {
+        let mut wh = self.writer.lock().await;
+        net::send_envelope(&mut wh, &Envelope::new(Some(id), msg)).await
+    }
+
+    // Invia un comando qualsiasi e restituisce l'id della richiesta
+    pub async fn request(&self, msg: ClientToServer) -> anyhow::Result<u64> {
+        let id = self.next_id();
+        self.send_with_id(id, msg).await?;
+        Ok(id)
+    }
+
+    // Invia un comando e attende la prima risposta diretta, al più per `wait`
+    pub async fn call(
+        &self,
+        msg: ClientToServer,
+        wait: Duration,
+    ) -> anyhow::Result<ServerToClient> {
+        self.requests.call(&self.writer, msg, wait).await
+    }
+
+    // Come `call` con il timeout della configurazione; una risposta Error diventa un errore con il suo ErrorCode
+    async fn query(&self, msg: ClientToServer) -> anyhow::Result<ServerToClient> {
+        match self.call(msg, self.config.timeout).await? {
+            ServerToClient::Error { code, .. } => Err(code.into()),
+            reply => Ok(reply),
+        }
+    }
+
+    pub async fn create_group(&self, group: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::CreateGroup {
+            group: group.to_string(),
+        })
+        .await
+    }
+
+    // `ttl`: secondi di validità del codice (None = default del server)
+    pub async fn invite(&self, group: &str, nick: &str, ttl: Option<u64>) -> anyhow::Result<u64> {
+        self.request(ClientToServer::Invite {
+            group: group.to_string(),
+            nick: nick.to_string(),
+            ttl,
+        })
+        .await
+    }
+
+    // Link di invito aperto, ricevuto come Event::Message con InviteLink
+    pub async fn create_invite_link(
+        &self,
+        group: &str,
+        ttl: Option<u64>,
+        max_uses: Option<u32>,
+    ) -> anyhow::Result<u64> {
+        self.request(ClientToServer::CreateInviteLink {
+            group: group.to_string(),
+            ttl,
+            max_uses,
+        })
+        .await
+    }
+
+    pub async fn revoke_invite(&self, code: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::RevokeInvite {
+            code: code.to_string(),
+        })
+        .await
+    }
+
+    pub async fn join(&self, group: &str, invite_code: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::JoinGroup {
+            group: group.to_string(),
+            invite_code: invite_code.to_string(),
+        })
+        .await
+    }
+
+    pub async fn leave(&self, group: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::LeaveGroup {
+            group: group.to_string(),
+        })
+        .await
+    }
+
+    pub async fn kick(&self, group: &str, nick: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::Kick {
+            group: group.to_string(),
+            nick: nick.to_string(),
+        })
+        .await
+    }
+
+    pub async fn promote(&self, group: &str, nick: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::Promote {
+            group: group.to_string(),
+            nick: nick.to_string(),
+        })
+        .await
+    }
+
+    pub async fn demote(&self, group: &str, nick: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::Demote {
+            group: group.to_string(),
+            nick: nick.to_string(),
+        })
+        .await
+    }
+
+    pub async fn transfer_ownership(&self, group: &str, nick: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::TransferOwnership {
+            group: group.to_string(),
+            nick: nick.to_string(),
+        })
+        .await
+    }
+
+    pub async fn rename_group(&self, group: &str, new_name: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::RenameGroup {
+            group: group.to_string(),
+            new_name: new_name.to_string(),
+        })
+        .await
+    }
+
+    // Azione di moderazione: il server la accetta solo dagli operatori (--operator)
+    pub async fn moderate(&self, action: ModAction) -> anyhow::Result<u64> {
+        self.request(ClientToServer::Moderate { action }).await
+    }
+
+    // Messaggio a un gruppo
+    pub async fn send(&self, group: &str, text: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::SendMessage {
+            group: group.to_string(),
+            text: text.to_string(),
+        })
+        .await
+    }
+
+    // Messaggio a tutti gli utenti connessi
+    pub async fn global(&self, text: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::GlobalMessage {
+            text: text.to_string(),
+        })
+        .await
+    }
+
+    // Messaggio privato a un utente connesso
+    pub async fn dm(&self, to: &str, text: &str) -> anyhow::Result<u64> {
+        self.request(ClientToServer::DirectMessage {
+            to: to.to_string(),
+            text: text.to_string(),
+        })
+        .await
+    }
+
+    // Pagina di cronologia, ricevuta come Event::Message con HistoryPage
+    pub async fn history(
+        &self,
+        group: &str,
+        before: Option<u64>,
+        limit: Option<u32>,
+    ) -> anyhow::Result<u64> {
+        self.request(ClientToServer::History {
+            group: group.to_string(),
+            before,
+            limit,
+        })
+        .await
+    }
+
+    // Utenti connessi
+    pub async fn list_users(&self) -> anyhow::Result<Vec<String>> {
+        match self.query(ClientToServer::ListUsers).await? {
+            ServerToClient::ListUsers { users } => Ok(users),
+            other => anyhow::bail!(t!("conn.unexpected_reply", format!("{:?}", other))),
+        }
+    }
+
+    // Gruppi di cui si è membri (vuoto se nessuno)
+    pub async fn list_groups(&self) -> anyhow::Result<Vec<String>> {
+        match self.query(ClientToServer::ListGroups).await {
+            Ok(ServerToClient::Groups { groups }) => Ok(groups),
+            Ok(other) => anyhow::bail!(t!("conn.unexpected_reply", format!("{:?}", other))),
+            Err(e) if e.downcast_ref::<ErrorCode>() == Some(&ErrorCode::NoGroups) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Inviti ancora validi del gruppo (solo per proprietario e amministratori)
+    pub async fn list_invites(&self, group: &str) -> anyhow::Result<Vec<InviteEntry>> {
+        let msg = ClientToServer::ListInvites {
+            group: group.to_string(),
+        };
+        match self.query(msg).await? {
+            ServerToClient::Invites { invites, .. } => Ok(invites),
+            other => anyhow::bail!(t!("conn.unexpected_reply", format!("{:?}", other))),
+        }
+    }
+
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        self.query(ClientToServer::Ping).await.map(|_| ())
+    }
+
+    // Uscita esplicita: il server chiude la sessione senza periodo di grazia
+    pub async fn logout(&self, reason: Option<&str>) -> anyhow::Result<()> {
+        let mut wh = self.writer.lock().await;
+        net::send(
+            &mut wh,
+            &ClientToServer::Logout {
+                reason: reason.map(str::to_string),
+            },
+        )
+        .await
+    }
+}
+
+impl Drop for RuggineClient {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// Stream dei messaggi ricevuti dal server e dei cambi di stato della connessione
+pub struct Events {
+    rx: mpsc::UnboundedReceiver<Event>,
+}
+
+impl Stream for Events {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+// Legge i messaggi del server e li trasforma in eventi; se la connessione cade prova a riprendere la sessione
+async fn read_loop(
+    config: Config,
+    mut reader: ServerReader,
+    writer: Arc<Mutex<ServerWriter>>,
+    requests: Arc<Requests>,
+    events: mpsc::UnboundedSender<Event>,
+    nick: String,
+    session: Option<String>,
+) {
+    // gli eventi non letti (Events già distrutto) vengono ignorati: le risposte attese vanno comunque smistate
+    loop {
+        // attesa prima della riconnessione: quella indicata dal server se si sta arrestando
+        let mut first_retry = Duration::from_secs(1);
+        // la sessione è stata ripresa da un'altra connessione: riprenderla di nuovo chiuderebbe quella
+        let mut replaced = false;
+        // grazie ai ping periodici il server risponde sempre entro il timeout, se è raggiungibile
+        while let Ok(Ok(Some(frame))) =
+            tokio::time::timeout(config.timeout, reader.next_frame()).await
+        {
+            if let Ok(reply) = reader.codec().decode::<Envelope<ServerToClient>>(&frame) {
+                if let ServerToClient::ServerShutdown {
+                    reconnect_after: Some(secs),
+                    ..
+                } = &reply.body
+                {
+                    first_retry = Duration::from_secs(*secs).max(first_retry);
+                }
+                if let ServerToClient::Error {
+                    code: ErrorCode::SessionReplaced,
+                    ..
+                } = &reply.body
+                {
+                    replaced = true;
+                }
+                // le risposte attese (es. Pong dei ping automatici) sono consegnate a chi le aspetta
+                if let Some(event) = requests.resolve(reply) {
+                    let _ = events.send(event);
+                }
+            }
+        }
+        // EOF, errore o silenzio oltre il timeout
+        let session = match &session {
+            Some(s) if !replaced => s,
+            _ => {
+                let _ = events.send(Event::Disconnected);
+                return;
+            }
+        };
+        let _ = events.send(Event::Reconnecting);
+        match reconnect(&config, &nick, session, first_retry).await {
+            Some((new_reader, new_writer)) => {
+                reader = new_reader;
+                *writer.lock().await = new_writer;
+                let _ = events.send(Event::Reconnected);
+            }
+            None => {
+                let _ = events.send(Event::SessionExpired);
+                return;
+            }
+        }
+    }
+}
+
+// Invia un Ping a intervalli regolari per tenere viva la connessione
+async fn heartbeat(period: Duration, writer: Arc<Mutex<ServerWriter>>, requests: Arc<Requests>) {
+    let period = period.max(Duration::from_secs(1));
+    let mut ticker = tokio::time::interval(period);
+    ticker.tick().await; // il primo tick è immediato
+    loop {
+        ticker.tick().await;
+        // in caso di errore si continua: dopo una riconnessione il writer viene sostituito.
+        // La mancanza del Pong la rileva il task di lettura (timeout), qui basta attenderlo
+        let _ = requests.call(&writer, ClientToServer::Ping, period).await;
+    }
+}
+
+// Riprova la connessione con attesa crescente (da `first`, di solito 1 secondo, raddoppiando fino
+// a 30 secondi) finché il server risponde; None se il server rifiuta la ripresa della sessione
+async fn reconnect(
+    config: &Config,
+    nick: &str,
+    session: &str,
+    first: Duration,
+) -> Option<(ServerReader, ServerWriter)> {
+    let mut delay = first;
+    loop {
+        tokio::time::sleep(delay).await;
+        match net::resume(config, nick, session).await {
+            Ok(res) => return res,
+            Err(_) => delay = (delay * 2).min(MAX_RECONNECT_DELAY),
+        }
+    }
+}
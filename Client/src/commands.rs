@@ -3,124 +3,350 @@ Modulo Commands: contiene la logica per interpretare e gestire i comandi inserit
 Invia le richieste appropriate al server e gestisce la risposta locale.
 */
 
-use ruggine_common::ClientToServer;
-use std::sync::Arc;
-use tokio::net::tcp::OwnedWriteHalf;
-use tokio::sync::Mutex;
+use ruggine_client::catalog::t;
+use ruggine_client::RuggineClient;
+use ruggine_common::{ClientToServer, FrameError, ModAction};
 
-use crate::net::send;
+use crate::labels::Labels;
 use crate::terminal::restore_terminal;
 
 // Funzione che gestisce i comandi e messaggi
 pub async fn handle_command(
     line: &str,
-    writer_half: &Arc<Mutex<OwnedWriteHalf>>, // metà di scrittura di una connessione TCP asincrona gestita da Tokio
-    my_nick: &str,
+    client: &RuggineClient, // connessione al server, vedi la libreria ruggine_client
+    labels: &Labels,        // associa le risposte del server al comando digitato
 ) -> anyhow::Result<Vec<String>> {
     let mut out = Vec::new(); // vettore di output strings che verrà restituito a fine funzione
     if line == "/help" || line == "/" {
         out.push(String::new());
-        out.push("============================= MENU COMANDI ================================".into());
-        out.push("/help (o /)                  visualizza questo menu dettagliato".into());
-        out.push("/create <name>               crea un nuovo gruppo con nome <name>".into());
-        out.push("/invite <group> <nick>       invita l'utente <nick> nel gruppo <group>".into());
-        out.push("/join <group> <code>         unisciti al gruppo <group> con il codice <code>".into());
-        out.push("/leave <group>               esci dal gruppo <group>".into());
-        out.push("/users                       mostra tutti gli utenti connessi".into());
-        out.push("/groups                      mostra i gruppi di appartenenza".into());
-        out.push("/msg <group> <text>          invia il messaggio <text> al gruppo <group>".into());
-        out.push("/quit                        esci dal client".into());
-        out.push("==========================================================================".into());
+        out.push(t!("help.title"));
+        out.push(t!("help.help"));
+        out.push(t!("help.create"));
+        out.push(t!("help.invite"));
+        out.push(t!("help.link"));
+        out.push(t!("help.invites"));
+        out.push(t!("help.revoke"));
+        out.push(t!("help.join"));
+        out.push(t!("help.leave"));
+        out.push(t!("help.kick"));
+        out.push(t!("help.promote"));
+        out.push(t!("help.demote"));
+        out.push(t!("help.transfer"));
+        out.push(t!("help.rename"));
+        out.push(t!("help.users"));
+        out.push(t!("help.groups"));
+        out.push(t!("help.msg"));
+        out.push(t!("help.dm"));
+        out.push(t!("help.history"));
+        out.push(t!("help.op"));
+        out.push(t!("help.quit"));
+        out.push(
+            "==========================================================================".into(),
+        );
         out.push(String::new());
     } else if line == "/quit" {
-        // acquisire in modo asincrono il lock sul writer della connessione TCP
-        let mut wh = writer_half.lock().await;
-        // invio al server, tramite connessione TCP, il messaggio di Logout preso dalla common_lib
-        let _ = send(&mut *wh, &ClientToServer::Logout { reason: None }).await;
+        // invio al server del messaggio di Logout
+        let _ = client.logout(None).await;
 
         // ripristina stato terminale prima di uscire
         restore_terminal();
 
-        println!("{} ti sei disconnesso correttamente", my_nick);
+        println!("{}", t!("ui.bye", client.nick()));
         std::process::exit(0);
     } else if let Some(rest) = line.strip_prefix("/create ") {
-        let mut wh = writer_half.lock().await;
-        let _ = send(
-            &mut *wh, &ClientToServer::CreateGroup { 
-                group: rest.to_string()
-            }).await;
-    } else if let Some(rest) = line.strip_prefix("/invite ") {
-        let mut it = rest.splitn(2, ' ');
-        if let (Some(group), Some(nick)) = (it.next(), it.next()) {
-            let mut wh = writer_half.lock().await;
-            let _ = send(
-                &mut *wh,
-                &ClientToServer::Invite {
-                    group: group.into(),
-                    nick: nick.into(),
+        let _ = labels
+            .send(
+                client,
+                ClientToServer::CreateGroup {
+                    group: rest.to_string(),
                 },
-            ).await;
+                line,
+            )
+            .await;
+    } else if let Some(rest) = line.strip_prefix("/invite ") {
+        let mut it = rest.split_whitespace();
+        let (group, nick) = (it.next(), it.next());
+        let ttl = it.next().map_or(Some(None), parse_duration);
+        match (group, nick, ttl, it.next()) {
+            (Some(group), Some(nick), Some(ttl), None) => {
+                let _ = labels
+                    .send(
+                        client,
+                        ClientToServer::Invite {
+                            group: group.into(),
+                            nick: nick.into(),
+                            ttl,
+                        },
+                        line,
+                    )
+                    .await;
+            }
+            _ => out.push(t!("usage", "/invite <group> <nick> [duration]")),
+        }
+    } else if let Some(rest) = line.strip_prefix("/link ") {
+        let mut it = rest.split_whitespace();
+        let group = it.next();
+        let ttl = it.next().map_or(Some(None), parse_duration);
+        let max_uses = it
+            .next()
+            .map_or(Some(None), |n| n.parse::<u32>().ok().map(Some));
+        match (group, ttl, max_uses, it.next()) {
+            (Some(group), Some(ttl), Some(max_uses), None) => {
+                let _ = labels
+                    .send(
+                        client,
+                        ClientToServer::CreateInviteLink {
+                            group: group.into(),
+                            ttl,
+                            max_uses,
+                        },
+                        line,
+                    )
+                    .await;
+            }
+            _ => out.push(t!("usage", "/link <group> [duration] [uses]")),
+        }
+    } else if let Some(group) = line.strip_prefix("/invites ") {
+        let group = group.trim();
+        if group.is_empty() {
+            out.push(t!("usage", "/invites <group>"));
         } else {
-            out.push("[error] uso: /invite <group> <nick>".into());
+            let _ = labels
+                .send(
+                    client,
+                    ClientToServer::ListInvites {
+                        group: group.into(),
+                    },
+                    line,
+                )
+                .await;
+        }
+    } else if let Some(code) = line.strip_prefix("/revoke ") {
+        let code = code.trim();
+        if code.is_empty() {
+            out.push(t!("usage", "/revoke <code>"));
+        } else {
+            let _ = labels
+                .send(
+                    client,
+                    ClientToServer::RevokeInvite { code: code.into() },
+                    line,
+                )
+                .await;
         }
     } else if let Some(rest) = line.strip_prefix("/join ") {
         let mut it = rest.splitn(2, ' ');
         if let (Some(group), Some(code)) = (it.next(), it.next()) {
-            let mut wh = writer_half.lock().await;
-            let _ = send(
-                &mut *wh,
-                &ClientToServer::JoinGroup {
-                    group: group.into(),
-                    invite_code: code.into(),
-                },
-            ).await;
+            let _ = labels
+                .send(
+                    client,
+                    ClientToServer::JoinGroup {
+                        group: group.into(),
+                        invite_code: code.into(),
+                    },
+                    line,
+                )
+                .await;
         } else {
-            out.push("[error] uso: /join <group> <code>".into());
+            out.push(t!("usage", "/join <group> <code>"));
         }
     } else if let Some(group) = line.strip_prefix("/leave ") {
         let group = group.trim();
         if group.is_empty() {
-            out.push("[error] uso: /leave <group>".into());
+            out.push(t!("usage", "/leave <group>"));
         } else {
-            let mut wh = writer_half.lock().await;
-            let _ = send(
-                &mut *wh,
-                &ClientToServer::LeaveGroup {
-                    group: group.into(),
-                },
-            ).await;
+            let _ = labels
+                .send(
+                    client,
+                    ClientToServer::LeaveGroup {
+                        group: group.into(),
+                    },
+                    line,
+                )
+                .await;
+        }
+    } else if let Some((cmd, rest)) = line
+        .split_once(' ')
+        .filter(|(cmd, _)| matches!(*cmd, "/kick" | "/promote" | "/demote" | "/transfer"))
+    {
+        // comandi di gestione dei ruoli: tutti nella forma /<cmd> <group> <nick>
+        let mut it = rest.split_whitespace();
+        if let (Some(group), Some(nick)) = (it.next(), it.next()) {
+            let (group, nick) = (group.to_string(), nick.to_string());
+            let msg = match cmd {
+                "/kick" => ClientToServer::Kick { group, nick },
+                "/promote" => ClientToServer::Promote { group, nick },
+                "/demote" => ClientToServer::Demote { group, nick },
+                _ => ClientToServer::TransferOwnership { group, nick },
+            };
+            let _ = labels.send(client, msg, line).await;
+        } else {
+            out.push(t!("usage", format!("{} <group> <nick>", cmd)));
+        }
+    } else if let Some(rest) = line.strip_prefix("/rename ") {
+        let mut it = rest.split_whitespace();
+        match (it.next(), it.next(), it.next()) {
+            (Some(group), Some(new_name), None) => {
+                let _ = labels
+                    .send(
+                        client,
+                        ClientToServer::RenameGroup {
+                            group: group.into(),
+                            new_name: new_name.into(),
+                        },
+                        line,
+                    )
+                    .await;
+            }
+            _ => out.push(t!("usage", "/rename <group> <name>")),
         }
     } else if line == "/users" {
-        let mut wh = writer_half.lock().await;
-        let _ = send(&mut *wh, &ClientToServer::ListUsers).await;
+        let _ = labels.send(client, ClientToServer::ListUsers, line).await;
     } else if line == "/groups" {
-        let mut wh = writer_half.lock().await;
-        let _ = send(&mut *wh, &ClientToServer::ListGroups).await;
+        let _ = labels.send(client, ClientToServer::ListGroups, line).await;
     } else if let Some(rest) = line.strip_prefix("/msg ") {
         let mut it = rest.splitn(2, ' ');
         if let (Some(group), Some(text)) = (it.next(), it.next()) {
-            let mut wh = writer_half.lock().await;
-            let _ = send(
-                &mut *wh,
-                &ClientToServer::SendMessage {
-                    group: group.into(),
-                    text: text.into(),
-                    nick: my_nick.to_string(),
-                },
-            ).await;
+            let res = labels
+                .send(
+                    client,
+                    ClientToServer::SendMessage {
+                        group: group.into(),
+                        text: text.into(),
+                    },
+                    line,
+                )
+                .await;
+            check_size(res, &mut out);
+        } else {
+            out.push(t!("usage", "/msg <group> <text>"));
+        }
+    } else if let Some(rest) = line.strip_prefix("/dm ") {
+        let mut it = rest.splitn(2, ' ');
+        if let (Some(to), Some(text)) = (it.next(), it.next()) {
+            let res = labels
+                .send(
+                    client,
+                    ClientToServer::DirectMessage {
+                        to: to.into(),
+                        text: text.into(),
+                    },
+                    line,
+                )
+                .await;
+            // il server non rimanda il messaggio al mittente: lo mostriamo localmente
+            if check_size(res, &mut out) {
+                out.push(format!("[dm] -> <{}> {}", to, text));
+            }
         } else {
-            out.push("[error] uso: /msg <group> <text>".into());
+            out.push(t!("usage", "/dm <nick> <text>"));
+        }
+    } else if let Some(rest) = line.strip_prefix("/history ") {
+        let mut it = rest.split_whitespace();
+        let group = it.next();
+        let limit = it.next().map(|n| n.parse::<u32>());
+        let before = it.next().map(|id| id.parse::<u64>());
+        match (group, limit.transpose(), before.transpose()) {
+            (Some(group), Ok(limit), Ok(before)) => {
+                let _ = labels
+                    .send(
+                        client,
+                        ClientToServer::History {
+                            group: group.into(),
+                            before,
+                            limit,
+                        },
+                        line,
+                    )
+                    .await;
+            }
+            _ => out.push(t!("usage", "/history <group> [n] [id]")),
+        }
+    } else if let Some(rest) = line.strip_prefix("/op ") {
+        match parse_mod(rest) {
+            Some(action) => {
+                let _ = labels
+                    .send(client, ClientToServer::Moderate { action }, line)
+                    .await;
+            }
+            None => out.push(t!(
+                "usage",
+                "/op kick|ban <nick> [reason] | unban <nick> | banip <ip|nick> [reason] | unbanip <ip> | mute <nick> <seconds> | unmute <nick>"
+            )),
         }
     } else if line.starts_with('/') {
-        out.push("[error] comando errato".into());
+        out.push(t!("usage.bad_command"));
     } else {
-        let mut wh = writer_half.lock().await;
-        let _ = send(
-            &mut *wh,
-            &ClientToServer::GlobalMessage {
-                text: line.to_string(),
-            },
-        ).await;
+        let res = labels
+            .send(
+                client,
+                ClientToServer::GlobalMessage {
+                    text: line.to_string(),
+                },
+                line,
+            )
+            .await;
+        check_size(res, &mut out);
     }
     Ok(out)
 }
+
+// Un messaggio oltre la dimensione massima non viene inviato: lo si segnala e si restituisce false.
+// Gli altri errori (connessione persa) sono gestiti dal task di lettura
+fn check_size(res: anyhow::Result<()>, out: &mut Vec<String>) -> bool {
+    match res
+        .as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<FrameError>())
+    {
+        Some(FrameError::TooLarge { max }) => {
+            out.push(t!("msg.too_long", max));
+            false
+        }
+        _ => true,
+    }
+}
+
+// Durata di un invito: secondi, oppure un numero seguito da m (minuti), h (ore) o d (giorni).
+// None se non valida; Some(None) per "-" (default del server), es. /link team - 5
+fn parse_duration(s: &str) -> Option<Option<u64>> {
+    if s == "-" {
+        return Some(None);
+    }
+    let (num, unit) = match s.char_indices().last()? {
+        (i, 's') => (&s[..i], 1),
+        (i, 'm') => (&s[..i], 60),
+        (i, 'h') => (&s[..i], 60 * 60),
+        (i, 'd') => (&s[..i], 24 * 60 * 60),
+        _ => (s, 1),
+    };
+    num.parse::<u64>().ok()?.checked_mul(unit).map(Some)
+}
+
+// Azione di /op: il primo argomento è il nick (o l'indirizzo), il resto il motivo o la durata
+fn parse_mod(rest: &str) -> Option<ModAction> {
+    let mut it = rest.trim().splitn(3, ' ');
+    let (action, nick) = (it.next()?, it.next()?.to_string());
+    let reason = it
+        .next()
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(String::from);
+    let action = match action {
+        "kick" => ModAction::Kick { nick, reason },
+        "ban" => ModAction::Ban { nick, reason },
+        "unban" => ModAction::Unban { nick },
+        "banip" => ModAction::BanIp {
+            target: nick,
+            reason,
+        },
+        "unbanip" => ModAction::UnbanIp { ip: nick },
+        "mute" => ModAction::Mute {
+            nick,
+            seconds: reason?.parse().ok()?,
+        },
+        "unmute" => ModAction::Unmute { nick },
+        _ => return None,
+    };
+    Some(action)
+}
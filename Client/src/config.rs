@@ -0,0 +1,35 @@
+/*
+Modulo Config: parametri di connessione della libreria client (indirizzo, TLS, formato dei messaggi,
+ping e timeout). L'interfaccia testuale li ricava dagli argomenti da linea di comando (vedi args.rs).
+*/
+
+use ruggine_common::{Codec, MAX_FRAME_LEN};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub server: String,           // indirizzo del server, es. 127.0.0.1:7000
+    pub tls: bool,                // connessione cifrata con TLS
+    pub ca: Option<String>,       // certificato della CA (PEM) con cui verificare il server
+    pub tls_name: Option<String>, // nome atteso nel certificato (default: host di `server`)
+    pub codec: Codec,             // formato proposto al server con Hello
+    pub max_frame: usize,         // dimensione massima di un messaggio, in lettura e in scrittura
+    pub heartbeat: Duration,      // intervallo tra i ping automatici
+    pub timeout: Duration, // silenzio del server oltre cui la connessione è persa; attesa massima delle risposte
+}
+
+impl Config {
+    // Connessione in chiaro con i valori predefiniti dell'interfaccia testuale
+    pub fn new(server: impl Into<String>) -> Self {
+        Self {
+            server: server.into(),
+            tls: false,
+            ca: None,
+            tls_name: None,
+            codec: Codec::Json,
+            max_frame: MAX_FRAME_LEN,
+            heartbeat: Duration::from_secs(15),
+            timeout: Duration::from_secs(45),
+        }
+    }
+}
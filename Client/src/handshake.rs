@@ -1,33 +1,30 @@
 /*
-Modulo Handshake: gestisce la fase di registrazione e handshake tra client e server.
-Effettua il login e gestisce la negoziazione del nickname.
+Modulo Handshake: gestisce la fase di accesso interattivo al server.
+La connessione è già stata aperta e negoziata (vedi ruggine_client::Connection): qui si chiedono
+nickname e password ed effettua l'accesso (o la creazione dell'account), ritentando se rifiutato.
 */
 
-use ruggine_common::{ClientToServer, ServerToClient};
-use tokio::io::{BufReader, Lines};
-use tokio::net::tcp::OwnedReadHalf;
-use tokio::net::tcp::OwnedWriteHalf;
-use uuid::Uuid;
+use ruggine_client::catalog::t;
+use ruggine_client::Connection;
+use ruggine_common::ErrorCode;
 
 use crate::args::Args;
-use crate::net::send;
-use crate::terminal::prompt_nick;
+use crate::terminal::{prompt_nick, prompt_password, prompt_yes_no};
 
-// Registrazione con retry finché il nick è accettato
+// Accesso con retry finché nickname e password sono accettati; restituisce i messaggi da mostrare.
+// `relogin` è il nickname di una sessione scaduta: al primo tentativo si accede a quell'account
 pub async fn register_handshake(
     args: &Args,
-    // due metà di una connessione TCP asincrona gestita da Tokio
-    writer: &mut OwnedWriteHalf, // invio di messaggi al server
-    reader: &mut Lines<BufReader<OwnedReadHalf>>,
-    // metà di lettura incapsulata in in un buffer ed in un iteratore di linee
-    // di modo da gestire la lettura linea per linea
-) -> anyhow::Result<(Uuid, String, Vec<String>)> { // client_id, nick, msgs
+    conn: &mut Connection,
+    relogin: Option<&str>,
+) -> anyhow::Result<Vec<String>> {
+    let mut first_attempt = true;
     loop {
-
-        // se passo negli Args il nick
-        let nick: String = match &args.nick {
-            Some(n) => n.trim().to_string(),
-            None => {
+        // se passo negli Args il nick (vale solo per il primo tentativo)
+        let nick: String = match (relogin, &args.nick) {
+            (Some(n), _) if first_attempt => n.to_string(),
+            (None, Some(n)) if first_attempt => n.trim().to_string(),
+            _ => {
                 // Disabilita la raw mode prima di chiedere il nick
                 // RAW MODE: ripristina il comportamento normale del terminale
                 // visualizzazione caratteri, caratteri speciali
@@ -36,53 +33,36 @@ pub async fn register_handshake(
             }
         };
 
-        let client_id = Uuid::new_v4(); // creazione id randomico
-        send(
-            writer,
-            &ClientToServer::Register {
-                nick: nick.clone(),
-                client_id,
-            },
-        ).await?;
-
-        // Aspetta una risposta
-        let line = match reader.next_line().await? {
-            Some(l) => l,
-            None => anyhow::bail!("Connessione chiusa durante la registrazione"),
-            // bail! interrompe l'esecuzione della funzione ma non causa l'arresto
-            // del programma come la macro panic!
+        // Nuovo account (--new-account o risposta affermativa) oppure accesso
+        let _ = crossterm::terminal::disable_raw_mode();
+        let create = match relogin {
+            Some(_) if first_attempt => false,
+            _ => {
+                (args.new_account && first_attempt)
+                    || (!args.new_account && prompt_yes_no(&t!("login.ask_new_account"))?)
+            }
         };
+        first_attempt = false;
 
-        match serde_json::from_str::<ServerToClient>(&line) {
-            Ok(ServerToClient::Registered { ok, reason }) => {
-                if ok {
-                    let mut msgs = Vec::new();
-                    msgs.push(format!("[server] utente {} loggato correttamente", nick));
-                    msgs.push("[server] Per visualizzare il menu invia '/' ".to_string());
-                    return Ok((client_id, nick, msgs));
-                } else {
-                    // Se il campo reason (Option<String>) contiene un valore (Some),
-                    // viene usato quel valore. Se invece è None (cioè il server non ha fornito
-                    // una motivazione), viene restituita la stringa di default "motivo sconosciuto"
-                    eprintln!(
-                        "[server] Registrazione rifiutata: {}",
-                        reason.unwrap_or_else(|| "motivo sconosciuto".into())
-                    );
-                }
-            }
-            Ok(other) => {
-                eprintln!(
-                    "[server] risposta inattesa durante la registrazione: {:?}",
-                    other
-                );
+        let password = prompt_password(&t!("login.password"))?;
+        let res = if create {
+            if prompt_password(&t!("login.confirm_password"))? != password {
+                eprintln!("{}", t!("login.password_mismatch"));
+                continue;
             }
-            Err(e) => {
-                eprintln!("Parse della risposta di registrazione fallito: {e}");
-            }
-        }
+            conn.create_account(&nick, &password).await
+        } else {
+            conn.login(&nick, &password).await
+        };
 
-        if args.nick.is_some() {
-            // se --nick era passato ma rifiutato, la prossima iterazione chiederà interattivamente
+        match res {
+            Ok(()) => return Ok(vec![t!("login.ok", nick), t!("login.menu_hint")]),
+            // Rifiuto del server: il testo si ricava dal codice dell'errore, nella lingua del client.
+            // Gli altri errori (connessione chiusa, risposta inattesa) interrompono l'accesso
+            Err(e) => match e.downcast_ref::<ErrorCode>() {
+                Some(code) => eprintln!("{}", t!("login.rejected", code)),
+                None => return Err(e),
+            },
         }
     }
 }
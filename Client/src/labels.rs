@@ -0,0 +1,47 @@
+/*
+Modulo Labels: ricorda il comando digitato per ogni richiesta inviata al server, così un errore
+può indicare a quale comando si riferisce, es. "[error] (/invite team bob) motivo".
+*/
+
+use ruggine_client::RuggineClient;
+use ruggine_common::ClientToServer;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// Comandi ricordati al massimo: quelli riusciti senza risposta (es. /msg) non vengono mai rimossi
+const MAX_LABELS: usize = 256;
+
+#[derive(Default)]
+pub struct Labels {
+    labels: Mutex<BTreeMap<u64, String>>,
+}
+
+impl Labels {
+    // Invia un comando ricordando `label` per le sue risposte.
+    // L'id viene registrato prima dell'invio, così la risposta non può arrivare prima dell'etichetta
+    pub async fn send(
+        &self,
+        client: &RuggineClient,
+        msg: ClientToServer,
+        label: &str,
+    ) -> anyhow::Result<()> {
+        let id = client.next_id();
+        {
+            let mut labels = self.labels.lock().expect("etichette avvelenate");
+            labels.insert(id, label.to_string());
+            // gli id crescono: si scartano i comandi più vecchi
+            while labels.len() > MAX_LABELS {
+                labels.pop_first();
+            }
+        }
+        client.send_with_id(id, msg).await
+    }
+
+    // Comando che ha generato la risposta alla richiesta `id`
+    pub fn take(&self, id: u64) -> Option<String> {
+        self.labels
+            .lock()
+            .expect("etichette avvelenate")
+            .remove(&id)
+    }
+}
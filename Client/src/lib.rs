@@ -0,0 +1,22 @@
+/*
+Libreria del client Ruggine: connessione al server (TCP o TLS), negoziazione del protocollo, accesso
+e un `RuggineClient` con un metodo per ogni comando, più lo Stream degli eventi ricevuti dal server.
+Su questa libreria è costruita l'interfaccia testuale (main.rs); la possono usare anche bot e test.
+
+Esempio:
+    let mut conn = Connection::open(&Config::new("127.0.0.1:7000")).await?;
+    conn.login("bot", "password1").await?;
+    let (client, mut events) = conn.start();
+    client.create_group("rust").await?;
+    while let Some(event) = events.next().await { ... }
+*/
+
+pub mod catalog;
+mod client;
+mod config;
+mod net;
+mod requests;
+mod tls;
+
+pub use client::{Connection, Event, Events, RuggineClient};
+pub use config::Config;
@@ -1,30 +1,27 @@
 /*
 Entry point del client chat.
 Inizializza la connessione, effettua l'handshake e avvia l'interfaccia utente.
+La logica del protocollo sta nella libreria (lib.rs); qui c'è solo l'interfaccia testuale.
 */
 
 mod args;
 mod commands;
 mod handshake;
+mod labels;
 mod messages;
-mod net;
 mod terminal;
 mod ui;
 
 use args::Args;
 use clap::Parser; // per Args::parse
 use handshake::register_handshake;
+use ruggine_client::Connection;
+use ruggine_common::Lang;
 use std::sync::Arc;
 use terminal::restore_terminal;
-use tokio::io::AsyncBufReadExt;
-use tokio::io::BufReader;
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
-use uuid::Uuid;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-
     // Per questioni di sicurezza, si definisce un panic hook: funzione eseguita automaticamente
     // qualora dovesse verificarsi un panic
     std::panic::set_hook(Box::new(|info| {
@@ -35,24 +32,25 @@ async fn main() -> anyhow::Result<()> {
 
     // Parse degli args
     let args = Args::parse();
+    // Lingua dei testi mostrati: --lang oppure quella dell'ambiente
+    ruggine_common::i18n::set_lang(args.lang.unwrap_or_else(Lang::from_env));
 
-    // Connessione allo StreamTcp sul server
-    let stream = TcpStream::connect(&args.server).await?;
-
-    // Split dello stream in canale di lettura e scrittura
-    let (reader_half, writer_half) = stream.into_split();
+    // Nickname della sessione scaduta: al nuovo accesso si chiede solo la password
+    let mut relogin: Option<String> = None;
+    loop {
+        // Connessione al server (TCP in chiaro oppure TLS con --tls) e negoziazione del protocollo
+        let mut conn = Connection::open(&args.config()).await?;
 
-    // Incapsulamento del canale di scrittura in un Mutex ed in un Arc
-    let writer_half = Arc::new(Mutex::new(writer_half));
-    // Incapsulamento del canale di lettura in un Buffer
-    let mut reader_lines = BufReader::new(reader_half).lines();
-
-    let mut wh = writer_half.lock().await;
-    let (_client_id, my_nick, handshake_msgs): (Uuid, String, Vec<String>) =
         // Gestione della fase di registrazione, definita in handshake.rs
-        register_handshake(&args, &mut *wh, &mut reader_lines).await?;
-    drop(wh);
-
-    // Esecuzione del loop di gestione interfaccia utente, definita in ui.rs
-    ui::run_ui(reader_lines, writer_half, my_nick, handshake_msgs).await
+        let handshake_msgs = register_handshake(&args, &mut conn, relogin.as_deref()).await?;
+
+        // Avvio dei task del client (lettura, ping, riconnessione) e del loop dell'interfaccia utente
+        let (client, events) = conn.start();
+        let client = Arc::new(client);
+        match ui::run_ui(Arc::clone(&client), events, handshake_msgs).await? {
+            ui::Exit::Quit => return Ok(()),
+            // la libreria non conserva la password: la si chiede di nuovo all'utente
+            ui::Exit::SessionExpired(_) => relogin = Some(client.nick().to_string()),
+        }
+    }
 }
@@ -3,45 +3,157 @@ Modulo Messages: si occupa di formattare e visualizzare i messaggi ricevuti dal
 Traduce le strutture ServerToClient in stringhe leggibili per l'utente.
 */
 
-use ruggine_common::ServerToClient;
+use chrono::{DateTime, Local};
+use ruggine_common::{HistoryEntry, InviteEntry, ServerToClient};
+
+use ruggine_client::catalog::t;
 
 pub fn render(msg: ServerToClient) -> String {
     match msg {
-        ServerToClient::Registered { ok, reason } => {
-            format!("[server] registrazione: ok={} {:?}", ok, reason)
+        ServerToClient::Registered { ok, reason, .. } => {
+            t!("msg.registered", ok, reason.unwrap_or_default())
         }
         ServerToClient::InviteCode {
             group,
             code,
             client_id,
-        } => format!(
-            "[server] codice invito per il gruppo '{}': {} da {}",
-            group, code, client_id
+            expires_at,
+        } => with_expiry(
+            t!("msg.invite_code_from", group, code, client_id),
+            expires_at,
         ),
-        ServerToClient::InviteCodeForMe { group, code } => {
-            format!("[server] codice invito per il gruppo '{}': {}", group, code)
+        ServerToClient::InviteCodeForMe {
+            group,
+            code,
+            expires_at,
+        } => with_expiry(t!("msg.invite_code", group, code), expires_at),
+        ServerToClient::InviteLink {
+            group,
+            code,
+            expires_at,
+            max_uses,
+        } => {
+            let uses = max_uses.map_or_else(|| t!("msg.invite_unlimited"), |n| n.to_string());
+            with_expiry(t!("msg.invite_link", group, code, uses), expires_at)
+        }
+        ServerToClient::Invites { group, invites } => {
+            t!("msg.invites_count", invites.len(), group)
         }
         ServerToClient::Joined { group } => {
-            format!("[server] sei entrato nel gruppo '{}'", group)
+            t!("msg.joined", group)
         }
         ServerToClient::Left { group } => {
-            format!("[server] sei uscito dal gruppo '{}'", group)
+            t!("msg.left", group)
         }
         ServerToClient::Message { group, from, text } => {
             format!("[{}] <{}> {}", group, from, text)
         }
         ServerToClient::MessageServer { text } => format!("[server] {}", text),
         ServerToClient::Groups { groups } => {
-            format!("[server] Gruppi di appartenenza: {:?}", groups)
+            t!("msg.groups", format!("{:?}", groups))
         }
-        ServerToClient::ListUsers { users } => format!("[server] Users: {:?}", users),
-        ServerToClient::Error { reason } => format!("[error] {}", reason),
+        ServerToClient::ListUsers { users } => t!("msg.users", format!("{:?}", users)),
+        // il testo viene prodotto dal codice dell'errore, nella lingua del client
+        ServerToClient::Error { code, .. } => t!("ui.error", code),
         ServerToClient::Pong => "[server] pong".to_string(),
+        ServerToClient::Welcome {
+            protocol_version,
+            features,
+        } => t!("msg.welcome", protocol_version, features.join(", ")),
         ServerToClient::GlobalMessage { from, text } => {
-            format!("[globale] <{}> {}", from, text)
+            t!("msg.global", from, text)
+        }
+        ServerToClient::DirectMessage { from, text } => {
+            format!("[dm] <{}> {}", from, text)
         }
         ServerToClient::GroupCreated { group } => {
-            format!("[server] gruppo '{}' creato correttamente!", group)
+            t!("msg.group_created", group)
         }
+        ServerToClient::GroupRenamed {
+            group,
+            new_name,
+            by,
+        } => t!("msg.group_renamed", by, group, new_name),
+        ServerToClient::HistoryPage {
+            group, messages, ..
+        } => t!("msg.history_count", messages.len(), group),
+        ServerToClient::ServerShutdown {
+            reason,
+            reconnect_after: Some(secs),
+        } => t!("msg.shutdown_reconnect", reason, secs),
+        ServerToClient::ServerShutdown { reason, .. } => t!("msg.shutdown", reason),
+    }
+}
+
+// Come render, ma la cronologia viene espansa in un blocco di più righe
+pub fn render_lines(msg: ServerToClient) -> Vec<String> {
+    match msg {
+        ServerToClient::HistoryPage {
+            group,
+            messages,
+            has_more,
+        } => render_history(&group, &messages, has_more),
+        ServerToClient::Invites { group, invites } => render_invites(&group, &invites),
+        other => vec![render(other)],
+    }
+}
+
+// Inviti del gruppo, uno per riga: codice, destinatario (o link aperto), autore, usi e scadenza
+fn render_invites(group: &str, invites: &[InviteEntry]) -> Vec<String> {
+    if invites.is_empty() {
+        return vec![t!("invites.empty", group)];
+    }
+    let mut out = vec![t!("invites.header", group)];
+    for i in invites {
+        let target = i.nick.clone().unwrap_or_else(|| t!("invites.link"));
+        let uses = match i.max_uses {
+            Some(max) => format!("{}/{}", i.uses, max),
+            None => i.uses.to_string(),
+        };
+        let expires = i
+            .expires_at
+            .map_or_else(|| t!("invites.never"), format_time);
+        out.push(t!(
+            "invites.line",
+            i.code,
+            target,
+            i.created_by,
+            uses,
+            expires
+        ));
+    }
+    out
+}
+
+// Aggiunge la scadenza di un codice invito, se prevista
+fn with_expiry(text: String, expires_at: Option<i64>) -> String {
+    match expires_at {
+        Some(ts) => t!("msg.invite_expires", text, format_time(ts)),
+        None => text,
+    }
+}
+
+fn format_time(ts: i64) -> String {
+    DateTime::from_timestamp_millis(ts)
+        .map(|t| t.with_timezone(&Local).format("%d/%m %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+// Blocco della cronologia delimitato da intestazione e chiusura, ogni riga con prefisso [storico]/[history]
+fn render_history(group: &str, messages: &[HistoryEntry], has_more: bool) -> Vec<String> {
+    let mut out = Vec::with_capacity(messages.len() + 3);
+    if messages.is_empty() {
+        out.push(t!("history.empty", group));
+        return out;
+    }
+    out.push(t!("history.header", group));
+    if has_more {
+        // l'id del messaggio più vecchio serve per chiedere la pagina precedente
+        out.push(t!("history.more", group, messages[0].id));
+    }
+    for m in messages {
+        out.push(t!("history.line", format_time(m.ts), group, m.from, m.text));
     }
+    out.push(t!("history.footer"));
+    out
 }
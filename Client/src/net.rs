@@ -1,16 +1,156 @@
 /*
-Modulo Net: gestisce l'invio di messaggi dal client al server tramite la connessione TCP.
+Modulo Net: gestisce la connessione al server (TCP in chiaro o TLS) e l'invio dei messaggi.
 Serializza i dati e li trasmette in formato NDJSON.
-NDJSON (Newline Delimited JSON): Ogni riga è un JSON valido e le righe sono separate da 
-un carattere di newline (\n).
+NDJSON (Newline Delimited JSON): Ogni riga è un JSON valido e le righe sono separate da
+un carattere di newline (\n). Con il codec MessagePack, dopo la negoziazione si usano frame binari
+(vedi ruggine_common::codec).
 */
 
-use ruggine_common::ClientToServer;
-use tokio::io::AsyncWriteExt;
-use tokio::net::tcp::OwnedWriteHalf;
+use anyhow::Context;
+use ruggine_common::{
+    ClientToServer, Codec, Envelope, FrameReader, FrameWriter, ServerToClient,
+    FEATURE_DIRECT_MESSAGES, FEATURE_HISTORY, FEATURE_INVITE_LINKS, FEATURE_MODERATION,
+    FEATURE_MSGPACK, FEATURE_RENAME_GROUP, FEATURE_RESUME, FEATURE_ROLES, PROTOCOL_VERSION,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
 
-pub async fn send(writer: &mut OwnedWriteHalf, msg: &ClientToServer) -> anyhow::Result<()> {
-    let data = serde_json::to_string(msg)? + "\n"; // Serializza in formato NDJSON
-    writer.write_all(data.as_bytes()).await?;
+use crate::catalog::t;
+use crate::config::Config;
+use crate::tls;
+
+// Funzionalità che questo client sa usare
+const CLIENT_FEATURES: &[&str] = &[
+    FEATURE_HISTORY,
+    FEATURE_DIRECT_MESSAGES,
+    FEATURE_ROLES,
+    FEATURE_RESUME,
+    FEATURE_MODERATION,
+    FEATURE_INVITE_LINKS,
+    FEATURE_RENAME_GROUP,
+];
+
+// Metà di lettura e scrittura della connessione, indipendenti dal trasporto usato
+pub type ServerReader = FrameReader<Box<dyn AsyncRead + Send + Unpin>>;
+pub type ServerWriter = FrameWriter<Box<dyn AsyncWrite + Send + Unpin>>;
+
+pub async fn connect(config: &Config) -> anyhow::Result<(ServerReader, ServerWriter)> {
+    // Connessione allo StreamTcp sul server
+    let stream = TcpStream::connect(&config.server).await?;
+
+    if !config.tls {
+        // Split dello stream in canale di lettura e scrittura
+        let (r, w) = stream.into_split();
+        return Ok(frames(config, Box::new(r), Box::new(w)));
+    }
+
+    // Il nome atteso nel certificato è l'host dell'indirizzo (senza porta), se non indicato con --tls-name
+    let host = match &config.tls_name {
+        Some(name) => name.clone(),
+        None => config
+            .server
+            .rsplit_once(':')
+            .map_or(config.server.as_str(), |(h, _)| h)
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_string(),
+    };
+    let server_name =
+        ServerName::try_from(host.clone()).with_context(|| t!("conn.bad_tls_name", host))?;
+    let stream = tls::connector(config.ca.as_deref())?
+        .connect(server_name, stream)
+        .await
+        .context(t!("conn.tls_failed"))?;
+    let (r, w) = tokio::io::split(stream);
+    Ok(frames(config, Box::new(r), Box::new(w)))
+}
+
+// Le due metà partono in NDJSON; il codec può cambiare dopo la negoziazione (vedi negotiate)
+fn frames(
+    config: &Config,
+    r: Box<dyn AsyncRead + Send + Unpin>,
+    w: Box<dyn AsyncWrite + Send + Unpin>,
+) -> (ServerReader, ServerWriter) {
+    (
+        FrameReader::new(r, config.max_frame),
+        FrameWriter::new(w, config.max_frame),
+    )
+}
+
+// Invia Hello e attende Welcome; restituisce le funzionalità concordate con il server.
+// Con il codec MessagePack si propone anche il formato binario: se il server lo accetta, entrambe
+// le metà della connessione passano a MessagePack subito dopo il Welcome.
+// Se il server rifiuta la versione restituisce un errore con il motivo da mostrare all'utente
+pub async fn negotiate(
+    config: &Config,
+    writer: &mut ServerWriter,
+    reader: &mut ServerReader,
+) -> anyhow::Result<Vec<String>> {
+    let mut features: Vec<String> = CLIENT_FEATURES.iter().map(|f| f.to_string()).collect();
+    if config.codec == Codec::MsgPack {
+        features.push(FEATURE_MSGPACK.to_string());
+    }
+    send(
+        writer,
+        &ClientToServer::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            features,
+        },
+    )
+    .await?;
+    while let Some(frame) = reader.next_frame().await? {
+        match reader.codec().decode::<ServerToClient>(&frame) {
+            Ok(ServerToClient::Welcome { features, .. }) => {
+                let codec = Codec::negotiated(&features);
+                reader.set_codec(codec);
+                writer.set_codec(codec);
+                return Ok(features);
+            }
+            Ok(ServerToClient::Error { code, .. }) => {
+                anyhow::bail!(t!("conn.rejected", code))
+            }
+            // un server molto vecchio non conosce Welcome: il messaggio non si riesce a interpretare
+            Ok(_) | Err(_) => {}
+        }
+    }
+    anyhow::bail!(t!("conn.closed_negotiation"))
+}
+
+// Nuova connessione che riprende la sessione `session` dell'utente `nick`.
+// Restituisce None se il server rifiuta il token (sessione scaduta): inutile riprovare
+pub async fn resume(
+    config: &Config,
+    nick: &str,
+    session: &str,
+) -> anyhow::Result<Option<(ServerReader, ServerWriter)>> {
+    let (mut reader, mut writer) = connect(config).await?;
+    negotiate(config, &mut writer, &mut reader).await?;
+    send(
+        &mut writer,
+        &ClientToServer::Resume {
+            nick: nick.to_string(),
+            session: session.to_string(),
+        },
+    )
+    .await?;
+    while let Some(frame) = reader.next_frame().await? {
+        if let Ok(ServerToClient::Registered { ok, .. }) = reader.codec().decode(&frame) {
+            return Ok(ok.then_some((reader, writer)));
+        }
+    }
+    anyhow::bail!(t!("conn.closed_resume"))
+}
+
+pub async fn send(writer: &mut ServerWriter, msg: &ClientToServer) -> anyhow::Result<()> {
+    send_envelope(writer, &Envelope::new(None, msg.clone())).await
+}
+
+// Come `send`, con l'eventuale id della richiesta che il server ripeterà nelle risposte
+pub async fn send_envelope(
+    writer: &mut ServerWriter,
+    msg: &Envelope<ClientToServer>,
+) -> anyhow::Result<()> {
+    // Serializza in formato NDJSON (o MessagePack) e invia subito, anche con TLS
+    writer.send(msg).await?;
     Ok(())
 }
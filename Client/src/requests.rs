@@ -0,0 +1,83 @@
+/*
+Modulo Requests: associa le risposte del server ai comandi che le hanno generate.
+Ogni comando viene inviato con un id (vedi Envelope); il server lo ripete nelle risposte dirette.
+Chi usa `call` attende la risposta al proprio comando; tutte le altre diventano eventi del client.
+*/
+
+use ruggine_common::{ClientToServer, Envelope, ServerToClient};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex};
+
+use crate::catalog::t;
+use crate::client::Event;
+use crate::net::{send_envelope, ServerWriter};
+
+#[derive(Default)]
+pub struct Requests {
+    next_id: AtomicU64,
+    // richieste di cui qualcuno attende la risposta
+    pending: StdMutex<BTreeMap<u64, oneshot::Sender<ServerToClient>>>,
+}
+
+impl Requests {
+    // Id della prossima richiesta, sempre crescente
+    pub fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    // Invia un comando e attende la prima risposta diretta, al più per `wait`
+    pub async fn call(
+        &self,
+        writer: &Mutex<ServerWriter>,
+        msg: ClientToServer,
+        wait: Duration,
+    ) -> anyhow::Result<ServerToClient> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let id = self.next_id();
+        // registrata prima dell'invio, così la risposta non può arrivare prima di chi la attende
+        self.pending
+            .lock()
+            .expect("richieste avvelenate")
+            .insert(id, reply_tx);
+        let sent = {
+            let mut wh = writer.lock().await;
+            send_envelope(&mut wh, &Envelope::new(Some(id), msg)).await
+        };
+        let reply = match sent {
+            Ok(()) => tokio::time::timeout(wait, reply_rx).await.ok(),
+            Err(_) => None,
+        };
+        self.pending
+            .lock()
+            .expect("richieste avvelenate")
+            .remove(&id);
+        sent?;
+        match reply {
+            Some(Ok(reply)) => Ok(reply),
+            _ => anyhow::bail!(t!("conn.no_reply", id)),
+        }
+    }
+
+    // Smista un messaggio ricevuto: se qualcuno lo attende gli viene consegnato (None),
+    // altrimenti diventa un evento con l'id del comando a cui risponde
+    pub fn resolve(&self, reply: Envelope<ServerToClient>) -> Option<Event> {
+        if let Some(id) = reply.id {
+            let waiter = self
+                .pending
+                .lock()
+                .expect("richieste avvelenate")
+                .remove(&id);
+            if let Some(tx) = waiter {
+                let _ = tx.send(reply.body);
+                return None;
+            }
+        }
+        Some(Event::Message {
+            msg: reply.body,
+            request: reply.id,
+        })
+    }
+}
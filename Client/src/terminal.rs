@@ -1,9 +1,11 @@
 /*
-Modulo Terminal: gestisce le operazioni sul terminale locale, come la modalità raw, il prompt del nickname e il ripristino dello stato.
+Modulo Terminal: gestisce le operazioni sul terminale locale, come la modalità raw, il prompt di nickname e password e il ripristino dello stato.
 */
 
 use std::io::{self, Write};
 
+use ruggine_client::catalog::t;
+
 pub fn restore_terminal() {
     let _ = crossterm::terminal::disable_raw_mode();
     let mut stdout = std::io::stdout();
@@ -11,10 +13,9 @@ pub fn restore_terminal() {
         stdout,
         crossterm::event::DisableMouseCapture,
         crossterm::cursor::Show,
-        crossterm::terminal::LeaveAlternateScreen
-        // uscita dalla "alternate screen" tornando alla schermata principale del terminale
-        // Tutto ciò che era stato scritto nella alternate screen viene perso, tornando
-        // al buffer principale.
+        crossterm::terminal::LeaveAlternateScreen // uscita dalla "alternate screen" tornando alla schermata principale del terminale
+                                                  // Tutto ciò che era stato scritto nella alternate screen viene perso, tornando
+                                                  // al buffer principale.
     );
     // sequenza di escape ANSI che riattiva il "line wrapping", facendo
     // andare automaticamente a capo il testo che supera la larghezza del terminale
@@ -24,15 +25,39 @@ pub fn restore_terminal() {
 
 pub fn prompt_nick() -> anyhow::Result<String> {
     loop {
-        print!("Scegli un nickname: ");
+        print!("{}", t!("prompt.nick"));
         io::stdout().flush()?;
         let mut s = String::new();
         std::io::stdin().read_line(&mut s)?;
         let s = s.trim();
         if s.is_empty() {
-            eprintln!("Il nickname non può essere vuoto.");
+            eprintln!("{}", t!("prompt.nick_empty"));
             continue;
         }
         return Ok(s.to_string());
     }
 }
+
+// Richiesta della password senza mostrare i caratteri digitati
+pub fn prompt_password(label: &str) -> anyhow::Result<String> {
+    loop {
+        let s = rpassword::prompt_password(label)?;
+        if s.is_empty() {
+            eprintln!("{}", t!("prompt.password_empty"));
+            continue;
+        }
+        return Ok(s);
+    }
+}
+
+// Domanda sì/no, la risposta predefinita (invio) è "no"
+pub fn prompt_yes_no(question: &str) -> anyhow::Result<bool> {
+    print!("{}", t!("prompt.yes_no", question));
+    io::stdout().flush()?;
+    let mut s = String::new();
+    std::io::stdin().read_line(&mut s)?;
+    Ok(matches!(
+        s.trim().to_lowercase().as_str(),
+        "s" | "si" | "sì" | "y" | "yes"
+    ))
+}
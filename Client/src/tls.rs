@@ -0,0 +1,125 @@
+/*
+Modulo Tls: prepara il connettore TLS del client.
+Come radici di fiducia usa i certificati pubblici di webpki-roots oppure, con --ca, il certificato
+indicato (utile per server con certificato autofirmato).
+*/
+
+use anyhow::Context;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+pub fn connector(ca_path: Option<&str>) -> anyhow::Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+    match ca_path {
+        Some(path) => {
+            let file = File::open(path).with_context(|| format!("apertura CA {path}"))?;
+            for cert in rustls_pemfile::certs(&mut BufReader::new(file)) {
+                roots
+                    .add(cert.with_context(|| format!("lettura CA {path}"))?)
+                    .context("certificato CA non valido")?;
+            }
+            if roots.is_empty() {
+                anyhow::bail!("nessun certificato trovato in {path}");
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::net;
+    use ruggine_common::{
+        ClientToServer, Envelope, FrameReader, FrameWriter, ServerToClient, MAX_FRAME_LEN,
+        PROTOCOL_VERSION,
+    };
+    use std::net::SocketAddr;
+    use std::path::{Path, PathBuf};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    // Server TLS minimo con certificato autofirmato: risponde a Hello con Welcome.
+    // Restituisce l'indirizzo e il percorso del certificato da usare come CA
+    async fn start_server(dir: &Path) -> (SocketAddr, PathBuf) {
+        let names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(names).unwrap();
+        std::fs::create_dir_all(dir).unwrap();
+        let ca = dir.join("ca.pem");
+        std::fs::write(&ca, cert.pem()).unwrap();
+
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.der().clone()], key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let Ok(stream) = acceptor.accept(socket).await else {
+                    continue;
+                };
+                let (r, w) = tokio::io::split(stream);
+                let mut reader = FrameReader::new(r, MAX_FRAME_LEN);
+                let mut writer = FrameWriter::new(w, MAX_FRAME_LEN);
+                if let Ok(Some(Envelope {
+                    body: ClientToServer::Hello { features, .. },
+                    ..
+                })) = reader.next::<Envelope<ClientToServer>>().await
+                {
+                    let welcome = ServerToClient::Welcome {
+                        protocol_version: PROTOCOL_VERSION,
+                        features,
+                    };
+                    let _ = writer.send(&welcome).await;
+                }
+            }
+        });
+        (addr, ca)
+    }
+
+    #[tokio::test]
+    async fn hello_with_custom_ca() {
+        let dir = std::env::temp_dir().join(format!("ruggine-tls-{}", uuid::Uuid::new_v4()));
+        let (addr, ca) = start_server(&dir).await;
+
+        // il nome atteso è l'host dell'indirizzo, presente tra i nomi del certificato
+        let mut config = Config::new(addr.to_string());
+        config.tls = true;
+        config.ca = Some(ca.to_str().unwrap().to_string());
+        let (mut reader, mut writer) = net::connect(&config).await.unwrap();
+        let features = net::negotiate(&config, &mut writer, &mut reader)
+            .await
+            .unwrap();
+        assert!(!features.is_empty());
+
+        // senza --ca valgono le radici pubbliche, che non conoscono il certificato autofirmato
+        let name = ServerName::try_from("localhost").unwrap();
+        let res = connector(None)
+            .unwrap()
+            .connect(name, TcpStream::connect(addr).await.unwrap())
+            .await;
+        assert!(res.is_err());
+
+        // un file senza certificati non è una CA valida
+        let empty = dir.join("empty.pem");
+        std::fs::write(&empty, "").unwrap();
+        assert!(connector(Some(empty.to_str().unwrap())).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
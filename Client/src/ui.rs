@@ -4,29 +4,38 @@ Gestisce input, output, rendering dei messaggi e interazione con l'utente.
 */
 
 use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use crossterm::{cursor, event, terminal, ExecutableCommand, QueueableCommand};
+use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
 use crossterm::style::{Color, ResetColor, SetForegroundColor};
-use crossterm::event::{KeyCode, KeyModifiers, KeyEventKind};
-use tokio::io::{BufReader, Lines};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::sync::Mutex;
+use crossterm::{cursor, event, terminal, ExecutableCommand, QueueableCommand};
+use futures::StreamExt;
 
+use ruggine_client::catalog::t;
+use ruggine_client::{Event, Events, RuggineClient};
 use ruggine_common::{ClientToServer, ServerToClient};
 
 use crate::commands::handle_command;
-use crate::messages::render;
-use crate::net::send;
+use crate::labels::Labels;
+use crate::messages::render_lines;
 use crate::terminal::restore_terminal;
 
+// Motivo per cui l'interfaccia è terminata
+pub enum Exit {
+    // l'utente è uscito (CTRL+C)
+    Quit,
+    // il server non ricorda più la sessione (es. dopo un riavvio): si torna all'accesso.
+    // Contiene gli ultimi avvisi da mostrare fuori dall'interfaccia
+    SessionExpired(Vec<String>),
+}
+
 pub async fn run_ui(
-    mut reader_lines: Lines<BufReader<OwnedReadHalf>>,
-    writer_half: Arc<Mutex<OwnedWriteHalf>>,
-    my_nick: String,
+    client: Arc<RuggineClient>,
+    mut events: Events,
     handshake_msgs: Vec<String>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<Exit> {
     // Manteniamo un canale per inoltrare i messaggi del server all'interfaccia utente
     let (msg_tx, mut msg_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
 
@@ -38,33 +47,79 @@ pub async fn run_ui(
         let _ = msg_tx.send(m);
     }
 
-    // Task che legge dal server e invia testo formattato sul canale
+    // Diventa false quando il server non risponde più: il prompt lo segnala all'utente
+    let connected = Arc::new(AtomicBool::new(true));
+    // Comando digitato per ogni richiesta, per etichettare gli errori del server
+    let labels = Arc::new(Labels::default());
+    // Avvisato quando la sessione è scaduta, con i testi da mostrare dopo aver chiuso l'interfaccia
+    let (expired_tx, mut expired_rx) = tokio::sync::oneshot::channel::<Vec<String>>();
+
+    // Task che trasforma gli eventi del client in testo formattato e li invia sul canale.
+    // Lettura, ping periodici e riconnessione sono gestiti dalla libreria
     let read_task = {
         let msg_tx = msg_tx.clone();
+        let connected = Arc::clone(&connected);
+        let client = Arc::clone(&client);
+        let labels = Arc::clone(&labels);
 
         // Spawno un task asincrono per visualizzare i messaggi ricevuti
         tokio::spawn(async move {
-            while let Ok(Some(line)) = reader_lines.next_line().await {
-                if let Ok(msg) = serde_json::from_str::<ServerToClient>(&line) {
-                    let rendered = render(msg); // applico funzione render da messages.rs
-                    let _ = msg_tx.send(rendered);
+            // ultimo avviso di arresto del server, ripetuto quando si torna all'accesso
+            let mut notice = Vec::new();
+            while let Some(event) = events.next().await {
+                match event {
+                    Event::Message { msg, request } => {
+                        // i Pong dei /ping digitati non vengono mostrati
+                        if matches!(msg, ServerToClient::Pong) {
+                            continue;
+                        }
+                        if matches!(msg, ServerToClient::ServerShutdown { .. }) {
+                            notice = render_lines(msg.clone());
+                        }
+                        // un errore indica il comando che lo ha provocato
+                        let label = request.and_then(|id| labels.take(id));
+                        if let (ServerToClient::Error { code, .. }, Some(label)) = (&msg, &label) {
+                            let _ = msg_tx.send(t!("ui.error_for", label, code));
+                            continue;
+                        }
+                        // applico funzione render da messages.rs (la cronologia produce più righe)
+                        for rendered in render_lines(msg) {
+                            let _ = msg_tx.send(rendered);
+                        }
+                    }
+                    Event::Reconnecting => {
+                        connected.store(false, Ordering::Relaxed);
+                        let _ = msg_tx.send(t!("conn.reconnecting"));
+                    }
+                    Event::Reconnected => {
+                        connected.store(true, Ordering::Relaxed);
+                        let _ = msg_tx.send(t!("conn.reconnected"));
+                        // chiede di nuovo l'elenco dei gruppi, ritrovati com'erano prima della caduta
+                        let _ = labels
+                            .send(&client, ClientToServer::ListGroups, "/groups")
+                            .await;
+                    }
+                    Event::Disconnected => {
+                        connected.store(false, Ordering::Relaxed);
+                        let _ = msg_tx.send(t!("conn.lost"));
+                    }
+                    Event::SessionExpired => {
+                        connected.store(false, Ordering::Relaxed);
+                        notice.push(t!("conn.session_expired"));
+                        let _ = expired_tx.send(notice);
+                        break;
+                    }
                 }
             }
         })
     };
 
     // Gestione CTRL+C
-    let writer_half_ctrlc = Arc::clone(&writer_half);
-    tokio::spawn(async move {
+    let client_ctrlc = Arc::clone(&client);
+    let ctrlc_task = tokio::spawn(async move {
         let _ = tokio::signal::ctrl_c().await;
-        let mut wh = writer_half_ctrlc.lock().await;
-        let _ = send(
-            &mut *wh,
-            &ClientToServer::Logout {
-                reason: Some("CTRL+C".to_string()),
-            },
-        ).await;
-        
+        let _ = client_ctrlc.logout(Some("CTRL+C")).await;
+
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
         restore_terminal();
         std::process::exit(0);
@@ -82,15 +137,23 @@ pub async fn run_ui(
     write!(stdout, "\x1b[?7l")?;
     stdout.flush()?;
 
-    let prompt = "> ";
     let mut input = String::new();
     let mut messages: Vec<String> = Vec::new();
     let mut scroll_offset: usize = 0;
-
+    let mut exit = Exit::Quit;
 
     // Funzione lambda di ridisegno della ui a seguito di modifiche di dimensione, scroll, ...
-    let redraw = 
-    |stdout: &mut io::Stdout, messages: &Vec<String>, scroll_offset: usize, input: &str| -> anyhow::Result<()> {
+    let redraw = |stdout: &mut io::Stdout,
+                  messages: &Vec<String>,
+                  scroll_offset: usize,
+                  input: &str|
+     -> anyhow::Result<()> {
+        // Il prompt indica se la connessione con il server è stata persa
+        let prompt = if connected.load(Ordering::Relaxed) {
+            "> ".to_string()
+        } else {
+            t!("ui.prompt_lost")
+        };
 
         let (cols, rows) = terminal::size()?; // Ottenimento dimensioni attuali del terminale
         let usable_rows = rows.saturating_sub(1); // Lascia una riga libera per il prompt di input
@@ -98,7 +161,7 @@ pub async fn run_ui(
 
         // Calcola quanto si può scrollare al massimo: se ci sono più messaggi di quelli che entrano
         // nello schermo, questa variabile sarà > 0, altrimenti sarà 0.
-        let max_scroll = total.saturating_sub(usable_rows as usize); 
+        let max_scroll = total.saturating_sub(usable_rows as usize);
 
         // Se l'utente ha scrollato più del massimo consentito, viene limitato al massimo scroll possibile
         let eff_scroll = scroll_offset.min(max_scroll);
@@ -115,24 +178,27 @@ pub async fn run_ui(
 
         // PER OGNI messaggio da visualizzare
         for (i, line) in visible_messages.iter().enumerate() {
-            
             // Sposta il cursore all’inizio della riga i
             stdout.queue(cursor::MoveTo(0, i as u16))?;
             // Pulisce tutta la riga corrente
             stdout.queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
 
             let mut display = line.clone();
-            // Se il messaggio è più lungo della larghezza del terminale, lo tronca per evitare che sbordi 
+            // Se il messaggio è più lungo della larghezza del terminale, lo tronca per evitare che sbordi
             // o causi problemi di visualizzazione
             if display.len() > cols as usize {
                 display.truncate(cols as usize);
             }
-            
+
             // Scelta del colore con cui visualizzare il messaggio in base alla tipologia
             let color = if display.starts_with("[error]") {
                 Some(Color::Red)
             } else if display.starts_with("[server]") {
                 Some(Color::Green)
+            } else if display.starts_with("[dm]") {
+                Some(Color::Magenta)
+            } else if display.starts_with("[storico]") || display.starts_with("[history]") {
+                Some(Color::DarkGrey)
             } else {
                 None
             };
@@ -149,7 +215,6 @@ pub async fn run_ui(
             }
         }
 
-
         stdout.queue(cursor::MoveTo(0, rows.saturating_sub(1)))?;
         stdout.queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
         let mut inp = format!("{}{}", prompt, input);
@@ -167,6 +232,11 @@ pub async fn run_ui(
         // select!: Attesa contemporanea di più eventi asincroni e consecutiva esecuzione non appena uno
         // di essi si verifica
         tokio::select! {
+            // Sessione scaduta: si chiude l'interfaccia e si torna all'accesso
+            Ok(notice) = &mut expired_rx => {
+                exit = Exit::SessionExpired(notice);
+                break;
+            }
             // Ricezione di un messaggio dal canale
             maybe_msg = msg_rx.recv() => {
                 if let Some(txt) = maybe_msg {
@@ -186,7 +256,7 @@ pub async fn run_ui(
                     }
                 }
             }
-            // yield_now permette al task corrente di cedere volontariamente il controllo, 
+            // yield_now permette al task corrente di cedere volontariamente il controllo,
             // lasciando che altri task pronti vengano eseguiti prima di riprendere
             _ = tokio::task::yield_now() => {
                 // controllo nuovo input ogni 30 secondi
@@ -196,19 +266,14 @@ pub async fn run_ui(
                     match event::read()? {
 
                         // se il tipo di evento avvenuto è di tasto premuto, si controlla quale
-                        event::Event::Key(k) => {
-                            if k.kind == KeyEventKind::Press {
-
+                        event::Event::Key(k) if k.kind == KeyEventKind::Press => {
                                 match k.code {
-                                    
+
                                     // Gestione CTRL+C
                                     KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => {
-                                        messages.push("Uscita dal client...".into());
+                                        messages.push(t!("ui.exiting"));
                                         redraw(&mut stdout, &messages, scroll_offset, &input)?;
-                                        {
-                                            let mut wh = writer_half.lock().await;
-                                            let _ = send(&mut *wh, &ClientToServer::Logout { reason: Some("CTRL+C".into()) }).await;
-                                        }
+                                        let _ = client.logout(Some("CTRL+C")).await;
                                         break;
                                     }
 
@@ -217,7 +282,7 @@ pub async fn run_ui(
                                         let line = input.trim().to_string();
                                         if !line.is_empty() {
                                             messages.push(format!("> {}", line));
-                                            let produced = handle_command(&line, &writer_half, &my_nick).await?;
+                                            let produced = handle_command(&line, &client, &labels).await?;
                                             if !produced.is_empty() { messages.extend(produced); }
                                         }
                                         input.clear();
@@ -233,7 +298,6 @@ pub async fn run_ui(
                                     KeyCode::Esc => { input.clear(); redraw(&mut stdout, &messages, scroll_offset, &input)?; }
                                     _ => {}
                                 }
-                            }
                         }
                         event::Event::Paste(p) => { input.push_str(&p); redraw(&mut stdout, &messages, scroll_offset, &input)?; }
 
@@ -242,25 +306,21 @@ pub async fn run_ui(
                         event::Event::Mouse(m) => {
                             use crossterm::event::MouseEventKind;
                             match m.kind {
-                                MouseEventKind::ScrollUp => {
-                                    if !messages.is_empty() {
-                                        let (_, rows) = terminal::size()?;
-                                        let usable_rows = rows.saturating_sub(1) as usize;
-                                        let total = messages.len();
-                                        if total > usable_rows { 
-                                            let max_scroll = total - usable_rows; 
-                                            if scroll_offset < max_scroll {
-                                                scroll_offset += 1;
-                                                redraw(&mut stdout, &messages, scroll_offset, &input)?;
-                                            }
+                                MouseEventKind::ScrollUp if !messages.is_empty() => {
+                                    let (_, rows) = terminal::size()?;
+                                    let usable_rows = rows.saturating_sub(1) as usize;
+                                    let total = messages.len();
+                                    if total > usable_rows {
+                                        let max_scroll = total - usable_rows;
+                                        if scroll_offset < max_scroll {
+                                            scroll_offset += 1;
+                                            redraw(&mut stdout, &messages, scroll_offset, &input)?;
                                         }
                                     }
                                 }
-                                MouseEventKind::ScrollDown => {
-                                    if scroll_offset > 0 {
-                                        scroll_offset -= 1;
-                                        redraw(&mut stdout, &messages, scroll_offset, &input)?;
-                                    }
+                                MouseEventKind::ScrollDown if scroll_offset > 0 => {
+                                    scroll_offset -= 1;
+                                    redraw(&mut stdout, &messages, scroll_offset, &input)?;
                                 }
                                 _ => {}
                             }
@@ -278,7 +338,15 @@ pub async fn run_ui(
     write!(stdout, "\x1b[?7h")?; // re-enable wrap
     stdout.execute(terminal::LeaveAlternateScreen)?;
     stdout.flush()?;
-    println!("{} ti sei disconnesso correttamente", my_nick);
     read_task.abort();
-    Ok(())
+    ctrlc_task.abort();
+    match &exit {
+        Exit::Quit => println!("{}", t!("ui.bye", client.nick())),
+        Exit::SessionExpired(notice) => {
+            for line in notice {
+                println!("{}", line);
+            }
+        }
+    }
+    Ok(exit)
 }
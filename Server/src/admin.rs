@@ -0,0 +1,313 @@
+/*
+Modulo Admin: API HTTP opzionale (--admin-bind) per consultare e gestire il server.
+Espone in JSON utenti, gruppi con i membri, inviti in attesa e statistiche delle connessioni,
+e permette all'amministratore di disconnettere un utente, eliminare un gruppo e inviare un annuncio.
+Ogni richiesta deve avere l'intestazione `Authorization: Bearer <token>` con il token di --admin-token.
+Le operazioni sullo stato sono funzioni pubbliche, usate anche dalla console del server (console.rs).
+*/
+
+use axum::extract::{Path, Request, State as AxState};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use ruggine_common::{ErrorCode, InviteEntry, ServerToClient};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::catalog::t;
+use crate::connection::ConnConfig;
+use crate::state::State;
+use crate::util::same_token;
+
+// Dati condivisi dai gestori delle richieste HTTP
+#[derive(Clone)]
+struct Admin {
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+    token: Arc<str>,
+    started: Instant,
+}
+
+pub async fn run(
+    bind_addr: &str,
+    token: String,
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+) -> anyhow::Result<()> {
+    let admin = Admin {
+        state,
+        conf,
+        token: token.into(),
+        started: Instant::now(),
+    };
+    let app = Router::new()
+        .route("/users", get(users))
+        .route("/users/:nick/kick", post(kick))
+        .route("/groups", get(groups))
+        .route("/groups/:group", delete(delete_group))
+        .route("/invites", get(invites))
+        .route("/stats", get(stats))
+        .route("/announce", post(announce))
+        .route_layer(middleware::from_fn_with_state(admin.clone(), require_token))
+        .with_state(admin);
+
+    let listener = TcpListener::bind(bind_addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+// Rifiuta le richieste senza il token corretto, prima di toccare lo stato
+async fn require_token(AxState(admin): AxState<Admin>, req: Request, next: Next) -> Response {
+    let given = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match given {
+        Some(token) if same_token(token.as_bytes(), admin.token.as_bytes()) => next.run(req).await,
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response(),
+    }
+}
+
+// Errore di una richiesta: lo stesso codice del protocollo, con il testo nella lingua del server
+struct ApiError(StatusCode, ErrorCode);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let reason = self.1.to_string();
+        (
+            self.0,
+            Json(ErrorBody {
+                code: self.1,
+                reason,
+            }),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    code: ErrorCode,
+    reason: String,
+}
+
+// Stato della sessione di un account
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Presence {
+    Online,   // connesso
+    Detached, // connessione caduta, sessione ancora riprendibile
+    Offline,
+}
+
+#[derive(Serialize)]
+pub struct UserInfo {
+    pub nick: String,
+    pub presence: Presence,
+}
+
+#[derive(Serialize)]
+pub struct GroupInfo {
+    pub name: String,
+    pub owner: String,
+    pub admins: Vec<String>,
+    pub members: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Stats {
+    pub uptime_secs: u64,
+    pub accounts: usize,
+    pub online: usize,
+    pub detached: usize,
+    pub groups: usize,
+    pub invites: usize,
+    pub connections_open: usize,
+    pub connections_accepted: u64,
+    pub dropped_oldest: u64,
+    pub dropped_newest: u64,
+    pub evicted: u64,
+}
+
+#[derive(Deserialize)]
+struct Announcement {
+    text: String,
+}
+
+async fn users(AxState(admin): AxState<Admin>) -> Json<Vec<UserInfo>> {
+    Json(list_users(&*admin.state.read().await))
+}
+
+async fn groups(AxState(admin): AxState<Admin>) -> Json<Vec<GroupInfo>> {
+    Json(list_groups(&*admin.state.read().await))
+}
+
+async fn invites(AxState(admin): AxState<Admin>) -> Json<Vec<InviteEntry>> {
+    Json(list_invites(&*admin.state.read().await))
+}
+
+async fn stats(AxState(admin): AxState<Admin>) -> Json<Stats> {
+    Json(stats_of(
+        &*admin.state.read().await,
+        &admin.conf,
+        admin.started,
+    ))
+}
+
+async fn kick(
+    AxState(admin): AxState<Admin>,
+    Path(nick): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut st = admin.state.write().await;
+    match kick_user(&mut st, &nick) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(code @ ErrorCode::UserOffline { .. }) => Err(ApiError(StatusCode::CONFLICT, code)),
+        Err(code) => Err(ApiError(StatusCode::NOT_FOUND, code)),
+    }
+}
+
+async fn delete_group(
+    AxState(admin): AxState<Admin>,
+    Path(group): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut st = admin.state.write().await;
+    remove_group(&mut st, &group).map_err(|code| ApiError(StatusCode::NOT_FOUND, code))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn announce(AxState(admin): AxState<Admin>, Json(body): Json<Announcement>) -> StatusCode {
+    announce_all(&*admin.state.read().await, &body.text);
+    StatusCode::NO_CONTENT
+}
+
+// Account ordinati per nickname, con lo stato della sessione
+pub fn list_users(st: &State) -> Vec<UserInfo> {
+    let mut users: Vec<UserInfo> = st
+        .accounts
+        .values()
+        .map(|a| {
+            let presence = match st.users_by_nick.get(&a.nick) {
+                Some(id) if st.clients.contains_key(id) => Presence::Online,
+                Some(id) if st.detached.contains_key(id) => Presence::Detached,
+                _ => Presence::Offline,
+            };
+            UserInfo {
+                nick: a.nick.clone(),
+                presence,
+            }
+        })
+        .collect();
+    users.sort_by(|a, b| a.nick.cmp(&b.nick));
+    users
+}
+
+// Gruppi ordinati per nome, con proprietario, amministratori e membri
+pub fn list_groups(st: &State) -> Vec<GroupInfo> {
+    let mut groups: Vec<GroupInfo> = st
+        .groups
+        .iter()
+        .map(|(name, g)| GroupInfo {
+            name: name.clone(),
+            owner: g.owner.clone(),
+            admins: sorted(g.admins.iter()),
+            members: sorted(g.members.iter()),
+        })
+        .collect();
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+    groups
+}
+
+// Inviti in attesa (compresi i link aperti, con nick assente), ordinati per gruppo e destinatario
+pub fn list_invites(st: &State) -> Vec<InviteEntry> {
+    let mut invites: Vec<InviteEntry> = st
+        .invites
+        .iter()
+        .map(|(code, invite)| invite.entry(code))
+        .collect();
+    invites.sort_by(|a, b| (&a.group, &a.nick).cmp(&(&b.group, &b.nick)));
+    invites
+}
+
+// Contatori di /stats; `started` è l'istante da cui si misura l'uptime
+pub fn stats_of(st: &State, conf: &ConnConfig, started: Instant) -> Stats {
+    let (dropped_oldest, dropped_newest, evicted) = conf.queue_stats.snapshot();
+    Stats {
+        uptime_secs: started.elapsed().as_secs(),
+        accounts: st.accounts.len(),
+        online: st.clients.len(),
+        detached: st.detached.len(),
+        groups: st.groups.len(),
+        invites: st.invites.len(),
+        connections_open: conf.stats.open.load(Ordering::Relaxed),
+        connections_accepted: conf.stats.accepted.load(Ordering::Relaxed),
+        dropped_oldest,
+        dropped_newest,
+        evicted,
+    }
+}
+
+// Chiude la sessione dell'utente e restituisce il suo nickname; gruppi e inviti restano,
+// ma deve accedere di nuovo con la password
+pub fn kick_user(st: &mut State, nick: &str) -> Result<String, ErrorCode> {
+    let nick = st
+        .account_nick(nick)
+        .ok_or_else(|| ErrorCode::UserNotFound {
+            nick: nick.to_string(),
+        })?;
+    let id = match st.users_by_nick.get(&nick) {
+        Some(id) => *id,
+        None => return Err(ErrorCode::UserOffline { nick }),
+    };
+    st.disconnect(&id, ErrorCode::KickedByAdmin);
+    info!("{} disconnesso dall'amministratore", nick);
+    Ok(nick)
+}
+
+// Elimina il gruppo con la cronologia e gli inviti; i membri connessi ricevono Left
+pub fn remove_group(st: &mut State, group: &str) -> Result<(), ErrorCode> {
+    if !st.groups.contains_key(group) {
+        return Err(ErrorCode::GroupNotFound {
+            group: group.to_string(),
+        });
+    }
+    st.notify_group(group, &t!("admin.group_deleted", group));
+    let members: Vec<String> = st.groups[group].members.iter().cloned().collect();
+    for member in &members {
+        if let Some(tx) = st.tx_of_nick(member) {
+            let _ = tx.send(ServerToClient::Left {
+                group: group.to_string(),
+            });
+        }
+    }
+    st.delete_group(group);
+    info!("Gruppo {} eliminato dall'amministratore", group);
+    Ok(())
+}
+
+// Messaggio del server a tutti gli utenti connessi; restituisce quanti lo hanno ricevuto
+pub fn announce_all(st: &State, text: &str) -> usize {
+    let text = t!("admin.announcement", text);
+    for tx in st.clients.values() {
+        let _ = tx.send(ServerToClient::MessageServer { text: text.clone() });
+    }
+    info!("Annuncio inviato a {} utenti", st.clients.len());
+    st.clients.len()
+}
+
+fn sorted<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut names: Vec<String> = names.cloned().collect();
+    names.sort();
+    names
+}
@@ -12,7 +12,129 @@ Permette di specificare l'indirizzo di bind su cui il server ascolta.
 solitamente solo cargo run quindi non serve questo modulo
  */
 pub struct Args {
+    /// File di configurazione TOML; le opzioni sulla riga di comando prevalgono sul file
+    #[arg(long)]
+    pub config: Option<String>,
+
     /// Indirizzo di bind es. 0.0.0.0:7000
     #[arg(long, default_value = "127.0.0.1:7000")]
     pub bind: String,
+
+    /// Indirizzo di bind per i client WebSocket es. 0.0.0.0:7001 (se assente il gateway è disattivato)
+    #[arg(long)]
+    pub ws_bind: Option<String>,
+
+    /// Indirizzo di bind dell'API HTTP di amministrazione es. 127.0.0.1:7080 (richiede --admin-token)
+    #[arg(long, requires = "admin_token")]
+    pub admin_bind: Option<String>,
+
+    /// Token richiesto dall'API di amministrazione nell'intestazione Authorization: Bearer <token>
+    #[arg(long, requires = "admin_bind")]
+    pub admin_token: Option<String>,
+
+    /// Disattiva la console di amministrazione su stdin (es. server avviato in background)
+    #[arg(long)]
+    pub no_console: bool,
+
+    /// Nickname di un operatore del server, che può disconnettere, bandire e silenziare (ripetibile)
+    #[arg(long = "operator")]
+    pub operators: Vec<String>,
+
+    /// Indirizzo di bind dell'esportatore di metriche Prometheus (GET /metrics) es. 127.0.0.1:9100
+    #[arg(long)]
+    pub metrics_bind: Option<String>,
+
+    /// File in cui registrare periodicamente CPU, tempo di esecuzione e messaggi scartati
+    #[arg(long, default_value = crate::logger::DEFAULT_CPU_LOG)]
+    pub cpu_log: String,
+
+    /// Secondi tra due righe del file --cpu-log (0 = file disattivato)
+    #[arg(long, default_value_t = crate::logger::DEFAULT_CPU_LOG_INTERVAL)]
+    pub cpu_log_interval: u64,
+
+    /// Secondi concessi all'arresto per consegnare ai client i messaggi in coda
+    #[arg(long, default_value_t = crate::shutdown::DEFAULT_SHUTDOWN_TIMEOUT)]
+    pub shutdown_timeout: u64,
+
+    /// Secondi dopo cui i client possono riconnettersi, comunicati all'arresto (es. per un riavvio)
+    #[arg(long)]
+    pub reconnect_after: Option<u64>,
+
+    /// Certificato TLS del server in formato PEM (abilita il TLS insieme a --tls-key)
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<String>,
+
+    /// Chiave privata TLS del server in formato PEM
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<String>,
+
+    /// Cartella in cui salvare lo stato persistente (journal + snapshot)
+    #[arg(long, default_value = crate::storage::DEFAULT_DATA_DIR)]
+    pub data_dir: String,
+
+    /// Numero di modifiche nel journal dopo cui viene scritto uno snapshot
+    #[arg(long, default_value_t = crate::storage::DEFAULT_SNAPSHOT_EVERY)]
+    pub snapshot_every: usize,
+
+    /// Cartella in cui salvare la cronologia dei gruppi
+    #[arg(long, default_value = crate::history::DEFAULT_HISTORY_DIR)]
+    pub history_dir: String,
+
+    /// Numero massimo di messaggi conservati per ogni gruppo
+    #[arg(long, default_value_t = crate::history::DEFAULT_MAX_PER_GROUP)]
+    pub history_max: usize,
+
+    /// Limite generale di messaggi per connessione, formato <rate>:<burst> (messaggi al secondo : raffica)
+    #[arg(long, default_value = crate::rate_limit::DEFAULT_LIMIT)]
+    pub rate_limit: String,
+
+    /// Limite aggiuntivo per tipo di messaggio, formato <Kind>=<rate>:<burst> (ripetibile)
+    #[arg(long = "kind-limit", default_values = crate::rate_limit::DEFAULT_KIND_LIMITS)]
+    pub kind_limits: Vec<String>,
+
+    /// Violazioni dei limiti tollerate in 10 secondi prima di disconnettere il client
+    #[arg(long, default_value_t = crate::rate_limit::DEFAULT_MAX_STRIKES)]
+    pub max_strikes: u32,
+
+    /// Numero massimo di messaggi in attesa di essere inviati a ciascun client
+    #[arg(long, default_value_t = crate::outbox::DEFAULT_QUEUE_SIZE)]
+    pub queue_size: usize,
+
+    /// Cosa fare quando la coda di un client è piena
+    #[arg(long, value_enum, default_value_t = crate::outbox::QueuePolicy::DropOldest)]
+    pub queue_policy: crate::outbox::QueuePolicy,
+
+    /// Secondi senza messaggi (né ping) dopo cui una connessione viene chiusa
+    #[arg(long, default_value_t = 60)]
+    pub idle_timeout: u64,
+
+    /// Secondi per cui una sessione caduta resta valida e può essere ripresa dal client (0 = nessuna attesa)
+    #[arg(long, default_value_t = 30)]
+    pub grace: u64,
+
+    /// Dimensione massima in byte di un messaggio, in lettura e in scrittura
+    #[arg(long, default_value_t = ruggine_common::MAX_FRAME_LEN)]
+    pub max_frame: usize,
+
+    /// Lunghezza massima di nickname e nomi dei gruppi
+    #[arg(long, default_value_t = crate::validation::DEFAULT_MAX_NAME_LEN,
+          value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_name_len: usize,
+
+    /// Nome non utilizzabile come nickname o nome di gruppo (ripetibile, sostituisce i default)
+    #[arg(long = "reserved-name", default_values = crate::validation::DEFAULT_RESERVED_NAMES)]
+    pub reserved_names: Vec<String>,
+
+    /// Numero di caratteri dei codici invito generati
+    #[arg(long, default_value_t = crate::util::DEFAULT_CODE_LEN,
+          value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(4..=64))]
+    pub invite_code_len: usize,
+
+    /// Secondi di validità dei codici invito per cui il client non indica una durata (0 = nessuna scadenza)
+    #[arg(long, default_value_t = 0)]
+    pub invite_ttl: u64,
+
+    /// Lingua dei testi inviati ai client: it oppure en (default: da RUGGINE_LANG, LC_ALL o LANG)
+    #[arg(long)]
+    pub lang: Option<ruggine_common::Lang>,
 }
@@ -0,0 +1,52 @@
+/*
+Modulo Auth: calcolo e verifica degli hash delle password degli account.
+Si usa Argon2 con salt casuale; l'hash viene salvato in formato PHC (algoritmo, parametri e salt inclusi).
+*/
+
+use argon2::password_hash::{
+    rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::Argon2;
+use std::sync::OnceLock;
+
+// Calcola l'hash salato della password
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("hash della password fallito: {e}"))?;
+    Ok(hash.to_string())
+}
+
+// Verifica la password rispetto all'hash salvato; un hash illeggibile conta come password errata
+pub fn verify_password(password: &str, stored_hash: &str) -> bool {
+    match PasswordHash::new(stored_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+// Hash di una password qualsiasi, calcolato una volta sola con gli stessi parametri degli account:
+// verificarlo per un nickname inesistente rende il tempo di risposta uguale a quello di un account vero
+pub fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_password("ruggine-dummy-password").unwrap_or_default())
+}
+
+// Argon2 è volutamente lento: il calcolo va fatto fuori dal runtime asincrono
+pub async fn hash_password_blocking(password: String) -> anyhow::Result<String> {
+    tokio::task::spawn_blocking(move || hash_password(&password)).await?
+}
+
+pub async fn verify_password_blocking(password: String, stored_hash: String) -> bool {
+    tokio::task::spawn_blocking(move || verify_password(&password, &stored_hash))
+        .await
+        .unwrap_or(false)
+}
+
+// Verifica su `dummy_hash` per un nickname inesistente; il risultato non conta
+pub async fn verify_dummy_blocking(password: String) {
+    let _ = tokio::task::spawn_blocking(move || verify_password(&password, dummy_hash())).await;
+}
@@ -0,0 +1,179 @@
+/*
+Modulo Bans: elenco degli account e degli indirizzi IP banditi dagli operatori del server.
+L'elenco è salvato per intero in `bans.json` nella cartella dati a ogni modifica (file temporaneo + rename),
+così sopravvive ai riavvii. I ban per nickname bloccano l'accesso all'account, quelli per indirizzo
+bloccano la connessione già nel ciclo di accept.
+*/
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+const BANS_FILE: &str = "bans.json";
+
+// Singolo ban: chi l'ha deciso, quando e perché
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub by: String,
+    pub reason: Option<String>,
+    pub since: i64, // timestamp unix in millisecondi
+}
+
+impl Ban {
+    pub fn new(by: &str, reason: Option<String>) -> Self {
+        Self {
+            by: by.to_string(),
+            reason,
+            since: Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BanFile {
+    nicks: BTreeMap<String, Ban>, // nickname canonico -> ban
+    ips: BTreeMap<IpAddr, Ban>,
+}
+
+// Se `path` è None i ban restano solo in memoria
+#[derive(Debug, Default)]
+pub struct BanList {
+    path: Option<PathBuf>,
+    bans: BanFile,
+}
+
+impl BanList {
+    // Carica i ban salvati nella cartella dati (nessun file = nessun ban)
+    pub fn load(dir: &Path) -> std::io::Result<Self> {
+        let path = dir.join(BANS_FILE);
+        let bans = match fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BanFile::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            path: Some(path),
+            bans,
+        })
+    }
+
+    // Numero di nickname e di indirizzi banditi
+    pub fn counts(&self) -> (usize, usize) {
+        (self.bans.nicks.len(), self.bans.ips.len())
+    }
+
+    // I nickname vengono confrontati senza distinguere maiuscole e minuscole
+    pub fn nick(&self, nick: &str) -> Option<&Ban> {
+        self.bans
+            .nicks
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(nick))
+            .map(|(_, ban)| ban)
+    }
+
+    pub fn ip(&self, ip: IpAddr) -> Option<&Ban> {
+        self.bans.ips.get(&ip.to_canonical())
+    }
+
+    pub fn ban_nick(&mut self, nick: &str, ban: Ban) -> std::io::Result<()> {
+        self.bans.nicks.insert(nick.to_string(), ban);
+        self.save()
+    }
+
+    pub fn ban_ip(&mut self, ip: IpAddr, ban: Ban) -> std::io::Result<()> {
+        self.bans.ips.insert(ip.to_canonical(), ban);
+        self.save()
+    }
+
+    // Restituisce false se il nickname non era bandito
+    pub fn unban_nick(&mut self, nick: &str) -> std::io::Result<bool> {
+        let before = self.bans.nicks.len();
+        self.bans.nicks.retain(|n, _| !n.eq_ignore_ascii_case(nick));
+        if self.bans.nicks.len() == before {
+            return Ok(false);
+        }
+        self.save().map(|()| true)
+    }
+
+    pub fn unban_ip(&mut self, ip: IpAddr) -> std::io::Result<bool> {
+        if self.bans.ips.remove(&ip.to_canonical()).is_none() {
+            return Ok(false);
+        }
+        self.save().map(|()| true)
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let path = match &self.path {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let tmp = path.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(serde_json::to_string_pretty(&self.bans)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ruggine-bans-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn bans_survive_a_reload() {
+        let dir = temp_dir();
+        let mut bans = BanList::load(&dir).unwrap();
+        assert_eq!(bans.counts(), (0, 0));
+        bans.ban_nick("Mallory", Ban::new("root", Some("spam".into())))
+            .unwrap();
+        bans.ban_ip(Ipv4Addr::new(10, 0, 0, 7).into(), Ban::new("root", None))
+            .unwrap();
+
+        let bans = BanList::load(&dir).unwrap();
+        assert_eq!(bans.counts(), (1, 1));
+        let ban = bans.nick("mallory").unwrap();
+        assert_eq!(
+            (ban.by.as_str(), ban.reason.as_deref()),
+            ("root", Some("spam"))
+        );
+        assert!(bans.ip(Ipv4Addr::new(10, 0, 0, 7).into()).is_some());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn nick_bans_ignore_case() {
+        let mut bans = BanList::default();
+        bans.ban_nick("Mallory", Ban::new("root", None)).unwrap();
+        assert!(bans.nick("MALLORY").is_some());
+        assert!(bans.nick("mallory2").is_none());
+        assert!(bans.unban_nick("mallory").unwrap());
+        assert!(!bans.unban_nick("Mallory").unwrap());
+        assert_eq!(bans.counts(), (0, 0));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_match_plain_ipv4() {
+        let plain: IpAddr = Ipv4Addr::new(192, 0, 2, 1).into();
+        let mapped: IpAddr = Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped().into();
+        let mut bans = BanList::default();
+
+        // un client IPv4 su un socket dual-stack arriva come ::ffff:a.b.c.d
+        bans.ban_ip(mapped, Ban::new("root", None)).unwrap();
+        assert!(bans.ip(plain).is_some());
+        assert!(bans.ip(mapped).is_some());
+        assert!(bans.ip(Ipv6Addr::LOCALHOST.into()).is_none());
+        assert!(bans.unban_ip(plain).unwrap());
+        assert!(!bans.unban_ip(mapped).unwrap());
+    }
+}
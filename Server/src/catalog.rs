@@ -0,0 +1,114 @@
+/*
+Modulo Catalog: testi che il server invia ai client (avvisi MessageServer) in italiano e inglese.
+Ogni voce è (chiave, italiano, inglese); `{}` indica i parametri, nell'ordine.
+Gli errori non passano da qui: viaggiano come ErrorCode e il client li traduce nella propria lingua.
+*/
+
+use ruggine_common::i18n::Catalog;
+
+pub static CATALOG: Catalog = &[
+    (
+        "invite.sent",
+        "Utente {} invitato correttamente al gruppo {}",
+        "User {} invited to group {}",
+    ),
+    (
+        "invite.pending",
+        "Utente {} invitato al gruppo {}: riceverà il codice al prossimo accesso",
+        "User {} invited to group {}: they will get the code at their next login",
+    ),
+    (
+        "invite.revoked",
+        "Invito {} al gruppo {} revocato",
+        "Invite {} to group {} revoked",
+    ),
+    (
+        "group.kicked_you",
+        "Sei stato espulso dal gruppo {} da {}",
+        "You were removed from group {} by {}",
+    ),
+    (
+        "group.kicked",
+        "{} è stato espulso dal gruppo {} da {}",
+        "{} was removed from group {} by {}",
+    ),
+    (
+        "group.promoted",
+        "{} è ora amministratore del gruppo {}",
+        "{} is now an admin of group {}",
+    ),
+    (
+        "group.demoted",
+        "{} non è più amministratore del gruppo {}",
+        "{} is no longer an admin of group {}",
+    ),
+    (
+        "group.new_owner",
+        "{} è il nuovo proprietario del gruppo {}",
+        "{} is the new owner of group {}",
+    ),
+    (
+        "admin.group_deleted",
+        "Il gruppo {} è stato eliminato dall'amministratore del server",
+        "Group {} was deleted by the server administrator",
+    ),
+    ("admin.announcement", "[annuncio] {}", "[announcement] {}"),
+    (
+        "shutdown.reason",
+        "Il server si sta arrestando",
+        "The server is shutting down",
+    ),
+    (
+        "mod.kicked",
+        "{} è stato disconnesso",
+        "{} was disconnected",
+    ),
+    ("mod.banned", "{} è stato bandito", "{} was banned"),
+    (
+        "mod.unbanned",
+        "{} non è più bandito",
+        "{} is no longer banned",
+    ),
+    (
+        "mod.ip_banned",
+        "Indirizzo {} bandito ({} sessioni chiuse)",
+        "Address {} banned ({} sessions closed)",
+    ),
+    (
+        "mod.ip_unbanned",
+        "L'indirizzo {} non è più bandito",
+        "Address {} is no longer banned",
+    ),
+    (
+        "mod.muted",
+        "{} non potrà inviare messaggi per {} secondi",
+        "{} cannot send messages for {} seconds",
+    ),
+    (
+        "mod.muted_you",
+        "L'operatore {} ti ha silenziato per {} secondi",
+        "Operator {} muted you for {} seconds",
+    ),
+    (
+        "mod.unmuted",
+        "{} può di nuovo inviare messaggi",
+        "{} can send messages again",
+    ),
+    (
+        "mod.unmuted_you",
+        "L'operatore {} ti ha tolto il silenzio",
+        "Operator {} unmuted you",
+    ),
+];
+
+// Testo della chiave nella lingua del server, con i parametri al posto di `{}`
+macro_rules! t {
+    ($key:literal $(, $arg:expr)* $(,)?) => {
+        ruggine_common::i18n::translate(
+            $crate::catalog::CATALOG,
+            $key,
+            &[$(&$arg as &dyn std::fmt::Display),*],
+        )
+    };
+}
+pub(crate) use t;
@@ -0,0 +1,66 @@
+/*
+Gestisce la creazione di un nuovo account. Verifica la sintassi di nickname e password e l'unicità,
+salva l'hash salato della password e apre subito la sessione.
+*/
+
+use super::{register, ClientId, CommandResult};
+use crate::auth::hash_password_blocking;
+use crate::state::{State, Tx};
+use crate::storage::Mutation;
+use crate::validation::{validate_nick_syntax, validate_password};
+use ruggine_common::ErrorCode;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::error;
+
+pub async fn handle(
+    nick: String,
+    password: String,
+    client_id: ClientId,
+    peer: IpAddr,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) -> CommandResult {
+    if client_id.is_some() {
+        return register::reject(ErrorCode::AlreadyAuthenticated, client_id, tx);
+    }
+
+    // Validazione sintassi lato server
+    if let Err(code) = validate_nick_syntax(&nick, &state.read().await.limits) {
+        return register::reject(code, client_id, tx);
+    }
+    if let Err(code) = validate_password(&password) {
+        return register::reject(code, client_id, tx);
+    }
+
+    // L'hash viene calcolato prima di prendere il lock sullo stato
+    let password_hash = match hash_password_blocking(password).await {
+        Ok(h) => h,
+        Err(e) => {
+            error!("Errore nel calcolo dell'hash: {:?}", e);
+            return register::reject(ErrorCode::Internal, client_id, tx);
+        }
+    };
+
+    let mut st = state.write().await;
+
+    // Controllo unicità case-insensitive
+    if let Some(existing) = st.account_nick(&nick) {
+        return register::reject(
+            ErrorCode::NickTaken {
+                name: nick,
+                existing,
+            },
+            client_id,
+            tx,
+        );
+    }
+
+    st.commit(Mutation::AccountCreated {
+        nick: nick.clone(),
+        password_hash,
+    });
+
+    register::open_session(&nick, peer, client_id, tx, &mut st)
+}
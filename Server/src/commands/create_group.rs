@@ -1,34 +1,21 @@
 /*
-Gestisce la creazione di un nuovo gruppo. Verifica la validità del nome, l'unicità e aggiunge il creatore come primo membro.
+Gestisce la creazione di un nuovo gruppo. Verifica la validità del nome, l'unicità e aggiunge il creatore come primo membro e proprietario.
 */
 
-use super::{ClientId, CommandResult};
+use super::Session;
 use crate::state::{State, Tx};
+use crate::storage::Mutation;
 use crate::validation::validate_group_name_syntax;
-use ruggine_common::ServerToClient;
+use ruggine_common::{ErrorCode, ServerToClient};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-pub async fn handle(
-    group: String,
-    client_id: ClientId,
-    tx: &Tx,
-    state: &Arc<RwLock<State>>,
-) -> CommandResult {
+pub async fn handle(group: String, session: &Session, tx: &Tx, state: &Arc<RwLock<State>>) {
     let mut st = state.write().await;
-    let id = match client_id {
-        Some(id) => id,
-        None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: "Non registrato".into(),
-            });
-            return CommandResult::continue_with(client_id);
-        }
-    };
 
-    if let Err(reason) = validate_group_name_syntax(&group) {
-        let _ = tx.send(ServerToClient::Error { reason });
-        return CommandResult::continue_with(client_id);
+    if let Err(code) = validate_group_name_syntax(&group, &st.limits) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
     }
 
     // Controllo case-insensitive per i gruppi
@@ -39,25 +26,32 @@ pub async fn handle(
         .cloned();
 
     if let Some(existing_group) = maybe_existing_group {
-        let _ = tx.send(ServerToClient::Error {
-            reason: format!(
-                "Esiste già un gruppo con il nome '{}' (già registrato come '{}')",
-                group, existing_group
-            ),
-        });
-        return CommandResult::continue_with(client_id);
+        let _ = tx.send(ServerToClient::error(ErrorCode::GroupTaken {
+            name: group.clone(),
+            existing: existing_group,
+        }));
+        return;
     }
 
-    if st.users_by_nick.get(&group).is_some() {
-        let _ = tx.send(ServerToClient::Error {
-            reason: format!("Il nome '{group}' è già usato da un utente"),
-        });
-        return CommandResult::continue_with(client_id);
+    if st.accounts.contains_key(&group.to_ascii_lowercase()) {
+        let _ = tx.send(ServerToClient::error(ErrorCode::NameUsedByUser {
+            name: group.clone(),
+        }));
+        return;
     }
-    let g = st.groups.entry(group.clone()).or_default();
-    g.members.insert(id);
+    let my_nick = session.nick.clone();
+    st.commit(Mutation::GroupCreated {
+        group: group.clone(),
+    });
+    st.commit(Mutation::MemberAdded {
+        group: group.clone(),
+        nick: my_nick.clone(),
+    });
+    // il creatore è il proprietario del gruppo
+    st.commit(Mutation::OwnerChanged {
+        group: group.clone(),
+        nick: my_nick,
+    });
     // Conferma creazione gruppo
     let _ = tx.send(ServerToClient::GroupCreated { group });
-
-    CommandResult::continue_with(client_id)
 }
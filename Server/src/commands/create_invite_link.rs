@@ -0,0 +1,55 @@
+/*
+Gestisce la creazione di un link di invito aperto: un codice non legato a un utente, con cui chiunque può
+entrare nel gruppo fino alla scadenza (`ttl` secondi o --invite-ttl) o all'esaurimento degli usi (`max_uses`).
+Come per gli inviti normali, lo possono creare solo il proprietario e gli amministratori.
+*/
+
+use super::Session;
+use crate::invites::{self, Invite};
+use crate::state::{State, Tx};
+use crate::storage::Mutation;
+use ruggine_common::{ErrorCode, GroupAction, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    group: String,
+    ttl: Option<u64>,
+    max_uses: Option<u32>,
+    session: &Session,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) {
+    let mut st = state.write().await;
+    if let Err(code) = st.require_manager(&group, &session.nick, GroupAction::Invite) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
+    }
+    let ttl = match invites::ttl_or_default(ttl, &st.limits) {
+        Ok(ttl) if max_uses != Some(0) => ttl,
+        Ok(_) => {
+            let _ = tx.send(ServerToClient::error(ErrorCode::InvalidInviteLimit));
+            return;
+        }
+        Err(code) => {
+            let _ = tx.send(ServerToClient::error(code));
+            return;
+        }
+    };
+
+    let code = invites::new_code(&st);
+    let mut invite = Invite::new(&group, None, &session.nick, ttl);
+    invite.max_uses = max_uses;
+    let expires_at = invite.expires_at;
+    st.commit(Mutation::InviteCreated {
+        code: code.clone(),
+        invite,
+    });
+
+    let _ = tx.send(ServerToClient::InviteLink {
+        group,
+        code,
+        expires_at,
+        max_uses,
+    });
+}
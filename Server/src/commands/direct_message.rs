@@ -0,0 +1,47 @@
+/*
+Gestisce i messaggi privati tra due utenti: il messaggio viene consegnato solo al destinatario,
+se al momento è connesso.
+*/
+
+use super::Session;
+use crate::state::{State, Tx};
+use crate::validation::validate_text;
+use ruggine_common::{ErrorCode, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    to: String,
+    text: String,
+    session: &Session,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) {
+    let st = state.read().await;
+    let my_nick = session.nick.clone();
+    if let Err(code) = validate_text(&text, &st.limits) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
+    }
+
+    // il destinatario può essere scritto senza rispettare maiuscole/minuscole
+    let target = st.account_nick(&to).unwrap_or(to);
+    if target == my_nick {
+        let _ = tx.send(ServerToClient::error(ErrorCode::DirectMessageToSelf));
+        return;
+    }
+
+    match st.tx_of_nick(&target) {
+        Some(txt) => {
+            let _ = txt.send(ServerToClient::DirectMessage {
+                from: my_nick,
+                text,
+            });
+        }
+        None => {
+            let _ = tx.send(ServerToClient::error(ErrorCode::UserOffline {
+                nick: target.clone(),
+            }));
+        }
+    }
+}
@@ -2,42 +2,25 @@
 Gestisce l'invio di messaggi globali a tutti i client connessi, escluso il mittente.
 */
 
-use super::{ClientId, CommandResult};
+use super::Session;
 use crate::state::{State, Tx};
+use crate::validation::validate_text;
 use ruggine_common::ServerToClient;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-pub async fn handle(
-    text: String,
-    client_id: ClientId,
-    tx: &Tx,
-    state: &Arc<RwLock<State>>,
-) -> CommandResult {
+pub async fn handle(text: String, session: &Session, tx: &Tx, state: &Arc<RwLock<State>>) {
     let st = state.read().await;
-    let id = match client_id {
-        Some(id) => id,
-        None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: "Non registrato".into(),
-            });
-            return CommandResult::continue_with(client_id);
-        }
-    };
-    let my_nick = st
-        .nicks_by_id
-        .get(&id)
-        .cloned()
-        .unwrap_or_else(|| "???".into());
-
+    if let Err(code) = validate_text(&text, &st.limits) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
+    }
     for (client_id, txm) in &st.clients {
-        if *client_id != id {
+        if *client_id != session.id {
             let _ = txm.send(ServerToClient::GlobalMessage {
-                from: my_nick.clone(),
+                from: session.nick.clone(),
                 text: text.clone(),
             });
         }
     }
-
-    CommandResult::continue_with(client_id)
 }
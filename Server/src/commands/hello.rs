@@ -0,0 +1,54 @@
+/*
+Gestisce la negoziazione iniziale del protocollo: controlla la versione del client e risponde con
+Welcome, indicando la versione concordata e le funzionalità supportate da entrambe le parti.
+*/
+
+use super::{ClientId, CommandResult};
+use crate::state::Tx;
+use ruggine_common::{
+    Codec, ErrorCode, ServerToClient, FEATURE_DIRECT_MESSAGES, FEATURE_HISTORY,
+    FEATURE_INVITE_LINKS, FEATURE_MODERATION, FEATURE_MSGPACK, FEATURE_RENAME_GROUP,
+    FEATURE_RESUME, FEATURE_ROLES, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+
+// Funzionalità offerte da questo server
+const SERVER_FEATURES: &[&str] = &[
+    FEATURE_HISTORY,
+    FEATURE_DIRECT_MESSAGES,
+    FEATURE_ROLES,
+    FEATURE_RESUME,
+    FEATURE_MSGPACK,
+    FEATURE_MODERATION,
+    FEATURE_INVITE_LINKS,
+    FEATURE_RENAME_GROUP,
+];
+
+// Restituisce il codec da usare dopo il Welcome se il client è stato accettato;
+// in caso contrario (None) la connessione va chiusa
+pub fn negotiate(protocol_version: u32, features: Vec<String>, tx: &Tx) -> Option<Codec> {
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&protocol_version) {
+        let _ = tx.send(ServerToClient::error(ErrorCode::UnsupportedVersion {
+            requested: protocol_version,
+            min: MIN_PROTOCOL_VERSION,
+            max: PROTOCOL_VERSION,
+        }));
+        return None;
+    }
+
+    let features: Vec<String> = features
+        .into_iter()
+        .filter(|f| SERVER_FEATURES.contains(&f.as_str()))
+        .collect();
+    let codec = Codec::negotiated(&features);
+    let _ = tx.send(ServerToClient::Welcome {
+        protocol_version,
+        features,
+    });
+    Some(codec)
+}
+
+// Hello ricevuto dopo la negoziazione
+pub fn handle(client_id: ClientId, tx: &Tx) -> CommandResult {
+    let _ = tx.send(ServerToClient::error(ErrorCode::AlreadyNegotiated));
+    CommandResult::continue_with(client_id)
+}
@@ -0,0 +1,46 @@
+/*
+Restituisce una pagina della cronologia di un gruppo. Solo i membri del gruppo possono leggerla.
+*/
+
+use super::Session;
+use crate::history::{DEFAULT_PAGE, MAX_PAGE};
+use crate::state::{State, Tx};
+use ruggine_common::{ErrorCode, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    group: String,
+    before: Option<u64>,
+    limit: Option<u32>,
+    session: &Session,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) {
+    let st = state.read().await;
+    match st.groups.get(&group) {
+        Some(g) if g.members.contains(&session.nick) => {}
+        Some(_) => {
+            let _ = tx.send(ServerToClient::error(ErrorCode::NotMember {
+                group: group.clone(),
+            }));
+            return;
+        }
+        None => {
+            let _ = tx.send(ServerToClient::error(ErrorCode::GroupNotFound {
+                group: group.clone(),
+            }));
+            return;
+        }
+    }
+
+    let limit = limit.map_or(DEFAULT_PAGE, |l| (l as usize).clamp(1, MAX_PAGE));
+    let (messages, has_more) =
+        st.history
+            .page(&group, before, limit, st.limits.history_page_bytes());
+    let _ = tx.send(ServerToClient::HistoryPage {
+        group,
+        messages,
+        has_more,
+    });
+}
@@ -1,74 +1,63 @@
 /*
 Gestisce l'invito di un utente a un gruppo tramite codice invito. Verifica i permessi e invia il codice al destinatario.
+Il codice vale una sola volta, solo per il destinatario, e scade dopo `ttl` secondi (o --invite-ttl) se indicato.
 */
 
-use super::{ClientId, CommandResult};
+use super::Session;
 use crate::{
+    catalog::t,
+    invites::{self, Invite, InviteEnd},
     state::{State, Tx},
-    util::short_code,
+    storage::Mutation,
 };
-use ruggine_common::ServerToClient;
+use ruggine_common::{ErrorCode, GroupAction, ServerToClient};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::warn;
 
 pub async fn handle(
     group: String,
     nick: String,
-    _client_id: ClientId,
+    ttl: Option<u64>,
+    session: &Session,
     tx: &Tx,
     state: &Arc<RwLock<State>>,
-) -> CommandResult {
+) {
     let mut st = state.write().await;
-    if !st.groups.contains_key(&group) {
-        let _ = tx.send(ServerToClient::Error {
-            reason: format!("Gruppo {group} inesistente"),
-        });
-        return CommandResult::continue_with(_client_id);
-    }
 
-    // verifica che il chiamante sia registrato e membro del gruppo
-    let inviter_id = match _client_id {
-        Some(id) => id,
-        None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: "Non registrato".into(),
-            });
-            return CommandResult::continue_with(_client_id);
+    // verifica che il chiamante sia membro del gruppo e proprietario o amministratore
+    let inviter_nick = session.nick.clone();
+    if let Err(code) = st.require_manager(&group, &inviter_nick, GroupAction::Invite) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
+    }
+    let ttl = match invites::ttl_or_default(ttl, &st.limits) {
+        Ok(ttl) => ttl,
+        Err(code) => {
+            let _ = tx.send(ServerToClient::error(code));
+            return;
         }
     };
-    if let Some(g) = st.groups.get(&group) {
-        if !g.members.contains(&inviter_id) {
-            let _ = tx.send(ServerToClient::Error {
-                reason: format!("Non sei membro del gruppo {group}"),
-            });
-            return CommandResult::continue_with(_client_id);
+    // lookup account destinatario case-insensitive (anche se non connesso)
+    let target_nick = match st.account_nick(&nick) {
+        Some(n) => n,
+        None => {
+            let _ = tx.send(ServerToClient::error(ErrorCode::UserNotFound {
+                nick: nick.clone(),
+            }));
+            return;
         }
-    }
-    // lookup utente destinatario case-insensitive
-    let id_user = st
-        .users_by_nick
-        .iter()
-        .find(|(existing_nick, _)| existing_nick.eq_ignore_ascii_case(&nick))
-        .map(|(_, id)| *id);
-
-    // se l'utente non esiste, errore
-    if id_user.is_none() {
-        let _ = tx.send(ServerToClient::Error {
-            reason: format!("Utente {nick} inesistente"),
-        });
-        return CommandResult::continue_with(_client_id);
-    }
+    };
 
     if st
         .groups
         .get(&group)
-        .map_or(false, |g| g.members.contains(&id_user.unwrap()))
+        .is_some_and(|g| g.members.contains(&target_nick))
     {
-        let _ = tx.send(ServerToClient::Error {
-            reason: format!("Utente {nick} già membro del gruppo {group}"),
-        });
-        return CommandResult::continue_with(_client_id);
+        let _ = tx.send(ServerToClient::error(ErrorCode::UserAlreadyMember {
+            nick: nick.clone(),
+            group: group.clone(),
+        }));
+        return;
     }
 
     // Invalida eventuali inviti precedenti per lo stesso (gruppo, utente) – solo l'ultimo rimane valido
@@ -76,8 +65,9 @@ pub async fn handle(
     let keys_to_remove: Vec<String> = st
         .invites
         .iter()
-        .filter_map(|(code, (g, n))| {
-            if g == &group && n.eq_ignore_ascii_case(&nick) {
+        .filter_map(|(code, invite)| {
+            let n = invite.nick.as_ref()?;
+            if invite.group == group && n.eq_ignore_ascii_case(&target_nick) {
                 Some(code.clone())
             } else {
                 None
@@ -85,31 +75,29 @@ pub async fn handle(
         })
         .collect();
     for k in keys_to_remove {
-        st.invites.remove(&k);
+        st.retire_invite(&k, InviteEnd::Revoked);
     }
 
-    let code = short_code();
-    st.invites
-        .insert(code.clone(), (group.clone(), nick.clone()));
-
-    // invia il codice di invito al client destinatario
-    if let Some(id) = id_user {
-        if let Some(txm) = st.clients.get(&id) {
-            let _ = txm.send(ServerToClient::InviteCode {
-                group: group.clone(),
-                code: code.clone(),
-                client_id: Some(inviter_id)
-                    .and_then(|id| st.nicks_by_id.get(&id).cloned())
-                    .unwrap_or_default(),
-            });
-        } else {
-            warn!("Client {} non trovato per invio codice invito", id);
-        }
-    }
-
-    let _ = tx.send(ServerToClient::MessageServer {
-        text: format!("Utente {} invitato correttamente al gruppo {}", nick, group),
+    let code = invites::new_code(&st);
+    let invite = Invite::new(&group, Some(target_nick.clone()), &inviter_nick, ttl);
+    let expires_at = invite.expires_at;
+    st.commit(Mutation::InviteCreated {
+        code: code.clone(),
+        invite,
     });
 
-    CommandResult::continue_with(_client_id)
+    // invia il codice di invito al destinatario se connesso, altrimenti lo riceverà al prossimo accesso
+    let text = if let Some(txm) = st.tx_of_nick(&target_nick) {
+        let _ = txm.send(ServerToClient::InviteCode {
+            group: group.clone(),
+            code: code.clone(),
+            client_id: inviter_nick,
+            expires_at,
+        });
+        t!("invite.sent", target_nick, group)
+    } else {
+        t!("invite.pending", target_nick, group)
+    };
+
+    let _ = tx.send(ServerToClient::MessageServer { text });
 }
@@ -1,79 +1,90 @@
 /*
 Gestisce la logica di ingresso in un gruppo tramite codice invito. Verifica la validità e aggiorna lo stato.
+Un codice rifiutato riporta il motivo: inesistente, scaduto, revocato, già usato o esaurito,
+per un altro gruppo o destinato a un altro utente.
 */
 
-use super::{ClientId, CommandResult};
+use super::Session;
+use crate::history::DEFAULT_PAGE;
+use crate::invites::{now_ms, InviteEnd};
 use crate::state::{State, Tx};
-use ruggine_common::ServerToClient;
+use crate::storage::Mutation;
+use ruggine_common::{ErrorCode, ServerToClient};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 pub async fn handle(
     group: String,
     invite_code: String,
-    client_id: ClientId,
+    session: &Session,
     tx: &Tx,
     state: &Arc<RwLock<State>>,
-) -> CommandResult {
+) {
     let mut st = state.write().await;
 
     // Non consumare il codice subito: verifica prima che il join sia valido
-    let (g, allowed) = match st.invites.get(&invite_code).cloned() {
+    let invite = match st.invites.get(&invite_code).cloned() {
         Some(v) => v,
         None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: "Invito non valido".into(),
-            });
-            return CommandResult::continue_with(client_id);
+            let _ = tx.send(ServerToClient::error(st.invite_error(&invite_code)));
+            return;
         }
     };
 
-    if g != group {
-        let _ = tx.send(ServerToClient::Error {
-            reason: "Invito non per questo gruppo".into(),
-        });
-        return CommandResult::continue_with(client_id);
+    if invite.group != group {
+        let _ = tx.send(ServerToClient::error(ErrorCode::InviteWrongGroup {
+            group: group.clone(),
+        }));
+        return;
     }
 
-    let id = match client_id {
-        Some(id) => id,
-        None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: "Non registrato".into(),
-            });
-            return CommandResult::continue_with(client_id);
-        }
-    };
+    // scaduto ma non ancora rimosso dal task periodico
+    if invite.is_expired(now_ms()) {
+        st.retire_invite(&invite_code, InviteEnd::Expired);
+        let _ = tx.send(ServerToClient::error(ErrorCode::InviteExpired));
+        return;
+    }
 
-    let my_nick = st.nicks_by_id.get(&id).cloned().unwrap_or_default();
-    if !my_nick.eq_ignore_ascii_case(&allowed) {
-        let _ = tx.send(ServerToClient::Error {
-            reason: format!("Invito destinato a {allowed}"),
-        });
-        return CommandResult::continue_with(client_id);
+    let my_nick = session.nick.clone();
+    if let Some(allowed) = &invite.nick {
+        if !my_nick.eq_ignore_ascii_case(allowed) {
+            let _ = tx.send(ServerToClient::error(ErrorCode::InviteForOther {
+                nick: allowed.clone(),
+            }));
+            return;
+        }
     }
 
     // Se già membro del gruppo, evita duplicati e segnala l'errore all'utente
     if st
         .groups
         .get(&group)
-        .map_or(false, |g| g.members.contains(&id))
+        .is_some_and(|g| g.members.contains(&my_nick))
     {
-        let _ = tx.send(ServerToClient::Error {
-            reason: format!("Sei già membro del gruppo {group}"),
-        });
-        return CommandResult::continue_with(client_id);
+        let _ = tx.send(ServerToClient::error(ErrorCode::AlreadyMember {
+            group: group.clone(),
+        }));
+        return;
     }
 
-    // L'utente può entrare: rimuovi il codice usato e qualsiasi altro invito pendente per lo stesso (gruppo, utente)
+    // L'utente può entrare: conta l'uso del codice, che viene ritirato quando raggiunge il limite
+    // (subito, per un invito destinato a un utente)
+    match invite.limit() {
+        Some(max_uses) if invite.uses + 1 >= max_uses => {
+            st.retire_invite(&invite_code, InviteEnd::UsedUp { max_uses })
+        }
+        _ => st.commit(Mutation::InviteUsed {
+            code: invite_code.clone(),
+        }),
+    }
+    // Rimuovi anche qualsiasi altro invito pendente per lo stesso (gruppo, utente)
     // in modo che eventuali vecchi codici non diventino riutilizzabili in seguito
-    // Consuma il codice usato
-    st.invites.remove(&invite_code);
     let to_delete: Vec<String> = st
         .invites
         .iter()
-        .filter_map(|(code, (gname, nick))| {
-            if gname == &group && nick.eq_ignore_ascii_case(&my_nick) {
+        .filter_map(|(code, invite)| {
+            let nick = invite.nick.as_ref()?;
+            if invite.group == group && nick.eq_ignore_ascii_case(&my_nick) {
                 Some(code.clone())
             } else {
                 None
@@ -81,16 +92,27 @@ pub async fn handle(
         })
         .collect();
     for c in to_delete {
-        st.invites.remove(&c);
+        st.commit(Mutation::InviteRemoved { code: c });
     }
 
-    st.groups
-        .entry(group.clone())
-        .or_default()
-        .members
-        .insert(id);
+    st.commit(Mutation::MemberAdded {
+        group: group.clone(),
+        nick: my_nick,
+    });
 
-    let _ = tx.send(ServerToClient::Joined { group });
+    let _ = tx.send(ServerToClient::Joined {
+        group: group.clone(),
+    });
 
-    CommandResult::continue_with(client_id)
+    // Replay della cronologia recente del gruppo per il nuovo membro
+    let (messages, has_more) =
+        st.history
+            .page(&group, None, DEFAULT_PAGE, st.limits.history_page_bytes());
+    if !messages.is_empty() {
+        let _ = tx.send(ServerToClient::HistoryPage {
+            group,
+            messages,
+            has_more,
+        });
+    }
 }
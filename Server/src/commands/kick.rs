@@ -0,0 +1,53 @@
+/*
+Gestisce l'espulsione di un membro da un gruppo. Il proprietario può espellere chiunque,
+gli amministratori solo i membri semplici.
+*/
+
+use super::Session;
+use crate::catalog::t;
+use crate::state::{Role, State, Tx};
+use ruggine_common::{ErrorCode, GroupAction, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    group: String,
+    nick: String,
+    session: &Session,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) {
+    let mut st = state.write().await;
+    let my_nick = session.nick.clone();
+
+    let (my_role, target, target_role) = match st.roles_in(&group, &my_nick, &nick) {
+        Ok(roles) => roles,
+        Err(code) => {
+            let _ = tx.send(ServerToClient::error(code));
+            return;
+        }
+    };
+    if target == my_nick {
+        let _ = tx.send(ServerToClient::error(ErrorCode::KickSelf));
+        return;
+    }
+    // serve un ruolo almeno di amministratore e strettamente superiore a quello del destinatario
+    if my_role < Role::Admin || my_role <= target_role {
+        let _ = tx.send(ServerToClient::error(ErrorCode::NotAllowed {
+            group: group.clone(),
+            action: GroupAction::Kick,
+        }));
+        return;
+    }
+
+    st.remove_member(&group, &target);
+    if let Some(txt) = st.tx_of_nick(&target) {
+        let _ = txt.send(ServerToClient::MessageServer {
+            text: t!("group.kicked_you", group, my_nick),
+        });
+        let _ = txt.send(ServerToClient::Left {
+            group: group.clone(),
+        });
+    }
+    st.notify_group(&group, &t!("group.kicked", target, group, my_nick));
+}
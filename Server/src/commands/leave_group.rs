@@ -2,49 +2,31 @@
 Gestisce la logica di uscita da un gruppo. Rimuove l'utente e cancella il gruppo se vuoto.
 */
 
-use super::{ClientId, CommandResult};
+use super::Session;
 use crate::state::{State, Tx};
-use ruggine_common::ServerToClient;
+use ruggine_common::{ErrorCode, ServerToClient};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-pub async fn handle(
-    group: String,
-    client_id: ClientId,
-    tx: &Tx,
-    state: &Arc<RwLock<State>>,
-) -> CommandResult {
+pub async fn handle(group: String, session: &Session, tx: &Tx, state: &Arc<RwLock<State>>) {
     let mut st = state.write().await;
-    let id = match client_id {
-        Some(id) => id,
-        None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: "Non registrato".into(),
-            });
-            return CommandResult::continue_with(client_id);
-        }
-    };
-
-    match st.groups.get_mut(&group) {
+    let my_nick = &session.nick;
+    match st.groups.get(&group) {
         Some(g) => {
-            if !g.members.remove(&id) {
-                let _ = tx.send(ServerToClient::Error {
-                    reason: format!("Non sei membro del gruppo {group}"),
-                });
-                return CommandResult::continue_with(client_id);
-            }
-            if g.members.is_empty() {
-                st.groups.remove(&group);
+            if !g.members.contains(my_nick) {
+                let _ = tx.send(ServerToClient::error(ErrorCode::NotMember {
+                    group: group.clone(),
+                }));
+                return;
             }
+            // rimuove il membro e cancella il gruppo se vuoto
+            st.remove_member(&group, my_nick);
             let _ = tx.send(ServerToClient::Left { group });
         }
         None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: format!("Gruppo {group} inesistente"),
-            });
-            return CommandResult::continue_with(client_id);
+            let _ = tx.send(ServerToClient::error(ErrorCode::GroupNotFound {
+                group: group.clone(),
+            }));
         }
     }
-
-    CommandResult::continue_with(client_id)
 }
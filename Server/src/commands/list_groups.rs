@@ -2,40 +2,25 @@
 Restituisce la lista dei gruppi di cui l'utente è membro. Segnala errore se non appartiene a nessun gruppo.
 */
 
-use super::{ClientId, CommandResult};
+use super::Session;
 use crate::state::{State, Tx};
-use ruggine_common::ServerToClient;
+use ruggine_common::{ErrorCode, ServerToClient};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-pub async fn handle(client_id: ClientId, tx: &Tx, state: &Arc<RwLock<State>>) -> CommandResult {
+pub async fn handle(session: &Session, tx: &Tx, state: &Arc<RwLock<State>>) {
     let st = state.read().await;
-
-    let id = match client_id {
-        Some(id) => id,
-        None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: "Non registrato".into(),
-            });
-            return CommandResult::continue_with(client_id);
-        }
-    };
-
     let groups: Vec<String> = st
         .groups
         .iter()
-        .filter(|(_, gr)| gr.members.contains(&id))
+        .filter(|(_, gr)| gr.members.contains(&session.nick))
         .map(|(name, _)| name.clone())
         .collect();
 
     if groups.is_empty() {
-        let _ = tx.send(ServerToClient::Error {
-            reason: "Nessun gruppo di appartenenza".into(),
-        });
-        return CommandResult::continue_with(client_id);
+        let _ = tx.send(ServerToClient::error(ErrorCode::NoGroups));
+        return;
     }
 
     let _ = tx.send(ServerToClient::Groups { groups });
-
-    CommandResult::continue_with(client_id)
 }
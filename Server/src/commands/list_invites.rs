@@ -0,0 +1,31 @@
+/*
+Gestisce l'elenco degli inviti ancora validi di un gruppo, con autore, scadenza e usi.
+Lo possono chiedere solo il proprietario e gli amministratori, perché contiene i codici.
+*/
+
+use super::Session;
+use crate::invites::now_ms;
+use crate::state::{State, Tx};
+use ruggine_common::{GroupAction, InviteEntry, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(group: String, session: &Session, tx: &Tx, state: &Arc<RwLock<State>>) {
+    let st = state.read().await;
+    if let Err(code) = st.require_manager(&group, &session.nick, GroupAction::ManageInvites) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
+    }
+
+    // gli inviti scaduti non ancora rimossi dal task periodico non vengono mostrati
+    let now = now_ms();
+    let mut invites: Vec<InviteEntry> = st
+        .invites
+        .iter()
+        .filter(|(_, i)| i.group == group && !i.is_expired(now))
+        .map(|(code, i)| i.entry(code))
+        .collect();
+    invites.sort_by_key(|i| i.created_at);
+
+    let _ = tx.send(ServerToClient::Invites { group, invites });
+}
@@ -0,0 +1,153 @@
+/*
+Gestisce l'accesso a un account esistente verificando la password con l'hash salvato.
+*/
+
+use super::{register, ClientId, CommandResult};
+use crate::auth::{verify_dummy_blocking, verify_password_blocking};
+use crate::state::{State, Tx};
+use ruggine_common::ErrorCode;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    nick: String,
+    password: String,
+    client_id: ClientId,
+    peer: IpAddr,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) -> CommandResult {
+    if client_id.is_some() {
+        return register::reject(ErrorCode::AlreadyAuthenticated, client_id, tx);
+    }
+
+    // Copia dell'account, così la verifica (lenta) avviene senza tenere il lock
+    let account = state
+        .read()
+        .await
+        .accounts
+        .get(&nick.to_ascii_lowercase())
+        .cloned();
+
+    // Stesso messaggio per utente inesistente e password errata
+    let account = match account {
+        Some(a) if verify_password_blocking(password.clone(), a.password_hash.clone()).await => a,
+        Some(_) => return register::reject(ErrorCode::WrongCredentials, client_id, tx),
+        // anche senza account si calcola un hash: il tempo di risposta non rivela quali nickname esistono
+        None => {
+            verify_dummy_blocking(password).await;
+            return register::reject(ErrorCode::WrongCredentials, client_id, tx);
+        }
+    };
+
+    let mut st = state.write().await;
+    register::open_session(&account.nick, peer, client_id, tx, &mut st)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::create_account;
+    use crate::outbox::{channel, Next, QueuePolicy, QueueStats, Rx};
+    use ruggine_common::{Envelope, ServerToClient};
+    use std::net::Ipv4Addr;
+
+    const PEER: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn client() -> (Tx, Rx) {
+        channel(16, QueuePolicy::DropNewest, Arc::new(QueueStats::default()))
+    }
+
+    // Esito dell'accesso: Ok(()) se la sessione è stata aperta, altrimenti il codice d'errore
+    async fn registered(rx: &mut Rx) -> Result<(), ErrorCode> {
+        match rx.recv().await {
+            Next::Send(Envelope {
+                body: ServerToClient::Registered { ok: true, .. },
+                ..
+            }) => Ok(()),
+            Next::Send(Envelope {
+                body:
+                    ServerToClient::Registered {
+                        ok: false,
+                        error: Some(code),
+                        ..
+                    },
+                ..
+            }) => Err(code),
+            _ => panic!("atteso Registered"),
+        }
+    }
+
+    async fn login(
+        nick: &str,
+        password: &str,
+        state: &Arc<RwLock<State>>,
+    ) -> Result<(), ErrorCode> {
+        let (tx, mut rx) = client();
+        handle(nick.into(), password.into(), None, PEER, &tx, state).await;
+        registered(&mut rx).await
+    }
+
+    // Crea l'account "Alice" e chiude subito la sessione aperta dalla registrazione
+    async fn with_account() -> Arc<RwLock<State>> {
+        let state = Arc::new(RwLock::new(State::default()));
+        let (tx, mut rx) = client();
+        let res =
+            create_account::handle("Alice".into(), "segreta1".into(), None, PEER, &tx, &state)
+                .await;
+        assert_eq!(registered(&mut rx).await, Ok(()));
+        state.write().await.end_session(&res.new_client_id.unwrap());
+        state
+    }
+
+    #[tokio::test]
+    async fn account_stores_only_a_salted_hash() {
+        let state = with_account().await;
+        let st = state.read().await;
+        let account = &st.accounts["alice"];
+        assert_eq!(account.nick, "Alice");
+        assert!(account.password_hash.starts_with("$argon2"));
+        assert!(!account.password_hash.contains("segreta1"));
+        drop(st);
+
+        // il nickname è unico senza distinzione di maiuscole
+        let (tx, mut rx) = client();
+        create_account::handle("ALICE".into(), "altra-pw".into(), None, PEER, &tx, &state).await;
+        assert!(matches!(
+            registered(&mut rx).await,
+            Err(ErrorCode::NickTaken { existing, .. }) if existing == "Alice"
+        ));
+    }
+
+    #[tokio::test]
+    async fn login_checks_the_password() {
+        let state = with_account().await;
+        assert_eq!(
+            login("alice", "sbagliata", &state).await,
+            Err(ErrorCode::WrongCredentials)
+        );
+        assert!(state.read().await.users_by_nick.is_empty());
+        assert_eq!(login("alice", "segreta1", &state).await, Ok(()));
+        assert!(state.read().await.users_by_nick.contains_key("Alice"));
+    }
+
+    #[tokio::test]
+    async fn unknown_nick_gets_the_same_answer_as_a_wrong_password() {
+        let state = with_account().await;
+        // la password dell'hash fittizio non apre nessun account
+        assert!(crate::auth::verify_password(
+            "ruggine-dummy-password",
+            crate::auth::dummy_hash()
+        ));
+        assert_eq!(
+            login("bob", "ruggine-dummy-password", &state).await,
+            Err(ErrorCode::WrongCredentials)
+        );
+        assert_eq!(
+            login("bob", "segreta1", &state).await,
+            login("alice", "sbagliata", &state).await
+        );
+        assert!(state.read().await.users_by_nick.is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 /*
-Gestisce la disconnessione di un utente dal server. Aggiorna lo stato e rimuove l'utente da gruppi e strutture dati.
+Gestisce la disconnessione di un utente dal server. Chiude la sessione: gruppi e inviti dell'account restano, si esce da un gruppo solo con LeaveGroup.
 */
 
 use super::{ClientId, CommandResult};
@@ -16,8 +16,7 @@ pub async fn handle(
     let mut st = state.write().await;
     let mut new_id = client_id;
     if let Some(id) = new_id.take() {
-        let nick_opt = st.nicks_by_id.get(&id).cloned();
-        if let Some(nick) = &nick_opt {
+        if let Some(nick) = st.nicks_by_id.get(&id) {
             if let Some(r) = &reason {
                 if r.to_lowercase().contains("ctrl") || r.to_lowercase().contains("c") {
                     println!("{} si è disconnesso dal server (ctrl+c)", nick);
@@ -27,28 +26,9 @@ pub async fn handle(
             } else {
                 println!("{} si è disconnesso dal server", nick);
             }
-            st.users_by_nick.remove(nick);
         }
-        // Rimuovi l'utente da tutti i gruppi e cancella i gruppi vuoti
-        for (_name, g) in st.groups.iter_mut() {
-            g.members.remove(&id);
-        }
-        st.groups.retain(|_, g| !g.members.is_empty());
-
-        // Rimuovi tutti gli inviti associati al nickname dell'utente
-        if let Some(nick) = &nick_opt {
-            let to_remove: Vec<String> = st.invites.iter()
-                .filter_map(|(code, (_group, invite_nick))| {
-                    if invite_nick.eq_ignore_ascii_case(nick) { Some(code.clone()) } else { None }
-                })
-                .collect();
-            for code in to_remove {
-                st.invites.remove(&code);
-            }
-        }
-
-        st.nicks_by_id.remove(&id);
-        st.clients.remove(&id);
+        // chiude solo la sessione: l'appartenenza ai gruppi è dell'account e resta
+        st.end_session(&id);
     }
 
     CommandResult {
@@ -1,26 +1,44 @@
 /*
 Modulo centrale dei comandi: definisce il tipo CommandResult, gestisce il dispatch dei comandi e importa tutti gli handler specifici.
+I comandi che richiedono l'accesso ricevono una Session già verificata: il dispatch controlla una sola volta
+che la connessione sia autenticata e ne ricava il nickname dallo stato, senza fidarsi di quanto dichiara il client.
 */
 
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use ruggine_common::ClientToServer;
+use ruggine_common::{ClientToServer, ErrorCode, ServerToClient};
 
 use crate::state::{State, Tx};
 
+pub mod create_account;
 pub mod create_group;
+pub mod create_invite_link;
+pub mod demote;
+pub mod direct_message;
 pub mod global_message;
+pub mod hello;
+pub mod history;
 pub mod invite;
 pub mod join_group;
+pub mod kick;
 pub mod leave_group;
 pub mod list_groups;
+pub mod list_invites;
 pub mod list_users;
+pub mod login;
 pub mod logout;
+pub mod moderate;
 pub mod ping;
+pub mod promote;
 pub mod register;
+pub mod rename_group;
+pub mod resume;
+pub mod revoke_invite;
 pub mod send_message;
+pub mod transfer_ownership;
 
 pub type ClientId = Option<Uuid>;
 
@@ -45,31 +63,330 @@ impl CommandResult {
     }
 }
 
+/// Utente autenticato sulla connessione, ricavato dallo stato del server
+pub struct Session {
+    pub id: Uuid,
+    pub nick: String,
+}
+
 pub async fn dispatch(
     msg: ClientToServer,
     client_id: ClientId,
+    peer: IpAddr,
     tx: &Tx,
     state: &Arc<RwLock<State>>,
 ) -> CommandResult {
     use ClientToServer::*;
+    // comandi ammessi anche prima dell'accesso
     match msg {
-        Register {
-            nick,
-            client_id: req_id,
-        } => register::handle(nick, req_id, client_id, tx, state).await,
-        CreateGroup { group } => create_group::handle(group, client_id, tx, state).await,
-        Invite { group, nick } => invite::handle(group, nick, client_id, tx, state).await,
-        LeaveGroup { group } => leave_group::handle(group, client_id, tx, state).await,
+        Hello { .. } => return hello::handle(client_id, tx),
+        CreateAccount { nick, password } => {
+            return create_account::handle(nick, password, client_id, peer, tx, state).await
+        }
+        Login { nick, password } => {
+            return login::handle(nick, password, client_id, peer, tx, state).await
+        }
+        Resume { nick, session } => {
+            return resume::handle(nick, session, client_id, peer, tx, state).await
+        }
+        Logout { reason } => return logout::handle(reason, client_id, tx, state).await,
+        Ping => return ping::handle(client_id, tx, state).await,
+        _ => {}
+    }
+
+    // tutti gli altri richiedono una sessione valida
+    let nick = match client_id {
+        Some(id) => state.read().await.nick_of(&id),
+        None => None,
+    };
+    let (Some(id), Some(nick)) = (client_id, nick) else {
+        let _ = tx.send(ServerToClient::error(ErrorCode::NotRegistered));
+        return CommandResult::continue_with(client_id);
+    };
+    let session = Session { id, nick };
+
+    // chi è stato silenziato da un operatore non può scrivere, ma può usare gli altri comandi
+    if matches!(
+        msg,
+        SendMessage { .. } | GlobalMessage { .. } | DirectMessage { .. }
+    ) {
+        if let Some(seconds) = state.read().await.muted_for(&session.nick) {
+            let _ = tx.send(ServerToClient::error(ErrorCode::Muted { seconds }));
+            return CommandResult::continue_with(client_id);
+        }
+    }
+
+    match msg {
+        CreateGroup { group } => create_group::handle(group, &session, tx, state).await,
+        Invite { group, nick, ttl } => invite::handle(group, nick, ttl, &session, tx, state).await,
+        CreateInviteLink {
+            group,
+            ttl,
+            max_uses,
+        } => create_invite_link::handle(group, ttl, max_uses, &session, tx, state).await,
+        ListInvites { group } => list_invites::handle(group, &session, tx, state).await,
+        RevokeInvite { code } => revoke_invite::handle(code, &session, tx, state).await,
+        LeaveGroup { group } => leave_group::handle(group, &session, tx, state).await,
+        Kick { group, nick } => kick::handle(group, nick, &session, tx, state).await,
+        Promote { group, nick } => promote::handle(group, nick, &session, tx, state).await,
+        Demote { group, nick } => demote::handle(group, nick, &session, tx, state).await,
+        TransferOwnership { group, nick } => {
+            transfer_ownership::handle(group, nick, &session, tx, state).await
+        }
+        RenameGroup { group, new_name } => {
+            rename_group::handle(group, new_name, &session, tx, state).await
+        }
         JoinGroup { group, invite_code } => {
-            join_group::handle(group, invite_code, client_id, tx, state).await
+            join_group::handle(group, invite_code, &session, tx, state).await
+        }
+        SendMessage { group, text } => send_message::handle(group, text, &session, tx, state).await,
+        ListGroups => list_groups::handle(&session, tx, state).await,
+        ListUsers => list_users::handle(&session, tx, state).await,
+        GlobalMessage { text } => global_message::handle(text, &session, tx, state).await,
+        DirectMessage { to, text } => direct_message::handle(to, text, &session, tx, state).await,
+        History {
+            group,
+            before,
+            limit,
+        } => history::handle(group, before, limit, &session, tx, state).await,
+        Moderate { action } => moderate::handle(action, &session, tx, state).await,
+        Hello { .. }
+        | CreateAccount { .. }
+        | Login { .. }
+        | Resume { .. }
+        | Logout { .. }
+        | Ping => {
+            unreachable!("gestiti sopra")
+        }
+    }
+    CommandResult::continue_with(Some(session.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryStore;
+    use crate::outbox::{channel, Next, QueuePolicy, QueueStats, Rx};
+    use crate::state::{Account, Group};
+    use futures::FutureExt;
+    use ruggine_common::{Envelope, GroupAction};
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+
+    // Gruppo "team": olga proprietaria, ada e bea amministratrici, max e zoe membri; ugo è fuori dal gruppo
+    struct Fixture {
+        state: Arc<RwLock<State>>,
+        clients: HashMap<&'static str, (Uuid, Tx, Rx)>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("ruggine-roles-{}", Uuid::new_v4()));
+            let mut st = State {
+                history: HistoryStore::new(dir, 10),
+                ..State::default()
+            };
+            let mut clients = HashMap::new();
+            for nick in ["olga", "ada", "bea", "max", "zoe", "ugo"] {
+                let (tx, rx) =
+                    channel(64, QueuePolicy::DropNewest, Arc::new(QueueStats::default()));
+                let id = Uuid::new_v4();
+                st.accounts.insert(
+                    nick.to_string(),
+                    Account {
+                        nick: nick.to_string(),
+                        password_hash: String::new(),
+                    },
+                );
+                st.users_by_nick.insert(nick.to_string(), id);
+                st.nicks_by_id.insert(id, nick.to_string());
+                st.clients.insert(id, tx.untagged());
+                clients.insert(nick, (id, tx, rx));
+            }
+            st.groups.insert(
+                "team".into(),
+                Group {
+                    members: ["olga", "ada", "bea", "max", "zoe"]
+                        .map(String::from)
+                        .into(),
+                    owner: "olga".into(),
+                    admins: ["ada", "bea"].map(String::from).into(),
+                },
+            );
+            Self {
+                state: Arc::new(RwLock::new(st)),
+                clients,
+            }
+        }
+
+        // Esegue il comando per conto di `nick` e restituisce l'errore ricevuto, se c'è
+        async fn run(&mut self, nick: &str, msg: ClientToServer) -> Option<ErrorCode> {
+            let (id, tx, rx) = self.clients.get_mut(nick).unwrap();
+            let peer = IpAddr::V4(Ipv4Addr::LOCALHOST);
+            dispatch(msg, Some(*id), peer, tx, &self.state).await;
+            let mut error = None;
+            while let Some(next) = rx.recv().now_or_never() {
+                if let Next::Send(Envelope {
+                    body: ServerToClient::Error { code, .. },
+                    ..
+                }) = next
+                {
+                    error = Some(code);
+                }
+            }
+            error
         }
-        SendMessage { group, text, nick } => {
-            send_message::handle(group, text, nick, client_id, tx, state).await
+
+        async fn group(&self, name: &str) -> Option<(String, Vec<String>, Vec<String>)> {
+            let st = self.state.read().await;
+            let g = st.groups.get(name)?;
+            let mut members: Vec<String> = g.members.iter().cloned().collect();
+            let mut admins: Vec<String> = g.admins.iter().cloned().collect();
+            members.sort();
+            admins.sort();
+            Some((g.owner.clone(), admins, members))
         }
-        ListGroups => list_groups::handle(client_id, tx, state).await,
-        ListUsers => list_users::handle(client_id, tx, state).await,
-        GlobalMessage { text } => global_message::handle(text, client_id, tx, state).await,
-        Logout { reason } => logout::handle(reason, client_id, tx, state).await,
-        Ping => ping::handle(client_id, tx, state).await,
+    }
+
+    fn kick(nick: &str) -> ClientToServer {
+        ClientToServer::Kick {
+            group: "team".into(),
+            nick: nick.into(),
+        }
+    }
+
+    fn not_allowed(action: GroupAction) -> Option<ErrorCode> {
+        Some(ErrorCode::NotAllowed {
+            group: "team".into(),
+            action,
+        })
+    }
+
+    #[tokio::test]
+    async fn kick_needs_a_higher_role() {
+        let mut f = Fixture::new();
+        assert_eq!(
+            f.run("max", kick("zoe")).await,
+            not_allowed(GroupAction::Kick)
+        );
+        assert_eq!(
+            f.run("ada", kick("bea")).await,
+            not_allowed(GroupAction::Kick)
+        );
+        assert_eq!(
+            f.run("ada", kick("olga")).await,
+            not_allowed(GroupAction::Kick)
+        );
+        assert_eq!(f.run("olga", kick("olga")).await, Some(ErrorCode::KickSelf));
+        assert!(matches!(
+            f.run("ugo", kick("max")).await,
+            Some(ErrorCode::NotMember { .. })
+        ));
+        assert!(matches!(
+            f.run("olga", kick("ugo")).await,
+            Some(ErrorCode::UserNotMember { .. })
+        ));
+
+        // l'amministratrice espelle un membro, la proprietaria un'amministratrice
+        assert_eq!(f.run("ada", kick("MAX")).await, None);
+        assert_eq!(f.run("olga", kick("bea")).await, None);
+        let (_, admins, members) = f.group("team").await.unwrap();
+        assert_eq!(admins, ["ada"]);
+        assert_eq!(members, ["ada", "olga", "zoe"]);
+    }
+
+    #[tokio::test]
+    async fn only_the_owner_promotes_and_demotes() {
+        let mut f = Fixture::new();
+        let promote = |nick: &str| ClientToServer::Promote {
+            group: "team".into(),
+            nick: nick.into(),
+        };
+        let demote = |nick: &str| ClientToServer::Demote {
+            group: "team".into(),
+            nick: nick.into(),
+        };
+        assert_eq!(
+            f.run("ada", promote("max")).await,
+            not_allowed(GroupAction::Promote)
+        );
+        assert_eq!(
+            f.run("ada", demote("bea")).await,
+            not_allowed(GroupAction::Demote)
+        );
+        assert!(matches!(
+            f.run("olga", promote("ada")).await,
+            Some(ErrorCode::AlreadyAdmin { .. })
+        ));
+        assert!(matches!(
+            f.run("olga", demote("max")).await,
+            Some(ErrorCode::NotAdmin { .. })
+        ));
+
+        assert_eq!(f.run("olga", promote("max")).await, None);
+        assert_eq!(f.run("olga", demote("ada")).await, None);
+        let (owner, admins, _) = f.group("team").await.unwrap();
+        assert_eq!(owner, "olga");
+        assert_eq!(admins, ["bea", "max"]);
+    }
+
+    #[tokio::test]
+    async fn transfer_keeps_the_old_owner_as_admin() {
+        let mut f = Fixture::new();
+        let transfer = |nick: &str| ClientToServer::TransferOwnership {
+            group: "team".into(),
+            nick: nick.into(),
+        };
+        assert_eq!(
+            f.run("ada", transfer("ada")).await,
+            not_allowed(GroupAction::TransferOwnership)
+        );
+        assert_eq!(
+            f.run("olga", transfer("olga")).await,
+            Some(ErrorCode::AlreadyOwner {
+                group: "team".into()
+            })
+        );
+
+        assert_eq!(f.run("olga", transfer("ada")).await, None);
+        let (owner, admins, _) = f.group("team").await.unwrap();
+        assert_eq!(owner, "ada");
+        assert_eq!(admins, ["bea", "olga"]);
+        // la vecchia proprietaria ora è solo amministratrice
+        assert_eq!(
+            f.run("olga", kick("bea")).await,
+            not_allowed(GroupAction::Kick)
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_keeps_members_and_roles() {
+        let mut f = Fixture::new();
+        let rename = |name: &str| ClientToServer::RenameGroup {
+            group: "team".into(),
+            new_name: name.into(),
+        };
+        f.state
+            .write()
+            .await
+            .groups
+            .insert("Altro".into(), Group::default());
+        assert_eq!(
+            f.run("max", rename("squadra")).await,
+            not_allowed(GroupAction::Rename)
+        );
+        assert!(matches!(
+            f.run("ada", rename("altro")).await,
+            Some(ErrorCode::GroupTaken { existing, .. }) if existing == "Altro"
+        ));
+        assert!(matches!(
+            f.run("ada", rename("Ugo")).await,
+            Some(ErrorCode::NameUsedByUser { .. })
+        ));
+
+        let before = f.group("team").await;
+        assert_eq!(f.run("ada", rename("squadra")).await, None);
+        assert!(f.group("team").await.is_none());
+        assert_eq!(f.group("squadra").await, before);
     }
 }
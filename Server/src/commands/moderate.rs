@@ -0,0 +1,270 @@
+/*
+Gestisce le azioni di moderazione degli operatori del server (--operator): disconnessione, ban per
+nickname o per indirizzo IP e silenzio temporaneo. Gli operatori non possono agire su altri operatori.
+I ban vengono salvati su disco (vedi bans.rs); il silenzio resta solo in memoria.
+*/
+
+use super::Session;
+use crate::bans::Ban;
+use crate::catalog::t;
+use crate::state::{State, Tx};
+use ruggine_common::{ErrorCode, ModAction, ServerToClient};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub async fn handle(action: ModAction, session: &Session, tx: &Tx, state: &Arc<RwLock<State>>) {
+    let mut st = state.write().await;
+    if !st.is_operator(&session.nick) {
+        let _ = tx.send(ServerToClient::error(ErrorCode::NotOperator));
+        return;
+    }
+    match apply(action, &session.nick, &mut st) {
+        Ok(text) => {
+            info!("[moderazione] {}: {}", session.nick, text);
+            let _ = tx.send(ServerToClient::MessageServer { text });
+        }
+        Err(code) => {
+            let _ = tx.send(ServerToClient::error(code));
+        }
+    }
+}
+
+// Esegue l'azione e restituisce la conferma per l'operatore
+fn apply(action: ModAction, by: &str, st: &mut State) -> Result<String, ErrorCode> {
+    match action {
+        ModAction::Kick { nick, reason } => {
+            let nick = target(st, &nick)?;
+            let id = online(st, &nick)?;
+            let by = by.to_string();
+            st.disconnect(&id, ErrorCode::KickedByOperator { by, reason });
+            Ok(t!("mod.kicked", nick))
+        }
+        ModAction::Ban { nick, reason } => {
+            let nick = target(st, &nick)?;
+            save(st.bans.ban_nick(&nick, Ban::new(by, reason.clone())));
+            if let Some(id) = st.users_by_nick.get(&nick).copied() {
+                st.disconnect(&id, ErrorCode::Banned { reason });
+            }
+            Ok(t!("mod.banned", nick))
+        }
+        ModAction::Unban { nick } => match st.bans.unban_nick(&nick) {
+            Ok(false) => Err(ErrorCode::NotBanned { target: nick }),
+            res => {
+                save(res.map(|_| ()));
+                Ok(t!("mod.unbanned", nick))
+            }
+        },
+        ModAction::BanIp {
+            target: value,
+            reason,
+        } => {
+            let ip = match value.parse::<IpAddr>() {
+                Ok(ip) => ip.to_canonical(),
+                // un nickname: si usa l'indirizzo della sua connessione
+                Err(_) => st
+                    .account_nick(&value)
+                    .and_then(|n| st.users_by_nick.get(&n))
+                    .and_then(|id| st.peers.get(id))
+                    .copied()
+                    .ok_or(ErrorCode::InvalidAddress { value })?,
+            };
+            let sessions = sessions_from(st, ip);
+            // un operatore non può bandire l'indirizzo da cui è collegato un operatore (anche se stesso)
+            if let Some(op) = sessions
+                .iter()
+                .filter_map(|id| st.nick_of(id))
+                .find(|n| st.is_operator(n))
+            {
+                return Err(ErrorCode::TargetIsOperator { nick: op });
+            }
+            save(st.bans.ban_ip(ip, Ban::new(by, reason.clone())));
+            for id in &sessions {
+                let reason = reason.clone();
+                st.disconnect(id, ErrorCode::Banned { reason });
+            }
+            Ok(t!("mod.ip_banned", ip, sessions.len()))
+        }
+        ModAction::UnbanIp { ip } => {
+            let addr = ip
+                .parse::<IpAddr>()
+                .map_err(|_| ErrorCode::InvalidAddress { value: ip.clone() })?;
+            match st.bans.unban_ip(addr) {
+                Ok(false) => Err(ErrorCode::NotBanned { target: ip }),
+                res => {
+                    save(res.map(|_| ()));
+                    Ok(t!("mod.ip_unbanned", ip))
+                }
+            }
+        }
+        ModAction::Mute { nick, seconds } => {
+            let nick = target(st, &nick)?;
+            let now = Instant::now();
+            // le scadenze passate non servono più
+            st.muted.retain(|_, until| *until > now);
+            st.muted
+                .insert(nick.clone(), now + Duration::from_secs(seconds));
+            if let Some(txt) = st.tx_of_nick(&nick) {
+                let _ = txt.send(ServerToClient::MessageServer {
+                    text: t!("mod.muted_you", by, seconds),
+                });
+            }
+            Ok(t!("mod.muted", nick, seconds))
+        }
+        ModAction::Unmute { nick } => {
+            let nick = st.account_nick(&nick).unwrap_or(nick);
+            if st.muted_for(&nick).is_none() {
+                return Err(ErrorCode::NotMuted { nick });
+            }
+            st.muted.remove(&nick);
+            if let Some(txt) = st.tx_of_nick(&nick) {
+                let _ = txt.send(ServerToClient::MessageServer {
+                    text: t!("mod.unmuted_you", by),
+                });
+            }
+            Ok(t!("mod.unmuted", nick))
+        }
+    }
+}
+
+// Nickname canonico di un account esistente che non sia un operatore
+fn target(st: &State, nick: &str) -> Result<String, ErrorCode> {
+    let nick = st
+        .account_nick(nick)
+        .ok_or_else(|| ErrorCode::UserNotFound {
+            nick: nick.to_string(),
+        })?;
+    if st.is_operator(&nick) {
+        return Err(ErrorCode::TargetIsOperator { nick });
+    }
+    Ok(nick)
+}
+
+// Sessione dell'utente, se connesso o in attesa di ripresa
+fn online(st: &State, nick: &str) -> Result<Uuid, ErrorCode> {
+    st.users_by_nick
+        .get(nick)
+        .copied()
+        .ok_or_else(|| ErrorCode::UserOffline {
+            nick: nick.to_string(),
+        })
+}
+
+// Sessioni aperte da un indirizzo
+fn sessions_from(st: &State, ip: IpAddr) -> Vec<Uuid> {
+    st.peers
+        .iter()
+        .filter(|(_, peer)| peer.to_canonical() == ip)
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+// Un errore di scrittura non annulla il ban, che resta valido fino al riavvio
+fn save(res: std::io::Result<()>) {
+    if let Err(e) = res {
+        warn!("Impossibile salvare l'elenco dei ban: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Account;
+    use std::net::Ipv4Addr;
+
+    const OPERATOR_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const USER_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    // "root" e "Sysop" sono operatori, "mallory" no; root e mallory sono connessi
+    fn state() -> State {
+        let mut st = State::default();
+        for nick in ["root", "Sysop", "mallory"] {
+            st.accounts.insert(
+                nick.to_ascii_lowercase(),
+                Account {
+                    nick: nick.to_string(),
+                    password_hash: String::new(),
+                },
+            );
+        }
+        st.operators = ["root", "sysop"].map(String::from).into();
+        for (nick, ip) in [("root", OPERATOR_IP), ("mallory", USER_IP)] {
+            let id = Uuid::new_v4();
+            st.users_by_nick.insert(nick.to_string(), id);
+            st.nicks_by_id.insert(id, nick.to_string());
+            st.peers.insert(id, ip);
+        }
+        st
+    }
+
+    fn is_operator(res: Result<String, ErrorCode>) -> bool {
+        matches!(res, Err(ErrorCode::TargetIsOperator { .. }))
+    }
+
+    #[test]
+    fn operators_cannot_act_on_operators() {
+        let mut st = state();
+        let reason = None;
+        assert!(is_operator(apply(
+            ModAction::Kick {
+                nick: "ROOT".into(),
+                reason: reason.clone(),
+            },
+            "sysop",
+            &mut st,
+        )));
+        assert!(is_operator(apply(
+            ModAction::Ban {
+                nick: "sysop".into(),
+                reason: reason.clone(),
+            },
+            "root",
+            &mut st,
+        )));
+        assert!(is_operator(apply(
+            ModAction::Mute {
+                nick: "root".into(),
+                seconds: 60,
+            },
+            "root",
+            &mut st,
+        )));
+        // anche per indirizzo: l'operatore collegato da quell'IP resta connesso
+        assert!(is_operator(apply(
+            ModAction::BanIp {
+                target: OPERATOR_IP.to_string(),
+                reason,
+            },
+            "sysop",
+            &mut st,
+        )));
+        assert_eq!(st.bans.counts(), (0, 0));
+        assert!(st.muted.is_empty());
+        assert_eq!(st.users_by_nick.len(), 2);
+    }
+
+    #[test]
+    fn banning_a_user_closes_the_session() {
+        let mut st = state();
+        let ban = ModAction::Ban {
+            nick: "Mallory".into(),
+            reason: Some("spam".into()),
+        };
+        assert!(apply(ban, "root", &mut st).is_ok());
+        assert!(st.bans.nick("mallory").is_some());
+        assert!(!st.users_by_nick.contains_key("mallory"));
+
+        // l'indirizzo si ricava dalla connessione dell'utente
+        let mut st = state();
+        let ban_ip = ModAction::BanIp {
+            target: "mallory".into(),
+            reason: None,
+        };
+        assert!(apply(ban_ip, "root", &mut st).is_ok());
+        assert!(st.bans.ip(USER_IP).is_some());
+        assert_eq!(st.users_by_nick.len(), 1);
+    }
+}
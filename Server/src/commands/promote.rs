@@ -0,0 +1,50 @@
+/*
+Gestisce la promozione di un membro ad amministratore del gruppo. Solo il proprietario può promuovere.
+*/
+
+use super::Session;
+use crate::catalog::t;
+use crate::state::{Role, State, Tx};
+use crate::storage::Mutation;
+use ruggine_common::{ErrorCode, GroupAction, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    group: String,
+    nick: String,
+    session: &Session,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) {
+    let mut st = state.write().await;
+    let my_nick = session.nick.clone();
+
+    let (my_role, target, target_role) = match st.roles_in(&group, &my_nick, &nick) {
+        Ok(roles) => roles,
+        Err(code) => {
+            let _ = tx.send(ServerToClient::error(code));
+            return;
+        }
+    };
+    if my_role != Role::Owner {
+        let _ = tx.send(ServerToClient::error(ErrorCode::NotAllowed {
+            group: group.clone(),
+            action: GroupAction::Promote,
+        }));
+        return;
+    }
+    if target_role != Role::Member {
+        let _ = tx.send(ServerToClient::error(ErrorCode::AlreadyAdmin {
+            nick: target.clone(),
+            group: group.clone(),
+        }));
+        return;
+    }
+
+    st.commit(Mutation::AdminAdded {
+        group: group.clone(),
+        nick: target.clone(),
+    });
+    st.notify_group(&group, &t!("group.promoted", target, group));
+}
@@ -1,83 +1,89 @@
 /*
-Gestisce la registrazione di un nuovo utente. Verifica la sintassi del nickname e l'unicità, aggiorna lo stato e invia la conferma.
+Associa una connessione a un account dopo CreateAccount o Login: controlla che l'account e l'indirizzo
+non siano banditi e che l'utente non sia già connesso,
+aggiorna lo stato, invia la conferma (con il token per riprendere la sessione) e consegna gli inviti
+ricevuti mentre l'utente era offline.
 */
 
 use super::{ClientId, CommandResult};
+use crate::invites::now_ms;
 use crate::state::{State, Tx};
-use crate::validation::validate_nick_syntax;
-use ruggine_common::ServerToClient;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use ruggine_common::{ErrorCode, ServerToClient};
+use std::net::IpAddr;
 use uuid::Uuid;
 
-pub async fn handle(
-    nick: String,
-    req_id: Uuid,
+// Invia il rifiuto dell'accesso lasciando la connessione non autenticata
+pub fn reject(code: ErrorCode, client_id: ClientId, tx: &Tx) -> CommandResult {
+    let _ = tx.send(ServerToClient::Registered {
+        ok: false,
+        reason: Some(code.to_string()),
+        session: None,
+        error: Some(code),
+    });
+    CommandResult::continue_with(client_id)
+}
+
+// Apre la sessione per l'account `nick` (nickname canonico)
+pub fn open_session(
+    nick: &str,
+    peer: IpAddr,
     client_id: ClientId,
     tx: &Tx,
-    state: &Arc<RwLock<State>>,
+    st: &mut State,
 ) -> CommandResult {
-    // Validazione sintassi lato server
-    if let Err(reason) = validate_nick_syntax(&nick) {
-        let _ = tx.send(ServerToClient::Registered {
-            ok: false,
-            reason: Some(reason),
-        });
-        return CommandResult::continue_with(client_id);
+    // ban per nickname, o per indirizzo deciso dopo l'apertura della connessione
+    if let Some(ban) = st.bans.nick(nick).or_else(|| st.bans.ip(peer)) {
+        let reason = ban.reason.clone();
+        return reject(ErrorCode::Banned { reason }, client_id, tx);
     }
 
-    let mut st = state.write().await;
-
-    // Controllo unicità case-insensitive senza hashmap aggiuntive
-    let maybe_existing = st
-        .users_by_nick
-        .iter()
-        .find(|(existing_nick, _)| existing_nick.eq_ignore_ascii_case(&nick))
-        .map(|(n, id)| (n.clone(), *id));
-
-    let (id, canonical_nick) = if let Some((existing_nick, existing_id)) = maybe_existing {
-        if existing_id != req_id {
-            let _ = tx.send(ServerToClient::Registered {
-                ok: false,
-                reason: Some(format!(
-                    "Esiste già un utente con il nome '{}' (già registrato come '{}')",
-                    nick, existing_nick
-                )),
-            });
-            return CommandResult::continue_with(client_id);
+    // Un account può avere una sola sessione attiva; una sessione in attesa di ripresa
+    // viene sostituita da un nuovo accesso con password (gruppi e inviti restano)
+    if let Some(old_id) = st.users_by_nick.get(nick).copied() {
+        if !st.detached.contains_key(&old_id) {
+            return reject(
+                ErrorCode::AlreadyConnected {
+                    nick: nick.to_string(),
+                },
+                client_id,
+                tx,
+            );
         }
-        // stesso client che riprova con case diverso: riusa l'ID e il nick canonico
-        (existing_id, existing_nick)
-    } else {
-        // nuovo nick
-        let id = st
-            .users_by_nick
-            .entry(nick.clone())
-            .or_insert(req_id)
-            .to_owned();
-
-        // Rimuovi tutti gli inviti pendenti associati a questo nickname
-        let to_remove: Vec<String> = st.invites.iter()
-            .filter_map(|(code, (_group, invite_nick))| {
-                if invite_nick.eq_ignore_ascii_case(&nick) { Some(code.clone()) } else { None }
-            })
-            .collect();
-        for code in to_remove {
-            st.invites.remove(&code);
-        }
-
-        (id, nick.clone())
-    };
+        st.end_session(&old_id);
+    }
 
-    st.nicks_by_id.insert(id, canonical_nick.clone());
-    st.clients.insert(id, tx.clone());
+    let id = Uuid::new_v4(); // id della sessione
+    let session = Uuid::new_v4().simple().to_string(); // token segreto per Resume
+    st.users_by_nick.insert(nick.to_string(), id);
+    st.nicks_by_id.insert(id, nick.to_string());
+    st.clients.insert(id, tx.untagged());
+    st.sessions.insert(id, session.clone());
+    st.peers.insert(id, peer);
 
-    println!("{} si è connesso al server", canonical_nick);
+    println!("{} si è connesso al server", nick);
 
     let _ = tx.send(ServerToClient::Registered {
         ok: true,
         reason: None,
+        session: Some(session),
+        error: None,
     });
 
+    // Consegna gli inviti ricevuti mentre l'utente non era connesso
+    let now = now_ms();
+    for (code, invite) in &st.invites {
+        let for_me = invite
+            .nick
+            .as_ref()
+            .is_some_and(|n| n.eq_ignore_ascii_case(nick));
+        if for_me && !invite.is_expired(now) {
+            let _ = tx.send(ServerToClient::InviteCodeForMe {
+                group: invite.group.clone(),
+                code: code.clone(),
+                expires_at: invite.expires_at,
+            });
+        }
+    }
+
     CommandResult::continue_with(Some(id))
 }
@@ -0,0 +1,72 @@
+/*
+Gestisce il cambio di nome di un gruppo, riservato a proprietario e amministratori.
+Il nuovo nome segue le regole di CreateGroup; membri, ruoli, inviti in attesa e cronologia passano al nuovo nome
+e tutti i membri connessi ricevono GroupRenamed.
+*/
+
+use super::Session;
+use crate::state::{State, Tx};
+use crate::validation::validate_group_name_syntax;
+use ruggine_common::{ErrorCode, GroupAction, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    group: String,
+    new_name: String,
+    session: &Session,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) {
+    let mut st = state.write().await;
+
+    if let Err(code) = st.require_manager(&group, &session.nick, GroupAction::Rename) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
+    }
+    if let Err(code) = validate_group_name_syntax(&new_name, &st.limits) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
+    }
+
+    // come in CreateGroup il confronto è case-insensitive; cambiare solo maiuscole e minuscole è ammesso
+    let taken = st
+        .groups
+        .keys()
+        .find(|existing| *existing != &group && existing.eq_ignore_ascii_case(&new_name))
+        .cloned();
+    if let Some(existing) = taken {
+        let _ = tx.send(ServerToClient::error(ErrorCode::GroupTaken {
+            name: new_name,
+            existing,
+        }));
+        return;
+    }
+    if st.accounts.contains_key(&new_name.to_ascii_lowercase()) {
+        let _ = tx.send(ServerToClient::error(ErrorCode::NameUsedByUser {
+            name: new_name,
+        }));
+        return;
+    }
+
+    if new_name != group {
+        st.rename_group(&group, &new_name);
+    }
+
+    let renamed = ServerToClient::GroupRenamed {
+        group,
+        new_name: new_name.clone(),
+        by: session.nick.clone(),
+    };
+    // chi ha fatto la richiesta riceve la conferma con il suo id, gli altri membri l'avviso
+    let _ = tx.send(renamed.clone());
+    if let Some(g) = st.groups.get(&new_name) {
+        for member in &g.members {
+            if let Some(txm) = st.tx_of_nick(member) {
+                if !txm.same_channel(tx) {
+                    let _ = txm.send(renamed.clone());
+                }
+            }
+        }
+    }
+}
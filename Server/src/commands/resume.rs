@@ -0,0 +1,63 @@
+/*
+Gestisce la ripresa di una sessione dopo una disconnessione: se il token è valido la nuova connessione
+prende il posto della vecchia, con lo stesso id di sessione, gli stessi gruppi e inviti.
+*/
+
+use super::register::reject;
+use super::{ClientId, CommandResult};
+use crate::state::{State, Tx};
+use crate::util::same_token;
+use ruggine_common::{ErrorCode, ServerToClient};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    nick: String,
+    session: String,
+    client_id: ClientId,
+    peer: IpAddr,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) -> CommandResult {
+    if client_id.is_some() {
+        let _ = tx.send(ServerToClient::error(ErrorCode::AlreadyAuthenticated));
+        return CommandResult::continue_with(client_id);
+    }
+
+    let mut st = state.write().await;
+    let id = match st
+        .account_nick(&nick)
+        .and_then(|n| st.users_by_nick.get(&n).copied())
+    {
+        Some(id)
+            if st
+                .sessions
+                .get(&id)
+                .is_some_and(|s| same_token(session.as_bytes(), s.as_bytes())) =>
+        {
+            id
+        }
+        // sessione scaduta (periodo di grazia terminato) o token errato
+        _ => return reject(ErrorCode::SessionExpired, client_id, tx),
+    };
+
+    // la vecchia connessione, se ancora aperta, viene chiusa: non può più agire come questa sessione
+    if let Some(old) = st.clients.get(&id) {
+        old.close(ErrorCode::SessionReplaced);
+    }
+    st.detached.remove(&id);
+    st.clients.insert(id, tx.untagged());
+    st.peers.insert(id, peer);
+    let nick = st.nick_of(&id).unwrap_or_default();
+    println!("{} si è riconnesso al server", nick);
+
+    let _ = tx.send(ServerToClient::Registered {
+        ok: true,
+        reason: None,
+        session: Some(session),
+        error: None,
+    });
+
+    CommandResult::continue_with(Some(id))
+}
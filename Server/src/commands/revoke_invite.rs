@@ -0,0 +1,41 @@
+/*
+Gestisce la revoca di un codice invito (destinato a un utente o link aperto) prima della scadenza.
+Possono revocarlo chi lo ha creato e il proprietario e gli amministratori del gruppo;
+chi prova a usarlo in seguito riceve l'errore InviteRevoked.
+*/
+
+use super::Session;
+use crate::catalog::t;
+use crate::invites::InviteEnd;
+use crate::state::{State, Tx};
+use ruggine_common::{ErrorCode, GroupAction, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(code: String, session: &Session, tx: &Tx, state: &Arc<RwLock<State>>) {
+    let mut st = state.write().await;
+    let Some(invite) = st.invites.get(&code) else {
+        let _ = tx.send(ServerToClient::error(st.invite_error(&code)));
+        return;
+    };
+    let group = invite.group.clone();
+    if invite.created_by != session.nick {
+        match st.require_manager(&group, &session.nick, GroupAction::ManageInvites) {
+            Ok(()) => {}
+            // a chi non è nel gruppo il codice non dice nulla, come un codice inesistente
+            Err(ErrorCode::NotMember { .. }) => {
+                let _ = tx.send(ServerToClient::error(ErrorCode::InvalidInvite));
+                return;
+            }
+            Err(code) => {
+                let _ = tx.send(ServerToClient::error(code));
+                return;
+            }
+        }
+    }
+
+    st.retire_invite(&code, InviteEnd::Revoked);
+    let _ = tx.send(ServerToClient::MessageServer {
+        text: t!("invite.revoked", code, group),
+    });
+}
@@ -2,75 +2,51 @@
 Gestisce l'invio di messaggi a un gruppo. Verifica i permessi e inoltra il messaggio ai membri del gruppo.
 */
 
-use super::{ClientId, CommandResult};
+use super::Session;
 use crate::state::{State, Tx};
-use ruggine_common::ServerToClient;
+use crate::validation::validate_text;
+use ruggine_common::{ErrorCode, ServerToClient};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 pub async fn handle(
     group: String,
     text: String,
-    nick: String,
-    client_id: ClientId,
+    session: &Session,
     tx: &Tx,
     state: &Arc<RwLock<State>>,
-) -> CommandResult {
+) {
+    // in lettura: la cronologia ha un proprio lock e la scrittura su disco avviene altrove
     let st = state.read().await;
-
-    let sender_id = st.users_by_nick.get(&nick).cloned();
-    if let Some(sender_id) = sender_id {
-        if !st
-            .groups
-            .get(&group)
-            .map_or(false, |g| g.members.contains(&sender_id))
-        {
-            let _ = tx.send(ServerToClient::Error {
-                reason: format!("Non sei membro di questo gruppo {group}"),
-            });
-            return CommandResult::continue_with(client_id);
-        }
-    } else {
-        let _ = tx.send(ServerToClient::Error {
-            reason: "ID del mittente invalido".into(),
-        });
-        return CommandResult::continue_with(client_id);
+    // il mittente è sempre l'utente della sessione: non si può scrivere a nome di altri
+    let my_nick = &session.nick;
+    if let Err(code) = validate_text(&text, &st.limits) {
+        let _ = tx.send(ServerToClient::error(code));
+        return;
     }
 
-    let id = match client_id {
-        Some(id) => id,
-        None => {
-            let _ = tx.send(ServerToClient::Error {
-                reason: "Non registrato".into(),
-            });
-            return CommandResult::continue_with(client_id);
-        }
-    };
-
-    let my_nick = st
-        .nicks_by_id
-        .get(&id)
-        .cloned()
-        .unwrap_or_else(|| "???".into());
-
-    if let Some(g) = st.groups.get(&group) {
-        for member in &g.members {
-            if member == &id {
-                continue;
-            } // non inviare a se stessi
-            if let Some(txm) = st.clients.get(member) {
-                let _ = txm.send(ServerToClient::Message {
-                    group: group.clone(),
-                    from: my_nick.clone(),
-                    text: text.clone(),
-                });
+    match st.groups.get(&group) {
+        Some(g) if g.members.contains(my_nick) => {
+            for member in &g.members {
+                if member == my_nick {
+                    continue;
+                } // non inviare a se stessi
+                if let Some(txm) = st.tx_of_nick(member) {
+                    let _ = txm.send(ServerToClient::Message {
+                        group: group.clone(),
+                        from: my_nick.clone(),
+                        text: text.clone(),
+                    });
+                }
             }
+            // Salva il messaggio nella cronologia del gruppo per chi entrerà o si riconnetterà
+            st.history.append(&group, my_nick, &text);
+        }
+        Some(_) => {
+            let _ = tx.send(ServerToClient::error(ErrorCode::NotMember { group }));
+        }
+        None => {
+            let _ = tx.send(ServerToClient::error(ErrorCode::GroupNotFound { group }));
         }
-    } else {
-        let _ = tx.send(ServerToClient::Error {
-            reason: format!("Gruppo {group} inesistente"),
-        });
     }
-
-    CommandResult::continue_with(client_id)
 }
@@ -0,0 +1,54 @@
+/*
+Gestisce la cessione della proprietà di un gruppo a un altro membro.
+Il vecchio proprietario resta nel gruppo come amministratore.
+*/
+
+use super::Session;
+use crate::catalog::t;
+use crate::state::{Role, State, Tx};
+use crate::storage::Mutation;
+use ruggine_common::{ErrorCode, GroupAction, ServerToClient};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub async fn handle(
+    group: String,
+    nick: String,
+    session: &Session,
+    tx: &Tx,
+    state: &Arc<RwLock<State>>,
+) {
+    let mut st = state.write().await;
+    let my_nick = session.nick.clone();
+
+    let (my_role, target, _) = match st.roles_in(&group, &my_nick, &nick) {
+        Ok(roles) => roles,
+        Err(code) => {
+            let _ = tx.send(ServerToClient::error(code));
+            return;
+        }
+    };
+    if my_role != Role::Owner {
+        let _ = tx.send(ServerToClient::error(ErrorCode::NotAllowed {
+            group: group.clone(),
+            action: GroupAction::TransferOwnership,
+        }));
+        return;
+    }
+    if target == my_nick {
+        let _ = tx.send(ServerToClient::error(ErrorCode::AlreadyOwner {
+            group: group.clone(),
+        }));
+        return;
+    }
+
+    st.commit(Mutation::OwnerChanged {
+        group: group.clone(),
+        nick: target.clone(),
+    });
+    st.commit(Mutation::AdminAdded {
+        group: group.clone(),
+        nick: my_nick,
+    });
+    st.notify_group(&group, &t!("group.new_owner", target, group));
+}
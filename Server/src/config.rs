@@ -0,0 +1,412 @@
+/*
+Modulo Config: file di configurazione TOML (--config) e ricarica delle impostazioni senza riavvio.
+Ogni chiave del file ha il nome di un'opzione della riga di comando, con `_` al posto di `-`
+(es. `idle_timeout = 90` vale come --idle-timeout 90, `operators = ["alice"]` come --operator alice),
+quindi ogni nuova opzione si può scrivere anche nel file. Le opzioni indicate sulla riga di comando
+prevalgono sul file.
+Con SIGHUP (o il comando `reload` della console) riga di comando e file vengono riletti: le impostazioni
+sicure sono applicate subito, le altre richiedono il riavvio; il log riporta cosa è cambiato.
+*/
+
+use anyhow::{anyhow, bail, Context};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, CommandFactory, FromArgMatches};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, RwLock};
+use tracing::{info, warn};
+
+use crate::args::Args;
+use crate::connection::ConnConfig;
+use crate::logger::CpuLog;
+use crate::rate_limit::RateLimitConfig;
+use crate::state::State;
+use crate::util::DEFAULT_CODE_LEN;
+use crate::validation::{DEFAULT_MAX_NAME_LEN, DEFAULT_RESERVED_NAMES};
+
+// Opzioni che la ricarica applica subito; per le altre serve il riavvio
+const RELOADABLE: &[&str] = &[
+    "operators",
+    "max_name_len",
+    "reserved_names",
+    "invite_code_len",
+    "invite_ttl",
+    "rate_limit",
+    "kind_limits",
+    "max_strikes",
+    "cpu_log",
+    "cpu_log_interval",
+];
+
+// Spazio riservato in un HistoryPage a id della richiesta, nomi dei campi e has_more
+const PAGE_OVERHEAD: usize = 256;
+
+// Opzioni il cui valore non va scritto nel log
+const SECRETS: &[&str] = &["admin_token"];
+
+// Valori delle opzioni (id -> valori testuali), usati per confrontare due configurazioni
+pub type Settings = BTreeMap<String, Vec<String>>;
+
+// Limiti sui nomi e sui codici invito (lunghezza e durata di default), conservati in `State::limits`:
+// i comandi li leggono dallo stato e la ricarica li sostituisce
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_name_len: usize,
+    pub reserved_names: Vec<String>,
+    pub invite_code_len: usize,
+    pub invite_ttl: u64,
+    pub max_frame: usize, // --max-frame, non ricaricabile: da qui derivano testo e pagine della cronologia
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_name_len: DEFAULT_MAX_NAME_LEN,
+            reserved_names: DEFAULT_RESERVED_NAMES
+                .iter()
+                .map(|n| n.to_string())
+                .collect(),
+            invite_code_len: DEFAULT_CODE_LEN,
+            invite_ttl: 0,
+            max_frame: ruggine_common::MAX_FRAME_LEN,
+        }
+    }
+}
+
+impl Limits {
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            max_name_len: args.max_name_len,
+            reserved_names: args.reserved_names.clone(),
+            invite_code_len: args.invite_code_len,
+            invite_ttl: args.invite_ttl,
+            max_frame: args.max_frame,
+        }
+    }
+
+    // Lunghezza massima del testo di un messaggio: ben sotto il frame, così il messaggio inoltrato
+    // e le voci della cronologia restano sempre inviabili
+    pub fn max_text_len(&self) -> usize {
+        self.max_frame / 4
+    }
+
+    // Byte disponibili per le voci di una pagina della cronologia, tolti gli altri campi del frame
+    pub fn history_page_bytes(&self) -> usize {
+        self.max_frame
+            .saturating_sub(PAGE_OVERHEAD + self.max_name_len)
+    }
+}
+
+// Legge la riga di comando e, se indicato con --config, il file di configurazione
+pub fn load(argv: &[OsString]) -> anyhow::Result<(Args, Settings)> {
+    let cli = Args::command().try_get_matches_from(argv)?;
+    let matches = match cli.get_one::<String>("config") {
+        Some(path) => {
+            let path = Path::new(path);
+            let merged = with_file(argv, &cli, path)?;
+            Args::command()
+                .try_get_matches_from(merged)
+                .with_context(|| format!("configurazione non valida in {}", path.display()))?
+        }
+        None => cli,
+    };
+    let mut args = Args::from_arg_matches(&matches)?;
+    // un elenco vuoto arriva come un solo valore vuoto (`--opzione=`) e sostituisce i default
+    for list in [
+        &mut args.operators,
+        &mut args.kind_limits,
+        &mut args.reserved_names,
+    ] {
+        list.retain(|v| !v.is_empty());
+    }
+    Ok((args, settings(&matches)))
+}
+
+// Aggiunge agli argomenti le chiavi del file che non sono già sulla riga di comando
+fn with_file(argv: &[OsString], cli: &ArgMatches, path: &Path) -> anyhow::Result<Vec<OsString>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("impossibile leggere {}", path.display()))?;
+    let table: toml::Table =
+        toml::from_str(&text).with_context(|| format!("file {} non valido", path.display()))?;
+    let cmd = Args::command();
+    let mut argv = argv.to_vec();
+    for (key, value) in table {
+        let arg = cmd
+            .get_arguments()
+            .filter(|a| a.get_id() != "config")
+            .find(|a| a.get_id() == key.as_str())
+            .ok_or_else(|| anyhow!("chiave '{}' sconosciuta in {}", key, path.display()))?;
+        let long = arg
+            .get_long()
+            .ok_or_else(|| anyhow!("chiave '{}' sconosciuta in {}", key, path.display()))?;
+        if cli.value_source(&key) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        // la forma --opzione=valore accetta anche valori che iniziano con '-'
+        match value {
+            toml::Value::Boolean(true) => argv.push(format!("--{long}").into()),
+            toml::Value::Boolean(false) => {}
+            // senza valori l'opzione non comparirebbe e resterebbero i default: si passa un valore vuoto
+            toml::Value::Array(items) if items.is_empty() => {
+                if !is_list(arg) {
+                    bail!("valore non valido per '{}': elenco vuoto", key);
+                }
+                argv.push(format!("--{long}=").into());
+            }
+            toml::Value::Array(items) => {
+                for item in items {
+                    argv.push(format!("--{long}={}", scalar(&key, item)?).into());
+                }
+            }
+            other => argv.push(format!("--{long}={}", scalar(&key, other)?).into()),
+        }
+    }
+    Ok(argv)
+}
+
+fn scalar(key: &str, value: toml::Value) -> anyhow::Result<String> {
+    match value {
+        toml::Value::String(s) => Ok(s),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Float(x) => Ok(x.to_string()),
+        other => bail!("valore non valido per '{}': {}", key, other),
+    }
+}
+
+// Opzione ripetibile, scritta nel file come elenco
+fn is_list(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Append)
+}
+
+// Valori effettivi di tutte le opzioni, compresi quelli di default
+// (il valore vuoto di un elenco vuoto non compare)
+fn settings(matches: &ArgMatches) -> Settings {
+    Args::command()
+        .get_arguments()
+        .filter(|a| !matches!(a.get_id().as_str(), "config" | "help" | "version"))
+        .filter_map(|a| {
+            let id = a.get_id().as_str();
+            let values = matches.get_raw(id)?;
+            Some((
+                id.to_string(),
+                values
+                    .map(|v| v.to_string_lossy().into_owned())
+                    .filter(|v| !is_list(a) || !v.is_empty())
+                    .collect(),
+            ))
+        })
+        .collect()
+}
+
+// Ricarica la configurazione su richiesta (SIGHUP o console)
+pub struct Reloader {
+    argv: Vec<OsString>,      // riga di comando originale, riletta insieme al file
+    current: Mutex<Settings>, // impostazioni in uso; il lock evita due ricariche insieme
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+    cpu_log: watch::Sender<CpuLog>,
+}
+
+impl Reloader {
+    pub fn new(
+        argv: Vec<OsString>,
+        current: Settings,
+        state: Arc<RwLock<State>>,
+        conf: Arc<ConnConfig>,
+        cpu_log: watch::Sender<CpuLog>,
+    ) -> Self {
+        Self {
+            argv,
+            current: Mutex::new(current),
+            state,
+            conf,
+            cpu_log,
+        }
+    }
+
+    // Un errore nel file lascia in uso la configurazione precedente
+    pub async fn reload(&self) {
+        let (args, new) = match load(&self.argv) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                warn!("Configurazione non ricaricata: {:#}", e);
+                return;
+            }
+        };
+        let rate_limit =
+            match RateLimitConfig::parse(&args.rate_limit, &args.kind_limits, args.max_strikes) {
+                Ok(r) => r,
+                Err(e) => {
+                    warn!("Configurazione non ricaricata: {}", e);
+                    return;
+                }
+            };
+
+        let mut current = self.current.lock().await;
+        let changed: Vec<String> = current
+            .keys()
+            .chain(new.keys())
+            .filter(|k| current.get(*k) != new.get(*k))
+            .cloned()
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        if changed.is_empty() {
+            info!("Configurazione ricaricata: nessuna modifica");
+            return;
+        }
+        for key in &changed {
+            let (old, now) = (show(key, current.get(key)), show(key, new.get(key)));
+            if RELOADABLE.contains(&key.as_str()) {
+                info!("Configurazione: {} {} -> {}", key, old, now);
+                match new.get(key) {
+                    Some(v) => current.insert(key.clone(), v.clone()),
+                    None => current.remove(key),
+                };
+            } else {
+                // resta il valore in uso: la prossima ricarica lo segnala di nuovo
+                warn!(
+                    "Configurazione: {} {} -> {} (richiede il riavvio)",
+                    key, old, now
+                );
+            }
+        }
+
+        *self.conf.rate_limit.write().expect("limiti avvelenati") = rate_limit;
+        let mut st = self.state.write().await;
+        // --max-frame richiede il riavvio: resta quello delle connessioni già configurate
+        st.limits = Limits {
+            max_frame: st.limits.max_frame,
+            ..Limits::from_args(&args)
+        };
+        st.operators = args
+            .operators
+            .iter()
+            .map(|n| n.to_ascii_lowercase())
+            .collect();
+        drop(st);
+        self.cpu_log.send_if_modified(|log| {
+            let new = CpuLog {
+                path: args.cpu_log.clone(),
+                interval: Duration::from_secs(args.cpu_log_interval),
+            };
+            let changed = *log != new;
+            *log = new;
+            changed
+        });
+    }
+}
+
+// Valore da scrivere nel log; i segreti vengono nascosti
+fn show(key: &str, values: Option<&Vec<String>>) -> String {
+    match values {
+        None => "(non impostato)".to_string(),
+        Some(_) if SECRETS.contains(&key) => "***".to_string(),
+        Some(v) if v.len() == 1 => v[0].clone(),
+        Some(v) => format!("[{}]", v.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // File di configurazione temporaneo e riga di comando che lo indica con --config
+    fn config_file(text: &str) -> (PathBuf, Vec<OsString>) {
+        let path = std::env::temp_dir().join(format!("ruggine-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, text).unwrap();
+        let argv = vec![
+            "ruggine-server".into(),
+            "--config".into(),
+            path.clone().into(),
+        ];
+        (path, argv)
+    }
+
+    #[test]
+    fn command_line_wins_over_the_file() {
+        let (path, mut argv) = config_file(
+            "idle_timeout = 90\nmax_name_len = 12\noperators = [\"alice\", \"Bob\"]\nno_console = true\n",
+        );
+        argv.extend(["--idle-timeout", "30"].map(OsString::from));
+        let (args, settings) = load(&argv).unwrap();
+        assert_eq!(args.idle_timeout, 30);
+        assert_eq!(args.max_name_len, 12);
+        assert_eq!(args.operators, ["alice", "Bob"]);
+        assert!(args.no_console);
+        assert_eq!(settings["idle_timeout"], ["30"]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn empty_array_replaces_the_defaults() {
+        let (path, argv) = config_file("reserved_names = []\n");
+        let (args, settings) = load(&argv).unwrap();
+        assert!(!DEFAULT_RESERVED_NAMES.is_empty());
+        assert!(args.reserved_names.is_empty());
+        assert!(settings["reserved_names"].is_empty());
+
+        // un elenco vuoto per un'opzione con un solo valore è un errore
+        std::fs::write(&path, "idle_timeout = []\n").unwrap();
+        assert!(load(&argv).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        for text in ["idle_timout = 90\n", "config = \"altro.toml\"\n"] {
+            let (path, argv) = config_file(text);
+            let err = load(&argv).unwrap_err().to_string();
+            assert!(err.contains("sconosciuta"), "{err}");
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_applies_only_the_reloadable_options() {
+        let (path, argv) =
+            config_file("operators = [\"alice\"]\nmax_name_len = 12\nidle_timeout = 90\n");
+        let (args, settings) = load(&argv).unwrap();
+        let state = Arc::new(RwLock::new(State {
+            limits: Limits::from_args(&args),
+            ..State::default()
+        }));
+        let (cpu_log, _) = watch::channel(CpuLog {
+            path: args.cpu_log.clone(),
+            interval: Duration::from_secs(args.cpu_log_interval),
+        });
+        let reloader = Reloader::new(
+            argv,
+            settings,
+            state.clone(),
+            Arc::new(ConnConfig::for_tests()),
+            cpu_log,
+        );
+
+        std::fs::write(
+            &path,
+            "operators = [\"Bob\"]\nmax_name_len = 20\nidle_timeout = 5\n",
+        )
+        .unwrap();
+        reloader.reload().await;
+        {
+            let st = state.read().await;
+            assert_eq!(st.operators, ["bob".to_string()].into());
+            assert_eq!(st.limits.max_name_len, 20);
+        }
+        // --idle-timeout richiede il riavvio: resta il valore in uso
+        let current = reloader.current.lock().await.clone();
+        assert_eq!(current["operators"], ["Bob"]);
+        assert_eq!(current["idle_timeout"], ["90"]);
+
+        // un file non valido lascia la configurazione precedente
+        std::fs::write(&path, "operators = [\"carol\"]\nmax_name_len = \"tanti\"\n").unwrap();
+        reloader.reload().await;
+        assert!(state.read().await.is_operator("bob"));
+        let _ = std::fs::remove_file(path);
+    }
+}
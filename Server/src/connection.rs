@@ -1,131 +1,351 @@
 /*
-Modulo Connection: gestisce la connessione TCP con ciascun client.
+Modulo Connection: gestisce la connessione (TCP, TLS o WebSocket) con ciascun client.
 Si occupa di ricevere, interpretare e inoltrare i messaggi tra client e server, e di gestire la disconnessione.
+Il ciclo `serve` non dipende dal trasporto: legge e scrive frame tramite i trait del modulo transport.
 */
-/* NDJSON -> Newline delimited JSON */
+/* NDJSON -> Newline delimited JSON, oppure frame MessagePack se concordati con Hello (vedi ruggine_common::codec) */
 
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    net::TcpStream,
+    io::{AsyncRead, AsyncWrite},
     sync::RwLock,
 };
-use tracing::error;
+use tracing::{debug, error, warn};
 use uuid::Uuid;
 
-use crate::commands::dispatch;
-use ruggine_common::{ClientToServer, ServerToClient};
+use crate::commands::{dispatch, hello};
+use crate::metrics::Metrics;
+use crate::outbox::{self, Next, QueuePolicy, QueueStats};
+use crate::rate_limit::{kind_of, RateLimitConfig, RateLimiter, Verdict, MALFORMED_KIND};
+use crate::shutdown::Shutdown;
+use crate::transport::{FrameSink, FrameSource};
+use ruggine_common::{
+    ClientToServer, Codec, Envelope, ErrorCode, FrameError, FrameReader, FrameWriter,
+    ServerToClient, PROTOCOL_VERSION,
+};
 
 use crate::state::{Rx, State, Tx};
 // Validazioni e utility ora sono usate nei singoli moduli comando
 
-pub async fn handle_conn(stream: TcpStream, state: Arc<RwLock<State>>) -> anyhow::Result<()> {
-    let (reader, mut writer) = stream.into_split();
-    let mut reader = BufReader::new(reader).lines();
+// Tempo concesso per scrivere il motivo della disconnessione a un client lento
+const EVICT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Parametri comuni a tutte le connessioni, decisi all'avvio del server
+pub struct ConnConfig {
+    pub rate_limit: std::sync::RwLock<RateLimitConfig>, // aggiornabile con la ricarica, vale per le nuove connessioni
+    pub queue_size: usize,
+    pub queue_policy: QueuePolicy,
+    pub queue_stats: Arc<QueueStats>,
+    pub idle_timeout: Duration, // chiusura delle connessioni che non inviano nulla per questo tempo
+    pub grace: Duration,        // per quanto una sessione caduta può essere ripresa con Resume
+    pub max_frame: usize,       // dimensione massima di un messaggio ricevuto o inviato
+    pub stats: ConnStats,
+    pub metrics: Metrics,
+    pub shutdown: Shutdown,
+}
+
+#[cfg(test)]
+impl ConnConfig {
+    // Parametri predefiniti della riga di comando, per i test che aprono connessioni vere
+    pub fn for_tests() -> Self {
+        use crate::rate_limit::{DEFAULT_KIND_LIMITS, DEFAULT_LIMIT, DEFAULT_MAX_STRIKES};
+        let kind_limits: Vec<String> = DEFAULT_KIND_LIMITS.iter().map(|s| s.to_string()).collect();
+        Self {
+            rate_limit: std::sync::RwLock::new(
+                RateLimitConfig::parse(DEFAULT_LIMIT, &kind_limits, DEFAULT_MAX_STRIKES).unwrap(),
+            ),
+            queue_size: outbox::DEFAULT_QUEUE_SIZE,
+            queue_policy: QueuePolicy::DropOldest,
+            queue_stats: Arc::new(QueueStats::default()),
+            idle_timeout: Duration::from_secs(60),
+            grace: Duration::ZERO,
+            max_frame: ruggine_common::MAX_FRAME_LEN,
+            stats: ConnStats::default(),
+            metrics: Metrics::default(),
+            shutdown: Shutdown::new(Duration::from_secs(1), None),
+        }
+    }
+}
+
+// Contatori delle connessioni di tutti i trasporti, anche non autenticate
+#[derive(Debug, Default)]
+pub struct ConnStats {
+    pub open: AtomicUsize,   // connessioni aperte in questo momento
+    pub accepted: AtomicU64, // connessioni accettate dall'avvio
+}
+
+// Tiene il conto delle connessioni aperte anche quando `serve` termina con un errore
+struct OpenConn<'a>(&'a ConnStats);
+
+impl<'a> OpenConn<'a> {
+    fn new(stats: &'a ConnStats) -> Self {
+        stats.open.fetch_add(1, Ordering::Relaxed);
+        stats.accepted.fetch_add(1, Ordering::Relaxed);
+        Self(stats)
+    }
+}
+
+impl Drop for OpenConn<'_> {
+    fn drop(&mut self) {
+        self.0.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Lo stream è generico: TCP in chiaro oppure TLS
+pub async fn handle_conn<S>(
+    stream: S,
+    peer: IpAddr,
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (reader, writer) = tokio::io::split(stream);
+    let reader = FrameReader::new(reader, conf.max_frame);
+    let writer = FrameWriter::new(writer, conf.max_frame);
+    serve(reader, writer, peer, state, conf).await
+}
+
+// Gestione di una connessione già aperta, qualunque sia il trasporto.
+// `peer` è l'indirizzo del client, registrato nella sessione per i ban per indirizzo
+pub async fn serve<R, W>(
+    mut reader: R,
+    mut writer: W,
+    peer: IpAddr,
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+) -> anyhow::Result<()>
+where
+    R: FrameSource,
+    W: FrameSink,
+{
+    let _open = OpenConn::new(&conf.stats);
 
-    // canale out verso questo client
-    let (tx, mut rx): (Tx, Rx) = tokio::sync::mpsc::unbounded_channel();
+    // coda limitata in uscita verso questo client
+    let (tx, mut rx): (Tx, Rx) =
+        outbox::channel(conf.queue_size, conf.queue_policy, conf.queue_stats.clone());
 
     // task di scrittura: prende ServerToClient dal canale e li scrive in NDJSON
     /*
     NDJSON -> è un formato in cui ogni riga di un file o di uno stream contiene un oggetto JSON separato,
-    terminato da un carattere di nuova linea (\n). */
-    tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            let line = match serde_json::to_string(&msg) {
-                Ok(s) => s,
-                Err(e) => {
-                    error!("Errore di serializzazione: {e}");
-                    continue;
+    terminato da un carattere di nuova linea (\n).
+    Dopo un Welcome che concorda "msgpack" il task passa ai frame binari. */
+    let wconf = conf.clone();
+    let mut writer_task = tokio::spawn(async move {
+        let metrics = &wconf.metrics;
+        loop {
+            let msg = match rx.recv().await {
+                Next::Send(msg) => msg,
+                Next::Evicted(code) => {
+                    // si prova a spiegare il motivo al client, ma senza aspettarlo all'infinito
+                    if matches!(code, ErrorCode::QueueFull { .. }) {
+                        warn!("Client lento disconnesso: coda in uscita piena");
+                    }
+                    let msg = Envelope::new(None, ServerToClient::error(code));
+                    let _ =
+                        timeout(EVICT_WRITE_TIMEOUT, write_msg(&mut writer, &msg, metrics)).await;
+                    let _ = writer.shutdown().await;
+                    break;
                 }
+                Next::Closed => break,
             };
-            if writer.write_all(line.as_bytes()).await.is_err() {
-                break; // se il client si disconnette, effettuo break, altrimenti invio il messaggio vero e proprio
-            }
-            if writer.write_all(b"\n").await.is_err() {
-                break; // se il client si disconnette, effettuo break, altrimenti aggiungo il carattere di nuova linea
+            // se il client smette di leggere la scrittura resta bloccata: la coda piena la interrompe
+            tokio::select! {
+                res = write_msg(&mut writer, &msg, metrics) => {
+                    if res.is_err() {
+                        break; // il client si è disconnesso
+                    }
+                    // il Welcome parte ancora in JSON, i messaggi successivi con il codec concordato
+                    if let ServerToClient::Welcome { features, .. } = &msg.body {
+                        writer.set_codec(Codec::negotiated(features));
+                    }
+                }
+                _ = rx.evicted() => {
+                    warn!("Client lento disconnesso: coda in uscita piena");
+                    break;
+                }
             }
         }
+        let dropped = rx.dropped();
+        if dropped > 0 {
+            warn!("{} messaggi non consegnati a un client lento", dropped);
+        }
     });
 
     // id di questa connessione dopo Register
     let mut client_id: Option<Uuid> = None;
+    let rate_limit = conf.rate_limit.read().expect("limiti avvelenati").clone();
+    let mut limiter = RateLimiter::new(rate_limit, Instant::now());
+    // diventa true dopo un Hello con versione compatibile
+    let mut negotiated = false;
 
     // loop di lettura NDJSON — gestiamo anche EOF/errori come disconnessioni normali
     loop {
-        match reader.next_line().await {
-            Ok(Some(line)) => {
-                let line = line.trim();
-                if line.is_empty() {
-                    continue;
+        // se il task di scrittura termina (client lento o non raggiungibile) si chiude anche la lettura
+        let next = tokio::select! {
+            frame = timeout(conf.idle_timeout, reader.next_frame()) => frame,
+            _ = &mut writer_task => {
+                if let Some(id) = client_id.take() {
+                    detach_session(id, &tx, &state, conf.grace).await;
                 }
-
+                break;
+            }
+            notice = conf.shutdown.requested() => {
+                // l'avviso parte dopo i messaggi già in coda; la sessione non va chiusa:
+                // gruppi e inviti sono già salvati e il processo sta per terminare
+                let _ = tx.send(ServerToClient::ServerShutdown {
+                    reason: notice.reason,
+                    reconnect_after: notice.reconnect_after,
+                });
+                tx.finish();
+                let _ = timeout(conf.shutdown.timeout, &mut writer_task).await;
+                break;
+            }
+        };
+        // nessun messaggio (neanche i Ping del client) entro il timeout: connessione considerata morta
+        let next = match next {
+            Ok(next) => next,
+            Err(_) => {
+                warn!("Connessione inattiva chiusa (sessione {:?})", client_id);
+                let _ = tx.send(ServerToClient::error(ErrorCode::IdleTimeout));
+                // come per una chiusura della connessione: la sessione si può riprendere con Resume
+                if let Some(id) = client_id.take() {
+                    detach_session(id, &tx, &state, conf.grace).await;
+                }
+                break;
+            }
+        };
+        match next {
+            Ok(Some(frame)) => {
+                conf.metrics
+                    .bytes_in
+                    .fetch_add(frame.len() as u64, Ordering::Relaxed);
                 // parse sicuro del JSON -> enum
                 //prova di conversione in un oggetto di tipo ClientToServer
                 //campo 'kind' per il controllo che sia corretto con il rispettivo match nelle varie funzioni
-                let msg: ClientToServer = match serde_json::from_str(line) {
-                    Ok(m) => m,
+                // l'eventuale id della richiesta viene ripetuto nelle risposte dirette
+                let codec = reader.codec();
+                let decoded = codec.decode::<Envelope<ClientToServer>>(&frame);
+
+                // Controllo anti-flood su ogni frame ricevuto, anche se non interpretabile
+                // (Logout è sempre accettato, vedi RateLimiter::check_at)
+                let kind = match &decoded {
+                    Ok(req) => kind_of(&req.body),
+                    Err(_) => MALFORMED_KIND,
+                };
+                let verdict = limiter.check(kind);
+                if verdict == Verdict::Disconnect {
+                    warn!("Connessione chiusa per flood (sessione {:?})", client_id);
+                    let _ = tx.send(ServerToClient::error(ErrorCode::Flood));
+                    // nessun periodo di grazia per chi viene disconnesso per flood
+                    if let Some(id) = client_id.take() {
+                        detach_session(id, &tx, &state, Duration::ZERO).await;
+                    }
+                    break;
+                }
+
+                let (request_id, msg) = match decoded {
+                    Ok(req) => (req.id, req.body),
                     Err(e) => {
-                        error!("Errore parsing messaggio: {}", e);
-                        let _ = tx.send(ServerToClient::Error {
-                            reason: "JSON errato".into(),
-                        });
+                        debug!("Errore parsing messaggio: {}", e);
+                        let _ = tx.send(ServerToClient::error(parse_error_code(codec, &frame)));
                         continue;
                     }
                 };
 
-                // Questa struttura permette al server di sapere se deve aggiornare l’ID del client e 
-                //se deve terminare la connessione dopo aver gestito un comando.
-                let res = dispatch(msg, client_id, &tx, &state).await;
+                let reply_tx = tx.for_request(request_id);
+
+                if verdict == Verdict::Limited {
+                    let _ = reply_tx.send(ServerToClient::error(ErrorCode::RateLimited {
+                        kind: kind.to_string(),
+                    }));
+                    continue;
+                }
+
+                // Prima di qualsiasi altro comando il client deve negoziare la versione del protocollo
+                if !negotiated {
+                    match msg {
+                        ClientToServer::Hello {
+                            protocol_version,
+                            features,
+                        } => {
+                            match hello::negotiate(protocol_version, features, &reply_tx) {
+                                Some(codec) => {
+                                    negotiated = true;
+                                    // il client manda il resto solo dopo aver ricevuto il Welcome
+                                    reader.set_codec(codec);
+                                }
+                                None => break,
+                            }
+                        }
+                        _ => {
+                            let _ =
+                                reply_tx.send(ServerToClient::error(ErrorCode::HelloRequired {
+                                    version: PROTOCOL_VERSION,
+                                }));
+                        }
+                    }
+                    continue;
+                }
+
+                // Questa struttura permette al server di sapere se deve aggiornare l’ID del client e
+                // se deve terminare la connessione dopo aver gestito un comando.
+                let started = Instant::now();
+                let res = dispatch(msg, client_id, peer, &reply_tx, &state).await;
+                conf.metrics.observe_dispatch(kind, started.elapsed());
                 client_id = res.new_client_id;
                 if res.close {
                     break;
                 }
             }
             Ok(None) => {
-                /*Serve a pulire lo stato del server quando un client si disconnette in modo ordinato,
-                 evitando utenti “fantasma” o risorse non liberate. */
+                // Serve a pulire lo stato del server quando un client si disconnette in modo ordinato,
+                // evitando utenti “fantasma” o risorse non liberate.
                 if let Some(id) = client_id.take() {
-                    let mut st = state.write().await;
-                    let nick_opt = st.nicks_by_id.get(&id).cloned();
-                    if let Some(nick) = nick_opt {
-                        println!("{} si è disconnesso dal server", nick);
-                    }
-                    //rimozione dagli utenti attivi
-                    if let Some(nick) = st.nicks_by_id.get(&id).cloned() {
-                        st.users_by_nick.remove(&nick);
-                    }
-                    st.nicks_by_id.remove(&id);
-                    st.clients.remove(&id);
+                    detach_session(id, &tx, &state, conf.grace).await;
+                }
+                break;
+            }
+            Err(FrameError::TooLarge { max }) => {
+                // non si può sapere dove inizia il messaggio successivo: la connessione va chiusa
+                warn!(
+                    "Connessione chiusa: messaggio oltre {} byte (sessione {:?})",
+                    max, client_id
+                );
+                let _ = tx.send(ServerToClient::error(ErrorCode::FrameTooLarge { max }));
+                if let Some(id) = client_id.take() {
+                    detach_session(id, &tx, &state, Duration::ZERO).await;
                 }
                 break;
             }
             Err(e) => {
-                // Se è un reset/abort/broken pipe, trattalo come disconnessione normale
-                    //errori che indicano che il client non è più connesso/raggiungibile
-                //se errore di altro tipo, viene propagato (es CTRL+C)
+                // qualunque errore di lettura chiude la connessione: la sessione va staccata
+                // come per un EOF, altrimenti il nickname resterebbe occupato fino al riavvio
+                if let Some(id) = client_id.take() {
+                    detach_session(id, &tx, &state, conf.grace).await;
+                }
+                // reset/abort/broken pipe, TLS chiuso senza close_notify o frame troncato:
+                // il client non è più raggiungibile, è una disconnessione normale.
+                // Gli errori di altro tipo vengono propagati
                 use std::io::ErrorKind;
-                if matches!(
-                    e.kind(),
-                    ErrorKind::ConnectionReset
-                        | ErrorKind::ConnectionAborted
-                        | ErrorKind::BrokenPipe
-                ) {
-                    if let Some(id) = client_id.take() {
-                        let mut st = state.write().await;
-                        let nick_opt = st.nicks_by_id.get(&id).cloned();
-                        if let Some(nick) = nick_opt {
-                            println!("{} si è disconnesso dal server", nick);
-                        }
-                        if let Some(nick) = st.nicks_by_id.get(&id).cloned() {
-                            st.users_by_nick.remove(&nick);
-                        }
-                        st.nicks_by_id.remove(&id);
-                        st.clients.remove(&id);
+                match e {
+                    FrameError::Io(e)
+                        if matches!(
+                            e.kind(),
+                            ErrorKind::ConnectionReset
+                                | ErrorKind::ConnectionAborted
+                                | ErrorKind::BrokenPipe
+                                | ErrorKind::UnexpectedEof
+                        ) =>
+                    {
+                        break
                     }
-                    break;
-                } else {
-                    return Err(e.into());
+                    e => return Err(e.into()),
                 }
             }
         }
@@ -133,3 +353,264 @@ pub async fn handle_conn(stream: TcpStream, state: Arc<RwLock<State>>) -> anyhow
 
     Ok(())
 }
+
+// Gestisce la caduta della connessione di una sessione autenticata (senza Logout).
+// La sessione resta valida per il periodo di grazia, così il client può riprenderla con Resume
+// ritrovando gruppi e inviti; poi viene chiusa
+async fn detach_session(id: Uuid, tx: &Tx, state: &Arc<RwLock<State>>, grace: Duration) {
+    let mut st = state.write().await;
+    // la sessione è già stata ripresa da un'altra connessione: non va toccata
+    if !st.clients.get(&id).is_some_and(|t| t.same_channel(tx)) {
+        return;
+    }
+    let nick = st.nick_of(&id).unwrap_or_default();
+    if grace.is_zero() {
+        println!("{} si è disconnesso dal server", nick);
+        st.end_session(&id);
+        return;
+    }
+
+    let since = Instant::now();
+    st.clients.remove(&id);
+    st.detached.insert(id, since);
+    println!(
+        "{} ha perso la connessione (sessione conservata per {} s)",
+        nick,
+        grace.as_secs()
+    );
+    drop(st);
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        let mut st = state.write().await;
+        // chiusa solo se nel frattempo non è stata ripresa (né ripresa e persa di nuovo)
+        if st.detached.get(&id) == Some(&since) {
+            println!("{} si è disconnesso dal server", nick);
+            st.end_session(&id);
+        }
+    });
+}
+
+// Errore per un messaggio non interpretabile: distingue i tipi di messaggio
+// sconosciuti (client di una versione diversa) dal JSON (o MessagePack) malformato
+fn parse_error_code(codec: Codec, frame: &[u8]) -> ErrorCode {
+    let value: serde_json::Value = match codec.decode(frame) {
+        Ok(v) => v,
+        Err(_) => return ErrorCode::BadJson,
+    };
+    match value.get("kind").and_then(|k| k.as_str()) {
+        Some(kind) => ErrorCode::UnknownMessage {
+            kind: kind.to_string(),
+            version: PROTOCOL_VERSION,
+        },
+        None => ErrorCode::MissingKind,
+    }
+}
+
+// Scrive un messaggio con il codec corrente (riga NDJSON o frame MessagePack).
+// Un messaggio troppo grande o non serializzabile viene sostituito da un Error con lo stesso id,
+// senza chiudere la connessione: chi ha fatto la richiesta non resta in attesa di una risposta persa
+async fn write_msg<W>(
+    writer: &mut W,
+    msg: &Envelope<ServerToClient>,
+    metrics: &Metrics,
+) -> std::io::Result<()>
+where
+    W: FrameSink,
+{
+    match writer.send(msg).await {
+        Ok(len) => {
+            metrics.bytes_out.fetch_add(len as u64, Ordering::Relaxed);
+            Ok(())
+        }
+        Err(FrameError::Io(e)) => Err(e),
+        Err(e) => {
+            error!("Messaggio non inviato: {e}");
+            let code = match e {
+                FrameError::TooLarge { max } => ErrorCode::ReplyTooLarge { max },
+                _ => ErrorCode::Internal,
+            };
+            let reply = Envelope::new(msg.id, ServerToClient::error(code));
+            match writer.send(&reply).await {
+                Ok(len) => {
+                    metrics.bytes_out.fetch_add(len as u64, Ordering::Relaxed);
+                    Ok(())
+                }
+                Err(FrameError::Io(e)) => Err(e),
+                Err(_) => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryStore;
+    use futures::StreamExt;
+    use ruggine_client::{Config, Connection, Event, Events, RuggineClient};
+    use ruggine_common::{Codec, FEATURE_MSGPACK, FEATURE_RESUME};
+    use std::net::SocketAddr;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::task::JoinHandle;
+
+    const WAIT: Duration = Duration::from_secs(10);
+
+    // Server TCP nello stesso processo, con un periodo di grazia per la ripresa delle sessioni
+    async fn start_server() -> SocketAddr {
+        let dir = std::env::temp_dir().join(format!("ruggine-client-{}", Uuid::new_v4()));
+        let state = Arc::new(RwLock::new(State {
+            history: HistoryStore::new(dir, 10),
+            ..State::default()
+        }));
+        let conf = Arc::new(ConnConfig {
+            grace: Duration::from_secs(30),
+            ..ConnConfig::for_tests()
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, peer)) = listener.accept().await {
+                tokio::spawn(handle_conn(socket, peer.ip(), state.clone(), conf.clone()));
+            }
+        });
+        addr
+    }
+
+    // Inoltra le connessioni verso il server; `cut` le interrompe come una rete che cade,
+    // mentre le nuove connessioni continuano a essere accettate
+    struct Proxy {
+        addr: SocketAddr,
+        links: Arc<std::sync::Mutex<Vec<JoinHandle<()>>>>,
+    }
+
+    impl Proxy {
+        async fn start(server: SocketAddr) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let links = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let accepted = links.clone();
+            tokio::spawn(async move {
+                while let Ok((mut client, _)) = listener.accept().await {
+                    let link = tokio::spawn(async move {
+                        let mut upstream = TcpStream::connect(server).await.unwrap();
+                        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+                    });
+                    accepted.lock().unwrap().push(link);
+                }
+            });
+            Self { addr, links }
+        }
+
+        fn cut(&self) {
+            for link in self.links.lock().unwrap().drain(..) {
+                link.abort();
+            }
+        }
+    }
+
+    async fn connect(addr: SocketAddr, codec: Codec, nick: &str) -> (RuggineClient, Events) {
+        let config = Config {
+            codec,
+            ..Config::new(addr.to_string())
+        };
+        let mut conn = Connection::open(&config).await.unwrap();
+        assert!(conn.features().iter().any(|f| f == FEATURE_RESUME));
+        assert_eq!(
+            conn.features().iter().any(|f| f == FEATURE_MSGPACK),
+            codec == Codec::MsgPack
+        );
+        conn.create_account(nick, "password1").await.unwrap();
+        conn.start()
+    }
+
+    // Primo evento che soddisfa `want`, scartando gli altri
+    async fn expect<T>(events: &mut Events, want: impl Fn(Event) -> Option<T>) -> T {
+        loop {
+            let event = timeout(WAIT, events.next()).await.unwrap().unwrap();
+            if let Some(found) = want(event) {
+                return found;
+            }
+        }
+    }
+
+    fn message(event: Event) -> Option<ServerToClient> {
+        match event {
+            Event::Message { msg, .. } => Some(msg),
+            _ => None,
+        }
+    }
+
+    fn created(event: Event) -> Option<Option<u64>> {
+        match event {
+            Event::Message {
+                msg: ServerToClient::GroupCreated { .. },
+                request,
+            } => Some(request),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn client_library_chats_through_the_server() {
+        let addr = start_server().await;
+        let (alice, mut alice_events) = connect(addr, Codec::MsgPack, "alice").await;
+        let (bob, mut bob_events) = connect(addr, Codec::Json, "bob").await;
+
+        // la risposta diretta porta l'id della richiesta
+        let id = alice.create_group("rust").await.unwrap();
+        let request = expect(&mut alice_events, created).await;
+        assert_eq!(request, Some(id));
+
+        alice.invite("rust", "bob", None).await.unwrap();
+        let code = expect(&mut bob_events, |e| match message(e)? {
+            ServerToClient::InviteCode { code, .. } => Some(code),
+            _ => None,
+        })
+        .await;
+        bob.join("rust", &code).await.unwrap();
+        let users = alice.list_users().await.unwrap();
+        assert_eq!(users.len(), 2);
+        assert!(users.iter().any(|u| u == "bob"));
+        assert_eq!(bob.list_groups().await.unwrap(), ["rust"]);
+
+        alice.send("rust", "ciao bob").await.unwrap();
+        let (from, text) = expect(&mut bob_events, |e| match message(e)? {
+            ServerToClient::Message { from, text, .. } => Some((from, text)),
+            _ => None,
+        })
+        .await;
+        assert_eq!((from.as_str(), text.as_str()), ("alice", "ciao bob"));
+    }
+
+    #[tokio::test]
+    async fn client_resumes_the_session_after_a_dropped_connection() {
+        let addr = start_server().await;
+        let proxy = Proxy::start(addr).await;
+        let (alice, mut alice_events) = connect(proxy.addr, Codec::MsgPack, "alice").await;
+        let (bob, _bob_events) = connect(addr, Codec::Json, "bob").await;
+        alice.create_group("rust").await.unwrap();
+        expect(&mut alice_events, created).await;
+
+        proxy.cut();
+        expect(&mut alice_events, |e| {
+            matches!(e, Event::Reconnecting).then_some(())
+        })
+        .await;
+        expect(&mut alice_events, |e| {
+            matches!(e, Event::Reconnected).then_some(())
+        })
+        .await;
+
+        // stessa sessione: i gruppi restano e i messaggi arrivano sulla nuova connessione
+        assert_eq!(alice.list_groups().await.unwrap(), ["rust"]);
+        bob.dm("alice", "ci sei?").await.unwrap();
+        let text = expect(&mut alice_events, |e| match message(e)? {
+            ServerToClient::DirectMessage { text, .. } => Some(text),
+            _ => None,
+        })
+        .await;
+        assert_eq!(text, "ci sei?");
+    }
+}
@@ -0,0 +1,204 @@
+/*
+Modulo Console: console di amministrazione sul terminale del server (stdin), attiva salvo --no-console.
+Legge un comando per riga e lo esegue sullo stato condiviso, con le stesse operazioni dell'API HTTP
+(vedi admin.rs): elenco di utenti, gruppi e inviti, statistiche, disconnessione di un utente,
+annuncio a tutti gli utenti connessi, ricarica della configurazione (come SIGHUP) e arresto del server.
+Se stdin viene chiuso (es. server avviato come servizio) la console termina e il server continua.
+*/
+
+use chrono::{DateTime, Local};
+use ruggine_common::ErrorCode;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{mpsc, RwLock};
+
+use crate::admin::{self, Presence};
+use crate::catalog::t;
+use crate::config::Reloader;
+use crate::connection::ConnConfig;
+use crate::state::State;
+
+const HELP: &str = "\
+Comandi della console:
+  users               account registrati e stato della sessione
+  groups              gruppi con proprietario e numero di membri
+  group <nome>        dettagli di un gruppo: ruoli, membri e inviti in attesa
+  invites             inviti in attesa, con usi e scadenza
+  kick <nick>         disconnette l'utente (dovrà accedere di nuovo)
+  announce <testo>    invia un annuncio a tutti gli utenti connessi
+  stats               statistiche di utenti e connessioni
+  reload              rilegge il file di configurazione e applica le modifiche
+  shutdown [motivo]   arresta il server dopo aver avvisato i client
+  help                mostra questo elenco";
+
+pub async fn run(state: Arc<RwLock<State>>, conf: Arc<ConnConfig>, reloader: Arc<Reloader>) {
+    let started = Instant::now();
+    let mut lines = stdin_lines();
+    println!("Console di amministrazione attiva: digita 'help' per l'elenco dei comandi");
+    while let Some(line) = lines.recv().await {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (cmd, arg) = match line.split_once(' ') {
+            Some((cmd, arg)) => (cmd, arg.trim()),
+            None => (line, ""),
+        };
+        match (cmd, arg) {
+            ("help", _) => println!("{HELP}"),
+            ("users", _) => users(&*state.read().await),
+            ("groups", _) => groups(&*state.read().await),
+            ("group", name) if !name.is_empty() => group(&*state.read().await, name),
+            ("invites", _) => invites(&*state.read().await),
+            ("kick", nick) if !nick.is_empty() => {
+                match admin::kick_user(&mut *state.write().await, nick) {
+                    Ok(nick) => println!("{nick} disconnesso"),
+                    Err(code) => println!("Errore: {code}"),
+                }
+            }
+            ("announce", text) if !text.is_empty() => {
+                let sent = admin::announce_all(&*state.read().await, text);
+                println!("Annuncio inviato a {sent} utenti");
+            }
+            ("stats", _) => stats(&*state.read().await, &conf, started),
+            // l'esito della ricarica compare nel log del server
+            ("reload", _) => reloader.reload().await,
+            ("shutdown", reason) => {
+                let reason = match reason {
+                    "" => t!("shutdown.reason"),
+                    reason => reason.to_string(),
+                };
+                conf.shutdown.trigger(reason);
+            }
+            ("group" | "kick" | "announce", _) => println!("Uso: {cmd} <{}>", usage(cmd)),
+            _ => println!("Comando sconosciuto '{cmd}': digita 'help' per l'elenco"),
+        }
+    }
+}
+
+// Le righe sono lette da un thread dedicato: una lettura bloccata sul terminale
+// non deve impedire al runtime di terminare dopo l'arresto del server
+fn stdin_lines() -> mpsc::UnboundedReceiver<String> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn usage(cmd: &str) -> &'static str {
+    match cmd {
+        "group" => "nome",
+        "kick" => "nick",
+        _ => "testo",
+    }
+}
+
+fn presence(p: &Presence) -> &'static str {
+    match p {
+        Presence::Online => "connesso",
+        Presence::Detached => "in attesa di ripresa",
+        Presence::Offline => "non connesso",
+    }
+}
+
+fn users(st: &State) {
+    let users = admin::list_users(st);
+    for u in &users {
+        println!("  {:<20} {}", u.nick, presence(&u.presence));
+    }
+    println!("{} account, {} connessi", users.len(), st.clients.len());
+}
+
+fn groups(st: &State) {
+    let groups = admin::list_groups(st);
+    for g in &groups {
+        println!(
+            "  {:<20} {} membri, proprietario {}",
+            g.name,
+            g.members.len(),
+            g.owner
+        );
+    }
+    println!("{} gruppi", groups.len());
+}
+
+fn group(st: &State, name: &str) {
+    let Some(g) = admin::list_groups(st).into_iter().find(|g| g.name == name) else {
+        let group = name.to_string();
+        println!("Errore: {}", ErrorCode::GroupNotFound { group });
+        return;
+    };
+    println!("Gruppo {}", g.name);
+    println!("  proprietario:     {}", g.owner);
+    println!("  amministratori:   {}", list(&g.admins));
+    let online: Vec<String> = g
+        .members
+        .iter()
+        .map(|m| match st.tx_of_nick(m) {
+            Some(_) => format!("{m}*"),
+            None => m.clone(),
+        })
+        .collect();
+    println!("  membri:           {} (* = connesso)", list(&online));
+    let invited: Vec<String> = admin::list_invites(st)
+        .into_iter()
+        .filter(|i| i.group == g.name)
+        .map(|i| i.nick.unwrap_or_else(|| format!("link {}", i.code)))
+        .collect();
+    println!("  invitati:         {}", list(&invited));
+}
+
+fn invites(st: &State) {
+    let invites = admin::list_invites(st);
+    for i in &invites {
+        let nick = i.nick.as_deref().unwrap_or("(link)");
+        let uses = match i.max_uses {
+            Some(max) => format!("{}/{}", i.uses, max),
+            None => i.uses.to_string(),
+        };
+        let expires = i
+            .expires_at
+            .and_then(DateTime::from_timestamp_millis)
+            .map(|t| t.with_timezone(&Local).format("%d/%m %H:%M").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "  {:<20} {:<20} {:<12} usi {:<8} scade {}",
+            i.group, nick, i.code, uses, expires
+        );
+    }
+    println!("{} inviti in attesa", invites.len());
+}
+
+fn stats(st: &State, conf: &ConnConfig, started: Instant) {
+    let s = admin::stats_of(st, conf, started);
+    println!("  attivo da:        {} s", s.uptime_secs);
+    println!("  account:          {}", s.accounts);
+    println!(
+        "  connessi:         {} (+{} in attesa di ripresa)",
+        s.online, s.detached
+    );
+    println!("  gruppi:           {}", s.groups);
+    println!("  inviti:           {}", s.invites);
+    println!(
+        "  connessioni:      {} aperte, {} accettate",
+        s.connections_open, s.connections_accepted
+    );
+    println!(
+        "  scartati:         {} drop-oldest, {} drop-newest, {} client lenti disconnessi",
+        s.dropped_oldest, s.dropped_newest, s.evicted
+    );
+}
+
+fn list(names: &[String]) -> String {
+    if names.is_empty() {
+        "-".to_string()
+    } else {
+        names.join(", ")
+    }
+}
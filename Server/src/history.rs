@@ -0,0 +1,353 @@
+/*
+Modulo History: mantiene su disco la cronologia limitata dei messaggi di ciascun gruppo.
+Ogni gruppo ha un file NDJSON in append; in memoria viene tenuta solo la coda degli ultimi messaggi.
+Le scritture su disco sono affidate a un thread dedicato, alimentato da un canale nell'ordine in cui
+vengono richieste: chi invia un messaggio non aspetta il disco e non lo fa aspettare agli altri.
+*/
+
+use chrono::Utc;
+use ruggine_common::HistoryEntry;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex, MutexGuard, OnceLock};
+use tokio::sync::oneshot;
+use tracing::warn;
+
+pub const DEFAULT_HISTORY_DIR: &str = "data/history";
+pub const DEFAULT_MAX_PER_GROUP: usize = 500;
+// dimensione pagina usata se il client non specifica un limite, e limite massimo accettato
+pub const DEFAULT_PAGE: usize = 50;
+pub const MAX_PAGE: usize = 200;
+
+// Cronologia di un singolo gruppo caricata in memoria
+struct GroupLog {
+    entries: VecDeque<HistoryEntry>,
+    next_id: u64,
+    lines_on_disk: usize, // righe presenti nel file, usato per decidere quando compattare
+}
+
+impl GroupLog {
+    fn empty() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            next_id: 1,
+            lines_on_disk: 0,
+        }
+    }
+}
+
+// Operazione per il thread di scrittura
+enum DiskOp {
+    Append {
+        path: PathBuf,
+        entry: HistoryEntry,
+    },
+    Rewrite {
+        path: PathBuf,
+        entries: Vec<HistoryEntry>,
+    },
+    Remove {
+        path: PathBuf,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    // risponde quando tutte le operazioni precedenti sono state eseguite
+    Flush(oneshot::Sender<()>),
+}
+
+// La coda in memoria è protetta da un proprio lock, così chi invia un messaggio
+// non ha bisogno del lock in scrittura sullo stato
+pub struct HistoryStore {
+    dir: PathBuf,
+    max_per_group: usize,
+    logs: Mutex<HashMap<String, GroupLog>>,
+    // Chiave: nome del gruppo in minuscolo (i nomi sono unici case-insensitive)
+    disk: OnceLock<mpsc::Sender<DiskOp>>,
+    // Canale verso il thread di scrittura, avviato alla prima scrittura
+}
+
+impl Default for HistoryStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_DIR, DEFAULT_MAX_PER_GROUP)
+    }
+}
+
+impl HistoryStore {
+    pub fn new(dir: impl Into<PathBuf>, max_per_group: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            max_per_group: max_per_group.max(1),
+            logs: Mutex::new(HashMap::new()),
+            disk: OnceLock::new(),
+        }
+    }
+
+    // Percorso del file di un gruppo: i nomi sono già validati come alfanumerici ASCII
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.ndjson"))
+    }
+
+    fn logs(&self) -> MutexGuard<'_, HashMap<String, GroupLog>> {
+        self.logs.lock().expect("cronologia avvelenata")
+    }
+
+    // Accoda una scrittura; il thread viene avviato la prima volta
+    fn write(&self, op: DiskOp) {
+        let disk = self.disk.get_or_init(|| {
+            let (tx, rx) = mpsc::channel();
+            let dir = self.dir.clone();
+            std::thread::Builder::new()
+                .name("history-writer".into())
+                .spawn(move || write_loop(&dir, rx))
+                .expect("avvio del thread della cronologia");
+            tx
+        });
+        if disk.send(op).is_err() {
+            warn!("Thread di scrittura della cronologia terminato");
+        }
+    }
+
+    // Carica (una sola volta) la cronologia del gruppo dal disco: da quel momento la copia in memoria
+    // è sempre la più recente, anche se le scritture accodate non sono ancora state eseguite
+    fn log_mut<'a>(&self, logs: &'a mut HashMap<String, GroupLog>, key: &str) -> &'a mut GroupLog {
+        logs.entry(key.to_string())
+            .or_insert_with(|| self.load(key))
+    }
+
+    fn load(&self, key: &str) -> GroupLog {
+        let mut log = GroupLog::empty();
+        let file = match fs::File::open(self.path_for(key)) {
+            Ok(f) => f,
+            Err(_) => return log, // nessuna cronologia salvata per questo gruppo
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            log.lines_on_disk += 1;
+            match serde_json::from_str::<HistoryEntry>(&line) {
+                Ok(entry) => {
+                    log.next_id = log.next_id.max(entry.id + 1);
+                    log.entries.push_back(entry);
+                    if log.entries.len() > self.max_per_group {
+                        log.entries.pop_front();
+                    }
+                }
+                Err(e) => warn!("Riga di cronologia non valida per {}: {}", key, e),
+            }
+        }
+        log
+    }
+
+    // Aggiunge un messaggio alla cronologia del gruppo e ne accoda la scrittura su disco
+    pub fn append(&self, group: &str, from: &str, text: &str) -> HistoryEntry {
+        let max = self.max_per_group;
+        let key = group.to_ascii_lowercase();
+        let path = self.path_for(&key);
+
+        let mut logs = self.logs();
+        let log = self.log_mut(&mut logs, &key);
+        let entry = HistoryEntry {
+            id: log.next_id,
+            from: from.to_string(),
+            text: text.to_string(),
+            ts: Utc::now().timestamp_millis(),
+        };
+        log.next_id += 1;
+        log.entries.push_back(entry.clone());
+        if log.entries.len() > max {
+            log.entries.pop_front();
+        }
+
+        // Quando il file supera il doppio del limite lo riscriviamo con i soli messaggi tenuti
+        let op = if log.lines_on_disk + 1 > max * 2 {
+            log.lines_on_disk = log.entries.len();
+            DiskOp::Rewrite {
+                path,
+                entries: log.entries.iter().cloned().collect(),
+            }
+        } else {
+            log.lines_on_disk += 1;
+            DiskOp::Append {
+                path,
+                entry: entry.clone(),
+            }
+        };
+        drop(logs);
+        self.write(op);
+        entry
+    }
+
+    // Restituisce al più `limit` messaggi con id < `before` (in ordine cronologico), che codificati
+    // in JSON occupano al più `max_bytes`, e un flag che indica se esistono messaggi ancora più vecchi.
+    // Il messaggio più recente è sempre incluso, così chi scorre la cronologia può andare avanti
+    pub fn page(
+        &self,
+        group: &str,
+        before: Option<u64>,
+        limit: usize,
+        max_bytes: usize,
+    ) -> (Vec<HistoryEntry>, bool) {
+        let mut logs = self.logs();
+        let log = self.log_mut(&mut logs, &group.to_ascii_lowercase());
+        let older: Vec<&HistoryEntry> = log
+            .entries
+            .iter()
+            .filter(|e| before.is_none_or(|b| e.id < b))
+            .collect();
+        let mut start = older.len();
+        let mut bytes = 0;
+        while start > 0 && older.len() - start < limit {
+            // la virgola che separa le voci nell'array
+            bytes += serde_json::to_vec(older[start - 1]).map_or(0, |v| v.len()) + 1;
+            if bytes > max_bytes && start < older.len() {
+                break;
+            }
+            start -= 1;
+        }
+        let page = older[start..].iter().map(|e| (*e).clone()).collect();
+        (page, start > 0)
+    }
+
+    // Cancella la cronologia di un gruppo sciolto, così un nuovo gruppo con lo stesso nome parte vuoto.
+    // In memoria resta una cronologia vuota: il file potrebbe non essere ancora stato cancellato
+    pub fn purge(&self, group: &str) {
+        let key = group.to_ascii_lowercase();
+        let path = self.path_for(&key);
+        self.logs().insert(key, GroupLog::empty());
+        self.write(DiskOp::Remove { path });
+    }
+
+    // Sposta la cronologia sotto il nuovo nome del gruppo. La coda viene prima caricata in memoria:
+    // da lì in poi non serve rileggere il file, che potrebbe non essere ancora stato rinominato
+    pub fn rename(&self, group: &str, new_name: &str) {
+        let (from, to) = (group.to_ascii_lowercase(), new_name.to_ascii_lowercase());
+        if from == to {
+            return;
+        }
+        let mut logs = self.logs();
+        self.log_mut(&mut logs, &from);
+        let log = logs.remove(&from).expect("cronologia appena caricata");
+        // il vecchio nome resta vuoto, come dopo purge
+        logs.insert(from.clone(), GroupLog::empty());
+        logs.insert(to.clone(), log);
+        drop(logs);
+        self.write(DiskOp::Rename {
+            from: self.path_for(&from),
+            to: self.path_for(&to),
+        });
+    }
+
+    // Si completa quando le scritture accodate finora sono su disco (usato all'arresto)
+    pub fn flush(&self) -> oneshot::Receiver<()> {
+        let (done, rx) = oneshot::channel();
+        match self.disk.get() {
+            Some(_) => self.write(DiskOp::Flush(done)),
+            None => {
+                let _ = done.send(());
+            }
+        }
+        rx
+    }
+}
+
+// Esegue le scritture nell'ordine di arrivo, finché esiste lo HistoryStore
+fn write_loop(dir: &Path, ops: mpsc::Receiver<DiskOp>) {
+    for op in ops {
+        let (path, result) = match op {
+            DiskOp::Append { path, entry } => {
+                let result = append_line(dir, &path, &entry);
+                (path, result)
+            }
+            DiskOp::Rewrite { path, entries } => {
+                let result = rewrite(dir, &path, &entries);
+                (path, result)
+            }
+            DiskOp::Remove { path } => {
+                let result = match fs::remove_file(&path) {
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                    other => other,
+                };
+                (path, result)
+            }
+            // un gruppo senza messaggi non ha ancora un file
+            DiskOp::Rename { from, to } => {
+                let result = match fs::rename(&from, &to) {
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                    other => other,
+                };
+                (from, result)
+            }
+            DiskOp::Flush(done) => {
+                let _ = done.send(());
+                continue;
+            }
+        };
+        if let Err(e) = result {
+            warn!(
+                "Impossibile aggiornare la cronologia {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+fn append_line(dir: &Path, path: &Path, entry: &HistoryEntry) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let line = serde_json::to_string(entry)? + "\n";
+    file.write_all(line.as_bytes())
+}
+
+// Riscrittura atomica: file temporaneo + rename
+fn rewrite(dir: &Path, path: &Path, entries: &[HistoryEntry]) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let tmp = path.with_extension("ndjson.tmp");
+    let mut file = fs::File::create(&tmp)?;
+    for entry in entries {
+        let line = serde_json::to_string(entry)? + "\n";
+        file.write_all(line.as_bytes())?;
+    }
+    file.sync_all()?;
+    fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> (HistoryStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("ruggine-history-{}", uuid::Uuid::new_v4()));
+        (HistoryStore::new(&dir, 100), dir)
+    }
+
+    #[test]
+    fn page_limited_by_count_and_bytes() {
+        let (history, dir) = store();
+        let text = "x".repeat(1000);
+        for _ in 0..10 {
+            history.append("team", "alice", &text);
+        }
+
+        let (page, has_more) = history.page("team", None, 4, usize::MAX);
+        assert_eq!(page.iter().map(|e| e.id).collect::<Vec<_>>(), [7, 8, 9, 10]);
+        assert!(has_more);
+
+        // poco più di tre voci: la pagina si ferma alle tre più recenti
+        let (page, has_more) = history.page("team", None, 50, 3500);
+        assert_eq!(page.iter().map(|e| e.id).collect::<Vec<_>>(), [8, 9, 10]);
+        assert!(has_more);
+        let (page, _) = history.page("team", Some(8), 50, 3500);
+        assert_eq!(page.iter().map(|e| e.id).collect::<Vec<_>>(), [5, 6, 7]);
+
+        // anche con un limite più piccolo di una voce si va avanti di un messaggio alla volta
+        let (page, has_more) = history.page("team", Some(2), 50, 10);
+        assert_eq!(page.iter().map(|e| e.id).collect::<Vec<_>>(), [1]);
+        assert!(!has_more);
+
+        history.flush().blocking_recv().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,339 @@
+/*
+Modulo Invites: codici invito con autore, data di creazione, scadenza facoltativa e numero massimo di usi.
+Un invito è destinato a un utente (vale una sola volta e solo per lui) oppure è un link aperto,
+usabile da chiunque fino alla scadenza o all'esaurimento degli usi.
+Gli inviti scaduti vengono rimossi da un task periodico (`run_sweeper`); i codici non più validi
+restano ricordati in memoria per un giorno, così chi li usa riceve il motivo preciso del rifiuto.
+*/
+
+use chrono::Utc;
+use ruggine_common::{ErrorCode, InviteEntry};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::config::Limits;
+use crate::state::State;
+use crate::util::short_code;
+
+// Intervallo tra due passaggi del task che rimuove gli inviti scaduti
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+// Per quanto si ricorda il motivo per cui un codice non è più valido (millisecondi)
+const RETIRED_FOR: i64 = 24 * 60 * 60 * 1000;
+
+// Invito salvato nello stato persistente
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invite {
+    pub group: String,
+    pub nick: Option<String>, // destinatario; None per un link aperto
+    pub created_by: String,
+    pub created_at: i64, // timestamp unix in millisecondi
+    pub expires_at: Option<i64>,
+    pub max_uses: Option<u32>, // solo per i link aperti; None = nessun limite
+    pub uses: u32,
+}
+
+// Motivo per cui un codice non è più valido
+#[derive(Debug, Clone, Copy)]
+pub enum InviteEnd {
+    Expired,
+    Revoked,
+    UsedUp { max_uses: u32 },
+}
+
+impl Invite {
+    // Nuovo invito creato ora da `by`; `ttl` in secondi
+    pub fn new(group: &str, nick: Option<String>, by: &str, ttl: Option<u64>) -> Self {
+        let now = now_ms();
+        Self {
+            group: group.to_string(),
+            nick,
+            created_by: by.to_string(),
+            created_at: now,
+            expires_at: ttl.map(|secs| now.saturating_add(secs.saturating_mul(1000) as i64)),
+            max_uses: None,
+            uses: 0,
+        }
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|t| t <= now)
+    }
+
+    // Usi ammessi: uno solo per gli inviti destinati a un utente
+    pub fn limit(&self) -> Option<u32> {
+        match self.nick {
+            Some(_) => Some(1),
+            None => self.max_uses,
+        }
+    }
+
+    pub fn entry(&self, code: &str) -> InviteEntry {
+        InviteEntry {
+            code: code.to_string(),
+            group: self.group.clone(),
+            nick: self.nick.clone(),
+            created_by: self.created_by.clone(),
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            uses: self.uses,
+            max_uses: self.limit(),
+        }
+    }
+}
+
+impl InviteEnd {
+    pub fn error(self) -> ErrorCode {
+        match self {
+            Self::Expired => ErrorCode::InviteExpired,
+            Self::Revoked => ErrorCode::InviteRevoked,
+            Self::UsedUp { max_uses } => ErrorCode::InviteUsedUp { max_uses },
+        }
+    }
+}
+
+pub fn now_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+// Durata richiesta dal client o, se assente, quella di default del server (--invite-ttl, 0 = nessuna)
+pub fn ttl_or_default(ttl: Option<u64>, limits: &Limits) -> Result<Option<u64>, ErrorCode> {
+    match ttl {
+        Some(0) => Err(ErrorCode::InvalidInviteLimit),
+        Some(secs) => Ok(Some(secs)),
+        None => Ok(Some(limits.invite_ttl).filter(|&secs| secs > 0)),
+    }
+}
+
+// Codice non ancora usato, né tra gli inviti validi né tra quelli ricordati
+pub fn new_code(st: &State) -> String {
+    let len = st.limits.invite_code_len;
+    loop {
+        let code = short_code(len);
+        if !st.invites.contains_key(&code) && !st.retired.contains_key(&code) {
+            return code;
+        }
+    }
+}
+
+// Rimuove periodicamente gli inviti scaduti e dimentica i codici ritirati da più di un giorno
+pub async fn run_sweeper(state: Arc<RwLock<State>>) {
+    let mut tick = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        tick.tick().await;
+        let removed = sweep(&mut *state.write().await, now_ms());
+        if removed > 0 {
+            info!("Inviti scaduti rimossi: {}", removed);
+        }
+    }
+}
+
+// Un passaggio del task all'istante `now`; restituisce il numero di inviti scaduti rimossi
+fn sweep(st: &mut State, now: i64) -> usize {
+    let expired: Vec<String> = st
+        .invites
+        .iter()
+        .filter(|(_, i)| i.is_expired(now))
+        .map(|(code, _)| code.clone())
+        .collect();
+    for code in &expired {
+        st.retire_invite(code, InviteEnd::Expired);
+    }
+    st.retired
+        .retain(|_, (_, since)| now - *since < RETIRED_FOR);
+    expired.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{join_group, revoke_invite, Session};
+    use crate::history::HistoryStore;
+    use crate::outbox::{channel, Next, QueuePolicy, QueueStats};
+    use crate::state::{Account, Group};
+    use futures::FutureExt;
+    use ruggine_common::{Envelope, ServerToClient};
+    use uuid::Uuid;
+
+    // Gruppo "team" di olga; max, zoe e ugo hanno un account ma non ne fanno parte
+    fn state() -> Arc<RwLock<State>> {
+        let dir = std::env::temp_dir().join(format!("ruggine-invites-{}", Uuid::new_v4()));
+        let mut st = State {
+            history: HistoryStore::new(dir, 10),
+            ..State::default()
+        };
+        for nick in ["olga", "max", "zoe", "ugo"] {
+            st.accounts.insert(
+                nick.to_string(),
+                Account {
+                    nick: nick.to_string(),
+                    password_hash: String::new(),
+                },
+            );
+        }
+        st.groups.insert(
+            "team".into(),
+            Group {
+                members: ["olga".to_string()].into(),
+                owner: "olga".into(),
+                ..Group::default()
+            },
+        );
+        Arc::new(RwLock::new(st))
+    }
+
+    fn link(max_uses: Option<u32>, expires_at: Option<i64>) -> Invite {
+        Invite {
+            max_uses,
+            expires_at,
+            ..Invite::new("team", None, "olga", None)
+        }
+    }
+
+    // Esegue `run` per conto di `nick` e restituisce l'errore ricevuto, se c'è
+    async fn reply<F, Fut>(nick: &str, run: F) -> Option<ErrorCode>
+    where
+        F: FnOnce(Session, crate::state::Tx) -> Fut,
+        Fut: std::future::Future<Output = ()>,
+    {
+        let (tx, mut rx) = channel(16, QueuePolicy::DropNewest, Arc::new(QueueStats::default()));
+        let session = Session {
+            id: Uuid::new_v4(),
+            nick: nick.to_string(),
+        };
+        run(session, tx).await;
+        // `tx` non esiste più: la coda termina con Closed dopo le risposte
+        loop {
+            match rx.recv().now_or_never()? {
+                Next::Send(Envelope {
+                    body: ServerToClient::Error { code, .. },
+                    ..
+                }) => return Some(code),
+                Next::Send(_) => {}
+                _ => return None,
+            }
+        }
+    }
+
+    async fn join(nick: &str, code: &str, state: &Arc<RwLock<State>>) -> Option<ErrorCode> {
+        reply(nick, |session, tx| async move {
+            join_group::handle("team".into(), code.into(), &session, &tx, state).await
+        })
+        .await
+    }
+
+    async fn revoke(nick: &str, code: &str, state: &Arc<RwLock<State>>) -> Option<ErrorCode> {
+        reply(nick, |session, tx| async move {
+            revoke_invite::handle(code.into(), &session, &tx, state).await
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn expired_invites_are_refused_with_the_reason() {
+        let state = state();
+        let now = now_ms();
+        {
+            let mut st = state.write().await;
+            st.invites.insert("old".into(), link(None, Some(now - 1)));
+            st.invites.insert("late".into(), link(None, Some(now - 1)));
+            st.invites
+                .insert("fresh".into(), link(None, Some(now + 60_000)));
+            st.invites.insert("forever".into(), link(None, None));
+            assert_eq!(sweep(&mut st, now), 2);
+            assert!(st.invites.contains_key("fresh") && st.invites.contains_key("forever"));
+            assert_eq!(st.invite_error("old"), ErrorCode::InviteExpired);
+            // scaduto ma non ancora rimosso dal task periodico
+            st.invites.insert("stale".into(), link(None, Some(now - 1)));
+        }
+        assert_eq!(
+            join("max", "old", &state).await,
+            Some(ErrorCode::InviteExpired)
+        );
+        assert_eq!(
+            join("max", "stale", &state).await,
+            Some(ErrorCode::InviteExpired)
+        );
+        assert!(!state.read().await.invites.contains_key("stale"));
+        assert_eq!(join("max", "fresh", &state).await, None);
+
+        // dopo un giorno il motivo viene dimenticato
+        let mut st = state.write().await;
+        sweep(&mut st, now + RETIRED_FOR + 60_000);
+        assert_eq!(st.invite_error("old"), ErrorCode::InvalidInvite);
+        assert!(st.invites.contains_key("forever"));
+    }
+
+    #[tokio::test]
+    async fn open_links_are_used_up_after_max_uses() {
+        let state = state();
+        state
+            .write()
+            .await
+            .invites
+            .insert("link".into(), link(Some(2), None));
+        assert_eq!(join("max", "link", &state).await, None);
+        assert_eq!(state.read().await.invites["link"].uses, 1);
+        assert_eq!(
+            join("max", "link", &state).await,
+            Some(ErrorCode::AlreadyMember {
+                group: "team".into()
+            })
+        );
+        assert_eq!(join("zoe", "link", &state).await, None);
+        assert_eq!(
+            join("ugo", "link", &state).await,
+            Some(ErrorCode::InviteUsedUp { max_uses: 2 })
+        );
+        let st = state.read().await;
+        assert!(!st.invites.contains_key("link"));
+        assert_eq!(st.groups["team"].members.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn personal_invites_are_single_use_and_for_one_user() {
+        let state = state();
+        let invite = Invite::new("team", Some("max".into()), "olga", None);
+        assert_eq!(invite.limit(), Some(1));
+        state.write().await.invites.insert("solo".into(), invite);
+        assert_eq!(
+            join("zoe", "solo", &state).await,
+            Some(ErrorCode::InviteForOther { nick: "max".into() })
+        );
+        assert_eq!(join("max", "solo", &state).await, None);
+        assert_eq!(
+            join("zoe", "solo", &state).await,
+            Some(ErrorCode::InviteUsedUp { max_uses: 1 })
+        );
+    }
+
+    #[tokio::test]
+    async fn revoked_invites_are_refused_with_the_reason() {
+        let state = state();
+        state
+            .write()
+            .await
+            .invites
+            .insert("link".into(), link(None, None));
+        // chi non è nel gruppo non scopre nulla del codice
+        assert_eq!(
+            revoke("ugo", "link", &state).await,
+            Some(ErrorCode::InvalidInvite)
+        );
+        assert_eq!(revoke("olga", "link", &state).await, None);
+        assert_eq!(
+            join("max", "link", &state).await,
+            Some(ErrorCode::InviteRevoked)
+        );
+        assert_eq!(
+            revoke("olga", "link", &state).await,
+            Some(ErrorCode::InviteRevoked)
+        );
+        assert_eq!(
+            join("max", "nope", &state).await,
+            Some(ErrorCode::InvalidInvite)
+        );
+    }
+}
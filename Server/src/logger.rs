@@ -1,16 +1,34 @@
 /*
 Modulo Logger: registra periodicamente l'utilizzo della CPU e il tempo di esecuzione del server in un file di log.
-Utile per monitoraggio e analisi delle prestazioni.
+Registra anche i contatori dei messaggi scartati dalle code in uscita.
+Utile per monitoraggio e analisi delle prestazioni; file e intervallo si scelgono con --cpu-log e
+--cpu-log-interval e possono cambiare con la ricarica della configurazione (modulo config). Per metriche più dettagliate c'è l'esportatore Prometheus (modulo metrics).
 */
 
+use crate::outbox::QueueStats;
 use anyhow::Result;
 use chrono::Local;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::sync::Arc;
 use sysinfo::{Pid, System};
+use tokio::sync::watch;
 use tokio::time::{sleep, Duration};
 
-pub async fn start_cpu_logger(log_path: &str) -> Result<()> {
+pub const DEFAULT_CPU_LOG: &str = "server_cpu.log";
+pub const DEFAULT_CPU_LOG_INTERVAL: u64 = 120;
+
+// File di log e intervallo tra due righe (zero = file disattivato)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuLog {
+    pub path: String,
+    pub interval: Duration,
+}
+
+pub async fn start_cpu_logger(
+    mut settings: watch::Receiver<CpuLog>,
+    queue_stats: Arc<QueueStats>,
+) -> Result<()> {
     // Ottengo il pid del processo server
     let pid = Pid::from(std::process::id() as usize);
     // Crea un'istanza mutabile della struttura System della crate sysinfo,
@@ -18,7 +36,27 @@ pub async fn start_cpu_logger(log_path: &str) -> Result<()> {
     let mut sys = System::new_all();
 
     loop {
-        sleep(Duration::from_secs(120)).await;
+        let CpuLog {
+            path: log_path,
+            interval,
+        } = settings.borrow_and_update().clone();
+        // Con il file disattivato si attende solo una nuova configurazione;
+        // se la configurazione cambia durante l'attesa si riparte con i nuovi valori
+        if interval.is_zero() {
+            if settings.changed().await.is_err() {
+                return Ok(());
+            }
+            continue;
+        }
+        tokio::select! {
+            _ = sleep(interval) => {}
+            res = settings.changed() => {
+                if res.is_err() {
+                    return Ok(());
+                }
+                continue;
+            }
+        }
 
         // Ottiene tutti i processi del sistema e ne aggiorna le informazioni
         sys.refresh_processes();
@@ -32,22 +70,25 @@ pub async fn start_cpu_logger(log_path: &str) -> Result<()> {
             let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
 
             // Formatta la stringa da inserire come append nel file
-            // Formattiamo cpu_usage in larghezza fissa con 2 decimali e virgola come separatore
-            // es: " 300,00" o "   0,05" in modo che tutte le righe siano allineate
-            let cpu_str = format!("{:7.2}", cpu_usage).replace('.', ",");
+            // Formattiamo cpu_usage in larghezza fissa con 2 decimali e il punto come separatore
+            // es: " 300.00" o "   0.05" in modo che tutte le righe siano allineate
+            let cpu_str = format!("{:7.2}", cpu_usage);
             // run_time (minuti) in larghezza fissa per tenere la colonna allineata
             let run_time_str = format!("{:3}", run_time);
 
+            // messaggi scartati dalle code in uscita e client lenti disconnessi dall'avvio
+            let (oldest, newest, evicted) = queue_stats.snapshot();
+
             let log_line = format!(
-                "[{}] CPU: {}% | Run Time: {} min\n",
-                timestamp, cpu_str, run_time_str
+                "[{}] CPU: {}% | Run Time: {} min | Scartati: {} vecchi, {} nuovi | Client lenti disconnessi: {}\n",
+                timestamp, cpu_str, run_time_str, oldest, newest, evicted
             );
 
             // Apertura file con le seguenti opzioni
             let mut file = OpenOptions::new()
                 .create(true) // Se esiste già lo apre solamente
                 .append(true) // Modalità append
-                .open(log_path)?; // Apre il file in log_path passato come parametro
+                .open(&log_path)?; // Apre il file in log_path passato come parametro
             file.write_all(log_line.as_bytes())?; // Scrittura dell'intero buffer
         }
     }
@@ -3,51 +3,237 @@ Entry point del server chat.
 Inizializza il logger, la struttura di stato e avvia il ciclo di accettazione delle connessioni.
 */
 
-use clap::Parser;
-use ctrlc;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::info;
 
+mod admin;
 mod args;
+mod auth;
+mod bans;
+mod catalog;
 pub mod commands;
+mod config;
 mod connection;
+mod console;
+mod history;
+mod invites;
 mod logger;
+mod metrics;
+mod outbox;
+mod rate_limit;
 mod server;
+mod shutdown;
 mod state;
+mod storage;
+mod tls;
+mod transport;
 mod util;
 mod validation;
+mod websocket;
 
-use args::Args;
+use bans::BanList;
+use catalog::t;
+use config::{Limits, Reloader};
+use connection::{ConnConfig, ConnStats};
+use history::HistoryStore;
+use logger::CpuLog;
+use metrics::Metrics;
+use outbox::QueueStats;
+use rate_limit::RateLimitConfig;
+use server::Transport;
+use shutdown::Shutdown;
 use state::State;
+use storage::Storage;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    //configura il logging affinché vengano visualizzati solo
+    //i messaggi informativi, di warning ed errore, con un formato leggibile in console.
+    tracing_subscriber::fmt().with_env_filter("info").init();
+
+    // Riga di comando e, con --config, file di configurazione (la riga di comando prevale)
+    let argv: Vec<_> = std::env::args_os().collect();
+    let (args, settings) = match config::load(&argv) {
+        Ok(loaded) => loaded,
+        Err(e) => match e.downcast::<clap::Error>() {
+            Ok(e) => e.exit(),
+            Err(e) => return Err(e),
+        },
+    };
+    if let Some(path) = &args.config {
+        info!("Configurazione letta da {}", path);
+    }
+    // Lingua degli avvisi e del testo degli errori; i client traducono gli errori nella propria
+    ruggine_common::i18n::set_lang(args.lang.unwrap_or_else(ruggine_common::Lang::from_env));
+    // Caricamento dello stato persistente: snapshot + modifiche del journal
+    let (storage, snapshot, journal) = Storage::open(&args.data_dir, args.snapshot_every)?;
+    let replayed = journal.len();
+    let history = HistoryStore::new(&args.history_dir, args.history_max);
+    let mut st = State::restore(storage, history, snapshot, journal);
+    // Ban salvati e operatori indicati all'avvio
+    st.bans = BanList::load(Path::new(&args.data_dir))?;
+    st.limits = Limits::from_args(&args);
+    st.operators = args
+        .operators
+        .iter()
+        .map(|n| n.to_ascii_lowercase())
+        .collect();
+    info!(
+        "Stato caricato da {}: {} account, {} gruppi, {} inviti",
+        args.data_dir,
+        st.accounts.len(),
+        st.groups.len(),
+        st.invites.len()
+    );
+    let (banned_nicks, banned_ips) = st.bans.counts();
+    if banned_nicks + banned_ips > 0 || !st.operators.is_empty() {
+        info!(
+            "Moderazione: {} operatori, {} account e {} indirizzi banditi",
+            st.operators.len(),
+            banned_nicks,
+            banned_ips
+        );
+    }
+    // compatta subito il journal riapplicato in un nuovo snapshot
+    if replayed > 0 {
+        st.save_snapshot();
+    }
+    let state = Arc::new(RwLock::new(st));
+
+    // Acceptor TLS opzionale: senza --tls-cert/--tls-key le connessioni restano in chiaro
+    let tls = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(tls::load_acceptor(cert, key)?),
+        _ => None,
+    };
+
+    // Limiti anti-flood applicati a ogni connessione
+    let rate_limit = RateLimitConfig::parse(&args.rate_limit, &args.kind_limits, args.max_strikes)
+        .map_err(anyhow::Error::msg)?;
+    let queue_stats = Arc::new(QueueStats::default());
+    let conf = Arc::new(ConnConfig {
+        rate_limit: std::sync::RwLock::new(rate_limit),
+        queue_size: args.queue_size,
+        queue_policy: args.queue_policy,
+        queue_stats: queue_stats.clone(),
+        idle_timeout: Duration::from_secs(args.idle_timeout.max(1)),
+        grace: Duration::from_secs(args.grace),
+        max_frame: args.max_frame,
+        stats: ConnStats::default(),
+        metrics: Metrics::default(),
+        shutdown: Shutdown::new(
+            Duration::from_secs(args.shutdown_timeout),
+            args.reconnect_after,
+        ),
+    });
+
     // Avvio del logger in background - task asincrono
-    //serve per loggare l'uso della CPU periodicamente (ogni due minuti)
-    tokio::spawn(async {
-        if let Err(e) = logger::start_cpu_logger("server_cpu.log").await {
+    //serve per loggare l'uso della CPU periodicamente (ogni --cpu-log-interval secondi), insieme ai messaggi scartati.
+    //Resta attivo anche con intervallo 0, perché la ricarica della configurazione può abilitarlo
+    let (cpu_log, cpu_log_rx) = tokio::sync::watch::channel(CpuLog {
+        path: args.cpu_log.clone(),
+        interval: Duration::from_secs(args.cpu_log_interval),
+    });
+    tokio::spawn(async move {
+        if let Err(e) = logger::start_cpu_logger(cpu_log_rx, queue_stats).await {
             eprintln!("Errore logger CPU: {:?}", e);
         }
     });
 
-    //configura il logging affinché vengano visualizzati solo 
-    //i messaggi informativi, di warning ed errore, con un formato leggibile in console.
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    // hash fittizio per i login con nickname inesistente, calcolato subito e non al primo tentativo
+    tokio::task::spawn_blocking(auth::dummy_hash);
+
+    // Rimozione periodica degli inviti scaduti
+    tokio::spawn(invites::run_sweeper(state.clone()));
 
-    let args = Args::parse();
-    let state = Arc::new(RwLock::new(State::default()));
+    // Ricarica della configurazione con SIGHUP o con il comando `reload` della console
+    let reloader = Arc::new(Reloader::new(
+        argv,
+        settings,
+        state.clone(),
+        conf.clone(),
+        cpu_log,
+    ));
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = signal(SignalKind::hangup())?;
+        let reloader = reloader.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("SIGHUP ricevuto: ricarica della configurazione");
+                reloader.reload().await;
+            }
+        });
+    }
 
     // Log dell'indirizzo di bind (il bind vero avviene nel modulo server)
-    info!("Server in ascolto su {}", args.bind);
+    info!(
+        "Server in ascolto su {}{}",
+        args.bind,
+        if tls.is_some() { " (TLS)" } else { "" }
+    );
+
+    // Gateway WebSocket opzionale per i client web, con lo stesso stato e gli stessi comandi
+    if let Some(ws_bind) = args.ws_bind.clone() {
+        info!(
+            "WebSocket in ascolto su {}{}",
+            ws_bind,
+            if tls.is_some() { " (TLS)" } else { "" }
+        );
+        let (state, tls, conf) = (state.clone(), tls.clone(), conf.clone());
+        tokio::spawn(async move {
+            if let Err(e) = server::run(&ws_bind, Transport::WebSocket, state, tls, conf).await {
+                eprintln!("Errore gateway WebSocket: {:?}", e);
+            }
+        });
+    }
+
+    // API HTTP di amministrazione, solo se richiesta e protetta dal token
+    if let (Some(admin_bind), Some(token)) = (args.admin_bind.clone(), args.admin_token.clone()) {
+        info!("API di amministrazione in ascolto su http://{}", admin_bind);
+        let (state, conf) = (state.clone(), conf.clone());
+        tokio::spawn(async move {
+            if let Err(e) = admin::run(&admin_bind, token, state, conf).await {
+                eprintln!("Errore API di amministrazione: {:?}", e);
+            }
+        });
+    }
 
-    // Gestore CTRL+C per shutdown pulito
-    ctrlc::set_handler(move || {
-        println!("Server non più in ascolto");
-        std::process::exit(0);
-    })
-    .expect("Errore nel registrare il gestore CTRL+C");
+    // Esportatore delle metriche in formato Prometheus
+    if let Some(metrics_bind) = args.metrics_bind.clone() {
+        info!("Metriche disponibili su http://{}/metrics", metrics_bind);
+        let (state, conf) = (state.clone(), conf.clone());
+        tokio::spawn(async move {
+            if let Err(e) = metrics::run(&metrics_bind, state, conf).await {
+                eprintln!("Errore esportatore metriche: {:?}", e);
+            }
+        });
+    }
+
+    // Console di amministrazione sul terminale del server
+    if !args.no_console {
+        let (state, conf, reloader) = (state.clone(), conf.clone(), reloader.clone());
+        tokio::spawn(console::run(state, conf, reloader));
+    }
+
+    // CTRL+C avvia l'arresto ordinato, come il comando `shutdown` della console;
+    // un secondo CTRL+C termina subito senza aspettare i client
+    let sig_conf = conf.clone();
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if !sig_conf.shutdown.trigger(t!("shutdown.reason")) {
+                println!("Arresto forzato");
+                std::process::exit(1);
+            }
+        }
+    });
 
-    // Avvia il loop del server (bind + accept + spawn connessioni)
-    server::run(&args.bind, state).await
+    // Avvia il loop del server (bind + accept + spawn connessioni) fino alla richiesta di arresto
+    server::run(&args.bind, Transport::Tcp, state.clone(), tls, conf.clone()).await?;
+    println!("Server non più in ascolto");
+    shutdown::finish(&state, &conf).await;
+    Ok(())
 }
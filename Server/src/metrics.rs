@@ -0,0 +1,256 @@
+/*
+Modulo Metrics: contatori del server esportati in formato Prometheus (testo) su HTTP (--metrics-bind).
+Le connessioni aggiornano i contatori di `Metrics` (messaggi per tipo, latenza del dispatch, byte
+ricevuti e inviati); CPU, memoria, utenti e gruppi vengono letti al momento della richiesta.
+*/
+
+use axum::extract::State as AxState;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use sysinfo::{Pid, System};
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+use crate::connection::ConnConfig;
+use crate::state::State;
+
+// Limiti superiori (in secondi) dei bucket dell'istogramma di latenza del dispatch
+const LATENCY_BUCKETS: [f64; 10] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.1, 1.0,
+];
+
+// Contatori aggiornati dalle connessioni
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub bytes_in: AtomicU64,  // byte dei messaggi ricevuti (senza delimitatori)
+    pub bytes_out: AtomicU64, // byte dei messaggi inviati (senza delimitatori)
+    dispatch: Mutex<BTreeMap<&'static str, Latency>>,
+}
+
+// Istogramma della durata del dispatch per un tipo di messaggio
+#[derive(Debug, Default)]
+struct Latency {
+    buckets: [u64; LATENCY_BUCKETS.len()], // conteggi non cumulativi, uno per bucket
+    count: u64,
+    sum: f64,
+}
+
+impl Metrics {
+    // Registra un messaggio gestito da commands::dispatch e il tempo impiegato
+    pub fn observe_dispatch(&self, kind: &'static str, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let mut dispatch = self.dispatch.lock().expect("metriche avvelenate");
+        let latency = dispatch.entry(kind).or_default();
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&le| secs <= le) {
+            latency.buckets[i] += 1;
+        }
+        latency.count += 1;
+        latency.sum += secs;
+    }
+
+    // Istogrammi in formato Prometheus (bucket cumulativi, con +Inf)
+    fn write_dispatch(&self, out: &mut String) {
+        let dispatch = self.dispatch.lock().expect("metriche avvelenate");
+        help(
+            out,
+            "ruggine_messages_total",
+            "counter",
+            "Messaggi gestiti da dispatch, per tipo",
+        );
+        for (kind, l) in dispatch.iter() {
+            let _ = writeln!(out, "ruggine_messages_total{{kind=\"{kind}\"}} {}", l.count);
+        }
+        help(
+            out,
+            "ruggine_dispatch_duration_seconds",
+            "histogram",
+            "Durata della gestione di un messaggio, per tipo",
+        );
+        for (kind, l) in dispatch.iter() {
+            let mut cumulative = 0;
+            for (le, n) in LATENCY_BUCKETS.iter().zip(l.buckets) {
+                cumulative += n;
+                let _ = writeln!(
+                    out,
+                    "ruggine_dispatch_duration_seconds_bucket{{kind=\"{kind}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "ruggine_dispatch_duration_seconds_bucket{{kind=\"{kind}\",le=\"+Inf\"}} {}",
+                l.count
+            );
+            let _ = writeln!(
+                out,
+                "ruggine_dispatch_duration_seconds_sum{{kind=\"{kind}\"}} {}",
+                l.sum
+            );
+            let _ = writeln!(
+                out,
+                "ruggine_dispatch_duration_seconds_count{{kind=\"{kind}\"}} {}",
+                l.count
+            );
+        }
+    }
+}
+
+// Dati condivisi dal gestore di /metrics
+#[derive(Clone)]
+struct Exporter {
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+    // la CPU si calcola tra due letture: il System resta tra una richiesta e l'altra
+    sys: Arc<Mutex<System>>,
+}
+
+pub async fn run(
+    bind_addr: &str,
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+) -> anyhow::Result<()> {
+    let exporter = Exporter {
+        state,
+        conf,
+        sys: Arc::new(Mutex::new(System::new())),
+    };
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(exporter);
+    let listener = TcpListener::bind(bind_addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn metrics(AxState(exp): AxState<Exporter>) -> impl IntoResponse {
+    let mut out = String::new();
+
+    let (cpu, rss) = {
+        let pid = Pid::from(std::process::id() as usize);
+        let mut sys = exp.sys.lock().expect("metriche avvelenate");
+        sys.refresh_process(pid);
+        sys.process(pid)
+            .map(|p| (p.cpu_usage(), p.memory()))
+            .unwrap_or_default()
+    };
+    gauge(
+        &mut out,
+        "ruggine_process_cpu_percent",
+        "Uso della CPU del processo (100 = un core)",
+        cpu,
+    );
+    gauge(
+        &mut out,
+        "ruggine_process_resident_memory_bytes",
+        "Memoria residente del processo",
+        rss,
+    );
+
+    {
+        let st = exp.state.read().await;
+        gauge(
+            &mut out,
+            "ruggine_connected_clients",
+            "Utenti autenticati e connessi",
+            st.clients.len(),
+        );
+        gauge(
+            &mut out,
+            "ruggine_detached_sessions",
+            "Sessioni in attesa di Resume",
+            st.detached.len(),
+        );
+        gauge(
+            &mut out,
+            "ruggine_registered_users",
+            "Account registrati",
+            st.accounts.len(),
+        );
+        gauge(
+            &mut out,
+            "ruggine_groups",
+            "Gruppi esistenti",
+            st.groups.len(),
+        );
+        gauge(
+            &mut out,
+            "ruggine_pending_invites",
+            "Inviti non ancora usati",
+            st.invites.len(),
+        );
+    }
+
+    let conn = &exp.conf.stats;
+    gauge(
+        &mut out,
+        "ruggine_open_connections",
+        "Connessioni aperte, anche non autenticate",
+        conn.open.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "ruggine_connections_total",
+        "Connessioni accettate dall'avvio",
+        conn.accepted.load(Ordering::Relaxed),
+    );
+
+    let m = &exp.conf.metrics;
+    counter(
+        &mut out,
+        "ruggine_received_bytes_total",
+        "Byte dei messaggi ricevuti",
+        m.bytes_in.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "ruggine_sent_bytes_total",
+        "Byte dei messaggi inviati",
+        m.bytes_out.load(Ordering::Relaxed),
+    );
+    m.write_dispatch(&mut out);
+
+    let (oldest, newest, evicted) = exp.conf.queue_stats.snapshot();
+    help(
+        &mut out,
+        "ruggine_dropped_messages_total",
+        "counter",
+        "Messaggi scartati dalle code in uscita",
+    );
+    let _ = writeln!(
+        out,
+        "ruggine_dropped_messages_total{{policy=\"drop_oldest\"}} {oldest}"
+    );
+    let _ = writeln!(
+        out,
+        "ruggine_dropped_messages_total{{policy=\"drop_newest\"}} {newest}"
+    );
+    counter(
+        &mut out,
+        "ruggine_evicted_clients_total",
+        "Client lenti disconnessi",
+        evicted,
+    );
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
+fn help(out: &mut String, name: &str, kind: &str, text: &str) {
+    let _ = writeln!(out, "# HELP {name} {text}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn gauge(out: &mut String, name: &str, text: &str, value: impl std::fmt::Display) {
+    help(out, name, "gauge", text);
+    let _ = writeln!(out, "{name} {value}");
+}
+
+fn counter(out: &mut String, name: &str, text: &str, value: u64) {
+    help(out, name, "counter", text);
+    let _ = writeln!(out, "{name} {value}");
+}
@@ -0,0 +1,342 @@
+/*
+Modulo Outbox: coda limitata dei messaggi in uscita verso ciascun client.
+Sostituisce il canale illimitato: un client che smette di leggere non fa crescere la memoria del server.
+Quando la coda è piena si applica la politica scelta all'avvio: scartare i messaggi più vecchi,
+scartare quelli nuovi oppure disconnettere il client lento.
+*/
+
+use clap::ValueEnum;
+use ruggine_common::{Envelope, ErrorCode, ServerToClient};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+// Cosa fare quando la coda di un client è piena
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum QueuePolicy {
+    DropOldest,
+    DropNewest,
+    Disconnect,
+}
+
+// Contatori globali, condivisi da tutte le code
+#[derive(Debug, Default)]
+pub struct QueueStats {
+    pub dropped_oldest: AtomicU64,
+    pub dropped_newest: AtomicU64,
+    pub evicted: AtomicU64, // client disconnessi perché troppo lenti
+}
+
+impl QueueStats {
+    // Restituisce (scartati vecchi, scartati nuovi, client disconnessi)
+    pub fn snapshot(&self) -> (u64, u64, u64) {
+        (
+            self.dropped_oldest.load(Ordering::Relaxed),
+            self.dropped_newest.load(Ordering::Relaxed),
+            self.evicted.load(Ordering::Relaxed),
+        )
+    }
+}
+
+struct Queue {
+    items: VecDeque<Envelope<ServerToClient>>,
+    closed: bool,               // nessun mittente rimasto oppure client disconnesso
+    evicted: Option<ErrorCode>, // motivo della disconnessione per coda piena
+    dropped: u64,               // messaggi scartati per questo client
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    notify: Notify,
+    evict: Notify, // segnala la disconnessione anche se il task di scrittura è bloccato
+    capacity: usize,
+    policy: QueuePolicy,
+    senders: AtomicUsize,
+    stats: Arc<QueueStats>,
+}
+
+// Errore di invio: il client non riceverà il messaggio
+#[derive(Debug)]
+pub struct SendError;
+
+// Lato di invio: si clona liberamente come il vecchio UnboundedSender.
+// Se `request_id` è impostato viene aggiunto a ogni messaggio inviato (risposte dirette a un comando)
+pub struct Tx {
+    shared: Arc<Shared>,
+    request_id: Option<u64>,
+}
+
+// Lato di ricezione, usato dal task di scrittura della connessione
+pub struct Rx {
+    shared: Arc<Shared>,
+}
+
+pub fn channel(capacity: usize, policy: QueuePolicy, stats: Arc<QueueStats>) -> (Tx, Rx) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            items: VecDeque::new(),
+            closed: false,
+            evicted: None,
+            dropped: 0,
+        }),
+        notify: Notify::new(),
+        evict: Notify::new(),
+        capacity: capacity.max(1),
+        policy,
+        senders: AtomicUsize::new(1),
+        stats,
+    });
+    (
+        Tx {
+            shared: shared.clone(),
+            request_id: None,
+        },
+        Rx { shared },
+    )
+}
+
+impl Tx {
+    // Non blocca mai: se la coda è piena applica la politica configurata
+    pub fn send(&self, msg: ServerToClient) -> Result<(), SendError> {
+        let shared = &self.shared;
+        let mut q = shared.queue.lock().expect("coda in uscita avvelenata");
+        if q.closed {
+            return Err(SendError);
+        }
+        if q.items.len() >= shared.capacity {
+            match shared.policy {
+                QueuePolicy::DropOldest => {
+                    q.items.pop_front();
+                    q.dropped += 1;
+                    shared.stats.dropped_oldest.fetch_add(1, Ordering::Relaxed);
+                }
+                QueuePolicy::DropNewest => {
+                    q.dropped += 1;
+                    shared.stats.dropped_newest.fetch_add(1, Ordering::Relaxed);
+                    return Err(SendError);
+                }
+                QueuePolicy::Disconnect => {
+                    // i messaggi in attesa non verranno più letti: si libera subito la memoria
+                    q.dropped += q.items.len() as u64;
+                    q.items.clear();
+                    q.closed = true;
+                    q.evicted = Some(ErrorCode::QueueFull {
+                        capacity: shared.capacity,
+                    });
+                    shared.stats.evicted.fetch_add(1, Ordering::Relaxed);
+                    drop(q);
+                    shared.notify.notify_one();
+                    shared.evict.notify_one();
+                    return Err(SendError);
+                }
+            }
+        }
+        q.items.push_back(Envelope::new(self.request_id, msg));
+        drop(q);
+        shared.notify.notify_one();
+        Ok(())
+    }
+}
+
+impl Tx {
+    // Vero se i due mittenti portano alla stessa connessione
+    pub fn same_channel(&self, other: &Tx) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    // Mittente che marca i messaggi con l'id della richiesta in corso
+    pub fn for_request(&self, request_id: Option<u64>) -> Tx {
+        let mut tx = self.clone();
+        tx.request_id = request_id;
+        tx
+    }
+
+    // Mittente senza id di richiesta, da conservare nello stato per i messaggi successivi
+    pub fn untagged(&self) -> Tx {
+        self.for_request(None)
+    }
+
+    // Chiude la connessione dal lato del server (es. su richiesta dell'amministratore):
+    // i messaggi in attesa vengono scartati e il task di scrittura invia solo `code`
+    pub fn close(&self, code: ErrorCode) {
+        let mut q = self.shared.queue.lock().expect("coda in uscita avvelenata");
+        if q.closed {
+            return;
+        }
+        q.items.clear();
+        q.closed = true;
+        q.evicted = Some(code);
+        drop(q);
+        self.shared.notify.notify_one();
+        self.shared.evict.notify_one();
+    }
+
+    // Chiude la coda senza scartare nulla (arresto del server): il task di scrittura consegna
+    // i messaggi già accodati e poi termina; gli invii successivi falliscono
+    pub fn finish(&self) {
+        self.shared
+            .queue
+            .lock()
+            .expect("coda in uscita avvelenata")
+            .closed = true;
+        self.shared.notify.notify_one();
+    }
+}
+
+impl Clone for Tx {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+            request_id: self.request_id,
+        }
+    }
+}
+
+impl Drop for Tx {
+    // Come per i canali mpsc: quando sparisce l'ultimo mittente il ricevitore termina
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared
+                .queue
+                .lock()
+                .expect("coda in uscita avvelenata")
+                .closed = true;
+            self.shared.notify.notify_one();
+        }
+    }
+}
+
+// Cosa deve fare il task di scrittura
+pub enum Next {
+    Send(Envelope<ServerToClient>),
+    Evicted(ErrorCode), // errore da scrivere prima di chiudere
+    Closed,
+}
+
+impl Rx {
+    pub async fn recv(&mut self) -> Next {
+        loop {
+            {
+                let mut q = self.shared.queue.lock().expect("coda in uscita avvelenata");
+                if let Some(code) = q.evicted.take() {
+                    return Next::Evicted(code);
+                }
+                if let Some(msg) = q.items.pop_front() {
+                    return Next::Send(msg);
+                }
+                if q.closed {
+                    return Next::Closed;
+                }
+            }
+            // notify_one conserva il permesso se arriva prima dell'attesa: nessun risveglio perso
+            self.shared.notify.notified().await;
+        }
+    }
+
+    // Si completa quando il client viene disconnesso per coda piena
+    pub async fn evicted(&self) {
+        self.shared.evict.notified().await
+    }
+
+    // Messaggi scartati per questo client
+    pub fn dropped(&self) -> u64 {
+        self.shared
+            .queue
+            .lock()
+            .expect("coda in uscita avvelenata")
+            .dropped
+    }
+}
+
+impl Drop for Rx {
+    // Il client non legge più: i mittenti ricevono errore invece di accodare
+    fn drop(&mut self) {
+        let mut q = self.shared.queue.lock().expect("coda in uscita avvelenata");
+        q.closed = true;
+        q.items.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: usize = 4;
+    const SENT: usize = 50;
+
+    fn message(i: usize) -> ServerToClient {
+        ServerToClient::Message {
+            group: "team".into(),
+            from: "alice".into(),
+            text: format!("m{}", i),
+        }
+    }
+
+    fn text(next: Next) -> String {
+        match next {
+            Next::Send(Envelope {
+                body: ServerToClient::Message { text, .. },
+                ..
+            }) => text,
+            _ => panic!("atteso un messaggio"),
+        }
+    }
+
+    fn queued(tx: &Tx) -> usize {
+        tx.shared.queue.lock().unwrap().items.len()
+    }
+
+    // Un client non legge mai, l'altro riceve ogni messaggio appena accodato:
+    // la coda del primo resta entro la capacità e il secondo non perde nulla
+    async fn stalled_client(policy: QueuePolicy) -> (Rx, Arc<QueueStats>) {
+        let stats = Arc::new(QueueStats::default());
+        let (slow_tx, slow_rx) = channel(CAPACITY, policy, stats.clone());
+        let (fast_tx, mut fast_rx) = channel(CAPACITY, policy, stats.clone());
+        for i in 0..SENT {
+            let _ = slow_tx.send(message(i));
+            assert!(queued(&slow_tx) <= CAPACITY);
+            fast_tx.send(message(i)).unwrap();
+            assert_eq!(text(fast_rx.recv().await), format!("m{}", i));
+        }
+        drop(fast_tx);
+        assert!(matches!(fast_rx.recv().await, Next::Closed));
+        (slow_rx, stats)
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_latest_messages() {
+        let (mut slow_rx, stats) = stalled_client(QueuePolicy::DropOldest).await;
+        let lost = (SENT - CAPACITY) as u64;
+        assert_eq!(stats.snapshot(), (lost, 0, 0));
+        assert_eq!(slow_rx.dropped(), lost);
+        for i in SENT - CAPACITY..SENT {
+            assert_eq!(text(slow_rx.recv().await), format!("m{}", i));
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_newest_keeps_first_messages() {
+        let (mut slow_rx, stats) = stalled_client(QueuePolicy::DropNewest).await;
+        let lost = (SENT - CAPACITY) as u64;
+        assert_eq!(stats.snapshot(), (0, lost, 0));
+        assert_eq!(slow_rx.dropped(), lost);
+        for i in 0..CAPACITY {
+            assert_eq!(text(slow_rx.recv().await), format!("m{}", i));
+        }
+    }
+
+    #[tokio::test]
+    async fn disconnect_evicts_slow_client() {
+        let (mut slow_rx, stats) = stalled_client(QueuePolicy::Disconnect).await;
+        assert_eq!(stats.snapshot(), (0, 0, 1));
+        assert_eq!(slow_rx.dropped(), CAPACITY as u64);
+        assert!(matches!(
+            slow_rx.recv().await,
+            Next::Evicted(ErrorCode::QueueFull { capacity: CAPACITY })
+        ));
+    }
+}
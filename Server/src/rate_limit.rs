@@ -0,0 +1,332 @@
+/*
+Modulo RateLimit: protezione dal flood per ogni connessione.
+Ogni connessione ha un token bucket generale e uno per ciascun tipo di messaggio configurato
+(es. GlobalMessage più restrittivo). Chi supera i limiti riceve un errore; chi continua viene disconnesso.
+Anche i frame che non si riescono a interpretare consumano un gettone e contano sempre come violazione.
+Il tempo viene passato dall'esterno (`check_at`), così il limitatore si può provare senza socket né attese.
+*/
+
+use ruggine_common::ClientToServer;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_LIMIT: &str = "10:20";
+pub const DEFAULT_KIND_LIMITS: &[&str] =
+    &["GlobalMessage=1:5", "CreateAccount=0.2:3", "Login=0.2:5"];
+pub const DEFAULT_MAX_STRIKES: u32 = 20;
+// sempre accettato, per permettere di uscire anche a chi ha superato i limiti
+pub const EXEMPT_KIND: &str = "Logout";
+// tipo usato per i frame non interpretabili, che non hanno un campo `kind` valido
+pub const MALFORMED_KIND: &str = "Malformed";
+// finestra entro cui le violazioni si accumulano; dopo una pausa più lunga il conteggio riparte
+pub const STRIKE_WINDOW: Duration = Duration::from_secs(10);
+
+// Limite di un bucket: `rate` gettoni al secondo, al massimo `burst` accumulati
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+// Formato "rate:burst", es. "10:20"
+impl FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rate, burst) = s
+            .split_once(':')
+            .ok_or_else(|| format!("limite '{s}' non valido, atteso <rate>:<burst>"))?;
+        let rate: f64 = rate
+            .trim()
+            .parse()
+            .map_err(|_| format!("rate '{rate}' non valido"))?;
+        let burst: f64 = burst
+            .trim()
+            .parse()
+            .map_err(|_| format!("burst '{burst}' non valido"))?;
+        if rate <= 0.0 || burst < 1.0 {
+            return Err(format!("limite '{s}' non valido: rate > 0 e burst >= 1"));
+        }
+        Ok(Limit { rate, burst })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub general: Limit, // vale per tutti i messaggi della connessione
+    pub per_kind: HashMap<String, Limit>, // tipo di messaggio (campo `kind`) -> limite aggiuntivo
+    pub max_strikes: u32, // violazioni tollerate nella finestra prima di disconnettere
+}
+
+impl RateLimitConfig {
+    // Costruisce la configurazione dagli argomenti: limite generale e voci "Kind=rate:burst"
+    pub fn parse(general: &str, per_kind: &[String], max_strikes: u32) -> Result<Self, String> {
+        let general = general.parse()?;
+        let mut map = HashMap::new();
+        for entry in per_kind {
+            let (kind, limit) = entry.split_once('=').ok_or_else(|| {
+                format!("limite '{entry}' non valido, atteso <Kind>=<rate>:<burst>")
+            })?;
+            map.insert(kind.trim().to_string(), limit.parse()?);
+        }
+        Ok(Self {
+            general,
+            per_kind: map,
+            max_strikes: max_strikes.max(1),
+        })
+    }
+}
+
+pub struct TokenBucket {
+    limit: Limit,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: Limit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.rate).min(self.limit.burst);
+        self.last = now;
+    }
+
+    // Verifica senza consumare, per non sprecare gettoni se un altro bucket rifiuta
+    fn has_token(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+// Esito del controllo su un messaggio in arrivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Limited,    // messaggio scartato, la connessione resta aperta
+    Disconnect, // troppe violazioni ravvicinate
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    general: TokenBucket,
+    per_kind: HashMap<&'static str, TokenBucket>,
+    strikes: u32,
+    last_strike: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, now: Instant) -> Self {
+        Self {
+            general: TokenBucket::new(config.general, now),
+            config,
+            per_kind: HashMap::new(),
+            strikes: 0,
+            last_strike: None,
+        }
+    }
+
+    pub fn check(&mut self, kind: &'static str) -> Verdict {
+        self.check_at(kind, Instant::now())
+    }
+
+    pub fn check_at(&mut self, kind: &'static str, now: Instant) -> Verdict {
+        if kind == EXEMPT_KIND {
+            return Verdict::Allow;
+        }
+        let general_ok = self.general.has_token(now);
+        let kind_ok = match self.config.per_kind.get(kind) {
+            Some(limit) => self
+                .per_kind
+                .entry(kind)
+                .or_insert_with(|| TokenBucket::new(*limit, now))
+                .has_token(now),
+            None => true,
+        };
+        if general_ok && kind_ok {
+            self.general.take();
+            if let Some(b) = self.per_kind.get_mut(kind) {
+                b.take();
+            }
+            // un frame malformato consuma il gettone e conta comunque come violazione
+            if kind != MALFORMED_KIND {
+                return Verdict::Allow;
+            }
+        }
+
+        // violazione: il conteggio riparte se l'ultima è fuori dalla finestra
+        if self
+            .last_strike
+            .is_some_and(|t| now.saturating_duration_since(t) > STRIKE_WINDOW)
+        {
+            self.strikes = 0;
+        }
+        self.strikes += 1;
+        self.last_strike = Some(now);
+        if self.strikes >= self.config.max_strikes {
+            Verdict::Disconnect
+        } else {
+            Verdict::Limited
+        }
+    }
+}
+
+// Nome del tipo di messaggio, uguale al campo `kind` del protocollo
+pub fn kind_of(msg: &ClientToServer) -> &'static str {
+    use ClientToServer::*;
+    match msg {
+        Hello { .. } => "Hello",
+        CreateAccount { .. } => "CreateAccount",
+        Login { .. } => "Login",
+        Resume { .. } => "Resume",
+        CreateGroup { .. } => "CreateGroup",
+        Invite { .. } => "Invite",
+        CreateInviteLink { .. } => "CreateInviteLink",
+        ListInvites { .. } => "ListInvites",
+        RevokeInvite { .. } => "RevokeInvite",
+        JoinGroup { .. } => "JoinGroup",
+        LeaveGroup { .. } => "LeaveGroup",
+        Kick { .. } => "Kick",
+        Promote { .. } => "Promote",
+        Demote { .. } => "Demote",
+        TransferOwnership { .. } => "TransferOwnership",
+        RenameGroup { .. } => "RenameGroup",
+        SendMessage { .. } => "SendMessage",
+        GlobalMessage { .. } => "GlobalMessage",
+        DirectMessage { .. } => "DirectMessage",
+        ListGroups => "ListGroups",
+        ListUsers => "ListUsers",
+        Logout { .. } => "Logout",
+        Ping => "Ping",
+        History { .. } => "History",
+        Moderate { .. } => "Moderate",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(general: &str, per_kind: &[&str], max_strikes: u32) -> (RateLimiter, Instant) {
+        let per_kind: Vec<String> = per_kind.iter().map(|s| s.to_string()).collect();
+        let config = RateLimitConfig::parse(general, &per_kind, max_strikes).unwrap();
+        let now = Instant::now();
+        (RateLimiter::new(config, now), now)
+    }
+
+    #[test]
+    fn burst_then_limited() {
+        let (mut rl, now) = limiter("1:3", &[], 100);
+        for _ in 0..3 {
+            assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        }
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Limited);
+    }
+
+    #[test]
+    fn refill_after_time() {
+        let (mut rl, now) = limiter("2:2", &[], 100);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Limited);
+        // a 2 gettoni al secondo, dopo mezzo secondo ne è tornato uno solo
+        let later = now + Duration::from_millis(500);
+        assert_eq!(rl.check_at("SendMessage", later), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", later), Verdict::Limited);
+        // il bucket non supera mai il burst
+        let much_later = now + Duration::from_secs(60);
+        assert_eq!(rl.check_at("SendMessage", much_later), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", much_later), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", much_later), Verdict::Limited);
+    }
+
+    #[test]
+    fn per_kind_limit_stricter_than_general() {
+        let (mut rl, now) = limiter("10:20", &["GlobalMessage=1:2"], 100);
+        assert_eq!(rl.check_at("GlobalMessage", now), Verdict::Allow);
+        assert_eq!(rl.check_at("GlobalMessage", now), Verdict::Allow);
+        assert_eq!(rl.check_at("GlobalMessage", now), Verdict::Limited);
+        // gli altri tipi usano ancora il limite generale
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        // il messaggio rifiutato non ha consumato gettoni generali: 20 - 2 - 1 rimasti
+        for _ in 0..17 {
+            assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        }
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Limited);
+    }
+
+    #[test]
+    fn limit_from_str() {
+        assert_eq!(
+            "10:20".parse::<Limit>(),
+            Ok(Limit {
+                rate: 10.0,
+                burst: 20.0
+            })
+        );
+        assert_eq!(
+            " 0.5 : 3 ".parse::<Limit>(),
+            Ok(Limit {
+                rate: 0.5,
+                burst: 3.0
+            })
+        );
+        for bad in [
+            "", "10", "10-20", "x:20", "10:y", "0:20", "-1:20", "10:0.5", "10:20:30",
+        ] {
+            assert!(bad.parse::<Limit>().is_err(), "{bad:?} accettato");
+        }
+        assert!(RateLimitConfig::parse("10:20", &["GlobalMessage".to_string()], 5).is_err());
+    }
+
+    #[test]
+    fn repeated_strikes_disconnect() {
+        let (mut rl, now) = limiter("1:1", &[], 3);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Limited);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Limited);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Disconnect);
+    }
+
+    #[test]
+    fn strikes_reset_after_window() {
+        let (mut rl, now) = limiter("1:1", &[], 2);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Limited);
+        let later = now + STRIKE_WINDOW + Duration::from_secs(1);
+        assert_eq!(rl.check_at("SendMessage", later), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", later), Verdict::Limited);
+    }
+
+    #[test]
+    fn malformed_frames_strike_and_consume() {
+        let (mut rl, now) = limiter("10:3", &[], 3);
+        // anche con gettoni disponibili ogni frame malformato è una violazione
+        assert_eq!(rl.check_at(MALFORMED_KIND, now), Verdict::Limited);
+        assert_eq!(rl.check_at(MALFORMED_KIND, now), Verdict::Limited);
+        // e consuma un gettone del bucket generale: ne resta uno solo
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        assert_eq!(rl.check_at(MALFORMED_KIND, now), Verdict::Disconnect);
+    }
+
+    #[test]
+    fn logout_exempt() {
+        let (mut rl, now) = limiter("1:1", &["Logout=1:1"], 2);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Allow);
+        assert_eq!(rl.check_at("SendMessage", now), Verdict::Limited);
+        for _ in 0..10 {
+            assert_eq!(rl.check_at("Logout", now), Verdict::Allow);
+        }
+    }
+}
@@ -1,19 +1,40 @@
 /*
 Modulo Server: si occupa del bind sull'indirizzo specificato e accetta le connessioni dai client.
-Per ogni connessione avvia un task dedicato per la gestione.
+Per ogni connessione avvia un task dedicato per la gestione, eseguendo prima l'handshake TLS se abilitato.
+Lo stesso ciclo serve sia l'indirizzo TCP sia quello WebSocket (--ws-bind) e termina all'arresto del server.
 */
 
+use std::net::IpAddr;
 use std::sync::Arc;
-use tokio::{net::TcpListener, sync::RwLock};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::RwLock,
+};
+use tokio_rustls::TlsAcceptor;
 use tracing::warn;
 
-use crate::connection::handle_conn;
+use crate::connection::{handle_conn, ConnConfig};
 use crate::state::State;
+use crate::websocket::handle_ws;
 
-pub async fn run(bind_addr: &str, state: Arc<RwLock<State>>) -> anyhow::Result<()> {
+// Protocollo delle connessioni accettate su un indirizzo
+#[derive(Debug, Clone, Copy)]
+pub enum Transport {
+    Tcp,       // NDJSON (o MessagePack) direttamente sullo stream
+    WebSocket, // un messaggio per frame WebSocket
+}
+
+pub async fn run(
+    bind_addr: &str,
+    transport: Transport,
+    state: Arc<RwLock<State>>,
+    tls: Option<TlsAcceptor>,
+    conf: Arc<ConnConfig>,
+) -> anyhow::Result<()> {
     // Proviamo a bindare l'indirizzo; se fallisce mostriamo un messaggio più amichevole in italiano
     let listener = match TcpListener::bind(bind_addr).await {
-        Ok(l) => l, 
+        Ok(l) => l,
         Err(e) => {
             use std::io::ErrorKind;
             if e.kind() == ErrorKind::AddrInUse {
@@ -27,12 +48,50 @@ pub async fn run(bind_addr: &str, state: Arc<RwLock<State>>) -> anyhow::Result<(
     };
 
     loop {
-        let (socket, _addr) = listener.accept().await?;
+        // su richiesta di arresto si smette di accettare; le connessioni aperte chiudono da sole
+        let (socket, addr) = tokio::select! {
+            res = listener.accept() => res?,
+            _ = conf.shutdown.requested() => return Ok(()),
+        };
+        // gli indirizzi banditi dagli operatori vengono chiusi subito, senza handshake
+        if state.read().await.bans.ip(addr.ip()).is_some() {
+            warn!("Connessione rifiutata da {}: indirizzo bandito", addr.ip());
+            continue;
+        }
         let st = state.clone();
+        let tls = tls.clone();
+        let conf = conf.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_conn(socket, st).await {
+            // l'handshake TLS avviene nel task della connessione per non bloccare l'accept
+            let res = match tls {
+                Some(acceptor) => match acceptor.accept(socket).await {
+                    Ok(stream) => serve_stream(transport, stream, addr.ip(), st, conf).await,
+                    Err(e) => {
+                        warn!("Handshake TLS fallito con {}: {}", addr, e);
+                        return;
+                    }
+                },
+                None => serve_stream(transport, socket, addr.ip(), st, conf).await,
+            };
+            if let Err(e) = res {
                 warn!("Connessione terminata con errore: {:?}", e);
             }
         });
     }
 }
+
+async fn serve_stream<S>(
+    transport: Transport,
+    stream: S,
+    peer: IpAddr,
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    match transport {
+        Transport::Tcp => handle_conn(stream, peer, state, conf).await,
+        Transport::WebSocket => handle_ws(stream, peer, state, conf).await,
+    }
+}
@@ -0,0 +1,98 @@
+/*
+Modulo Shutdown: arresto ordinato del server (CTRL+C o comando `shutdown` della console).
+`Shutdown::trigger` sveglia i cicli di accept, che smettono di accettare connessioni, e ogni connessione,
+che accoda `ServerShutdown` dopo i messaggi in attesa e chiude quando il task di scrittura ha svuotato
+la coda. `finish` aspetta la chiusura delle connessioni entro --shutdown-timeout e salva lo stato.
+*/
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, RwLock};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::connection::ConnConfig;
+use crate::state::State;
+
+pub const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 5;
+
+// Intervallo con cui si controlla se tutte le connessioni sono state chiuse
+const DRAIN_POLL: Duration = Duration::from_millis(20);
+
+// Avviso inviato ai client: motivo e, se previsto un riavvio, tra quanti secondi riconnettersi
+#[derive(Debug, Clone)]
+pub struct Notice {
+    pub reason: String,
+    pub reconnect_after: Option<u64>,
+}
+
+// Segnale di arresto condiviso: None finché nessuno lo ha richiesto
+#[derive(Debug)]
+pub struct Shutdown {
+    tx: watch::Sender<Option<Notice>>,
+    pub timeout: Duration, // tempo concesso alle connessioni per consegnare i messaggi in coda
+    reconnect_after: Option<u64>, // --reconnect-after, uguale per ogni arresto
+}
+
+impl Shutdown {
+    pub fn new(timeout: Duration, reconnect_after: Option<u64>) -> Self {
+        Self {
+            tx: watch::channel(None).0,
+            timeout,
+            reconnect_after,
+        }
+    }
+
+    // Richiede l'arresto; false se era già stato richiesto (vale il primo motivo)
+    pub fn trigger(&self, reason: String) -> bool {
+        self.tx.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            info!("Arresto del server: {}", reason);
+            *current = Some(Notice {
+                reason,
+                reconnect_after: self.reconnect_after,
+            });
+            true
+        })
+    }
+
+    // Si completa quando l'arresto viene richiesto, con l'avviso per i client
+    pub async fn requested(&self) -> Notice {
+        let mut rx = self.tx.subscribe();
+        let notice = rx
+            .wait_for(Option::is_some)
+            .await
+            .expect("il segnale di arresto vive quanto la configurazione");
+        notice.clone().expect("wait_for garantisce un avviso")
+    }
+}
+
+// Attende che le connessioni abbiano consegnato l'avviso (al massimo `timeout`) e salva lo stato
+pub async fn finish(state: &Arc<RwLock<State>>, conf: &ConnConfig) {
+    let timeout = conf.shutdown.timeout;
+    let deadline = Instant::now() + timeout;
+    while conf.stats.open.load(Ordering::Relaxed) > 0 && Instant::now() < deadline {
+        tokio::time::sleep(DRAIN_POLL).await;
+    }
+    let open = conf.stats.open.load(Ordering::Relaxed);
+    if open > 0 {
+        warn!(
+            "{} connessioni non chiuse entro {} s",
+            open,
+            timeout.as_secs()
+        );
+    }
+    // il journal è già aggiornato: lo snapshot lo compatta per il prossimo avvio
+    let (stored, flushed) = {
+        let mut st = state.write().await;
+        st.save_snapshot();
+        (st.storage.flush(), st.history.flush())
+    };
+    // attende le scritture di stato e cronologia ancora in coda
+    let _ = stored.await;
+    let _ = flushed.await;
+    info!("Server arrestato");
+}
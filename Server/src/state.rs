@@ -1,19 +1,67 @@
 /*
 Modulo State: mantiene lo stato globale del server, inclusi utenti, gruppi, inviti e canali di comunicazione.
 Fornisce strutture dati condivise tra i vari task.
+Account, gruppi, membri e inviti sono persistenti: ogni loro modifica passa da `commit`,
+che la applica in memoria e la scrive nello storage.
 */
 
-use ruggine_common::ServerToClient;
+use crate::bans::BanList;
+use crate::catalog::t;
+use crate::config::Limits;
+use crate::history::HistoryStore;
+use crate::invites::{Invite, InviteEnd};
+use crate::storage::{Mutation, Snapshot, Storage};
+use ruggine_common::{ErrorCode, GroupAction, ServerToClient};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use tokio::sync::mpsc;
+use std::net::IpAddr;
+use std::time::Instant;
+use tracing::warn;
 use uuid::Uuid;
 
-pub type Tx = mpsc::UnboundedSender<ServerToClient>;
-pub type Rx = mpsc::UnboundedReceiver<ServerToClient>;
+pub use crate::outbox::{Rx, Tx};
 
 #[derive(Default)]
 pub struct Group {
-    pub members: HashSet<Uuid>, // ID dei client associati al gruppo
+    pub members: HashSet<String>, // nickname canonici degli account membri del gruppo
+    pub owner: String,            // creatore del gruppo o a chi è stata ceduta la proprietà
+    pub admins: HashSet<String>,  // membri promossi ad amministratori dal proprietario
+}
+
+// Ruolo di un membro all'interno di un gruppo, in ordine crescente di permessi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Member,
+    Admin,
+    Owner,
+}
+
+impl Group {
+    pub fn role_of(&self, nick: &str) -> Option<Role> {
+        if !self.members.contains(nick) {
+            None
+        } else if self.owner == nick {
+            Some(Role::Owner)
+        } else if self.admins.contains(nick) {
+            Some(Role::Admin)
+        } else {
+            Some(Role::Member)
+        }
+    }
+
+    // Chi eredita la proprietà quando il proprietario esce: il primo amministratore,
+    // altrimenti il primo membro (in ordine alfabetico, così la scelta è deterministica)
+    fn successor(&self) -> Option<String> {
+        let admins = self.admins.iter().filter(|n| self.members.contains(*n));
+        admins.min().or_else(|| self.members.iter().min()).cloned()
+    }
+}
+
+// Account registrato: nickname canonico e hash salato della password (formato PHC)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub nick: String,
+    pub password_hash: String,
 }
 
 //users_by_nick e nicks_by_id vengono utilizzate entrambe per avere efficienza nelle ricerche
@@ -21,18 +69,469 @@ pub struct Group {
 #[derive(Default)]
 pub struct State {
     pub users_by_nick: HashMap<String, Uuid>,
-    // Mappa nickname -> UUID utente 
-    //(associa ogni nickname registrato all'ID univoco del client)
+    // Mappa nickname -> UUID utente
+    //(associa ogni nickname connesso all'ID univoco della sessione)
     pub nicks_by_id: HashMap<Uuid, String>,
-    // Mappa UUID utente -> nickname 
+    // Mappa UUID utente -> nickname
     //(associa ogni ID univoco al nickname corrispondente)
+    pub accounts: HashMap<String, Account>,
+    // Mappa nickname in minuscolo -> account
+    //(tutti gli account creati, anche se non connessi)
     pub groups: HashMap<String, Group>,
-    // Mappa nome gruppo -> struttura Group 
+    // Mappa nome gruppo -> struttura Group
     //(contiene tutti i gruppi attivi e i loro membri)
-    pub invites: HashMap<String, (String, String)>,
-    // Mappa codice invito -> (nome gruppo, nickname destinatario) 
+    pub invites: HashMap<String, Invite>,
+    // Mappa codice invito -> invito (gruppo, destinatario o link aperto, scadenza, usi)
     //(contiene tutti i codici invito attivi)
+    pub retired: HashMap<String, (InviteEnd, i64)>,
+    // Mappa codice -> motivo e istante (ms) in cui l'invito ha smesso di valere
+    //(solo in memoria, per spiegare il rifiuto di un codice scaduto, revocato o esaurito)
     pub clients: HashMap<Uuid, Tx>,
-    // Mappa UUID utente -> canale di invio (Tx) 
+    // Mappa UUID utente -> canale di invio (Tx)
     //(associa ogni client connesso al suo canale di comunicazione)
+    pub sessions: HashMap<Uuid, String>,
+    // Mappa UUID utente -> token di ripresa della sessione
+    //(inviato al client in Registered e richiesto da Resume)
+    pub detached: HashMap<Uuid, Instant>,
+    // Sessioni la cui connessione è caduta, con l'istante della disconnessione
+    //(restano valide per il periodo di grazia, senza canale in clients)
+    pub history: HistoryStore,
+    // Cronologia dei messaggi di gruppo, salvata su disco
+    pub storage: Storage,
+    // Journal + snapshot dello stato persistente
+    pub peers: HashMap<Uuid, IpAddr>,
+    // Mappa UUID utente -> indirizzo IP della connessione
+    //(usata dai ban per indirizzo)
+    pub operators: HashSet<String>,
+    // Nickname in minuscolo degli operatori del server, decisi all'avvio
+    pub bans: BanList,
+    // Nickname e indirizzi banditi, salvati su disco
+    pub muted: HashMap<String, Instant>,
+    // Mappa nickname canonico -> fine del silenzio imposto da un operatore
+    pub limits: Limits,
+    // Limiti su nomi e codici invito (--max-name-len, --reserved-name, --invite-*), ricaricabili
+}
+
+impl State {
+    // Ricostruisce lo stato a partire dallo snapshot e dalle modifiche del journal
+    pub fn restore(
+        storage: Storage,
+        history: HistoryStore,
+        snapshot: Snapshot,
+        journal: Vec<Mutation>,
+    ) -> Self {
+        let mut st = State {
+            storage,
+            history,
+            ..State::default()
+        };
+        for account in snapshot.accounts {
+            st.accounts
+                .insert(account.nick.to_ascii_lowercase(), account);
+        }
+        for (name, members) in snapshot.groups {
+            let owner = snapshot.owners.get(&name).cloned().unwrap_or_default();
+            let admins = snapshot.admins.get(&name).cloned().unwrap_or_default();
+            st.groups.insert(
+                name,
+                Group {
+                    members: members.into_iter().collect(),
+                    owner,
+                    admins: admins.into_iter().collect(),
+                },
+            );
+        }
+        st.invites = snapshot.invites;
+        for m in &journal {
+            st.apply(m);
+        }
+        // gruppi salvati prima dell'introduzione dei ruoli: il proprietario viene scelto come in successione
+        for g in st.groups.values_mut() {
+            if !g.members.contains(&g.owner) {
+                g.owner = g.successor().unwrap_or_default();
+            }
+        }
+        st
+    }
+
+    // Applica una modifica allo stato in memoria (usata sia a runtime che durante il replay)
+    fn apply(&mut self, m: &Mutation) {
+        match m {
+            Mutation::AccountCreated {
+                nick,
+                password_hash,
+            } => {
+                self.accounts.insert(
+                    nick.to_ascii_lowercase(),
+                    Account {
+                        nick: nick.clone(),
+                        password_hash: password_hash.clone(),
+                    },
+                );
+            }
+            Mutation::GroupCreated { group } => {
+                self.groups.entry(group.clone()).or_default();
+            }
+            Mutation::MemberAdded { group, nick } => {
+                self.groups
+                    .entry(group.clone())
+                    .or_default()
+                    .members
+                    .insert(nick.clone());
+            }
+            Mutation::MemberRemoved { group, nick } => {
+                if let Some(g) = self.groups.get_mut(group) {
+                    g.members.remove(nick);
+                    g.admins.remove(nick);
+                }
+            }
+            Mutation::OwnerChanged { group, nick } => {
+                if let Some(g) = self.groups.get_mut(group) {
+                    g.admins.remove(nick);
+                    g.owner = nick.clone();
+                }
+            }
+            Mutation::AdminAdded { group, nick } => {
+                if let Some(g) = self.groups.get_mut(group) {
+                    g.admins.insert(nick.clone());
+                }
+            }
+            Mutation::AdminRemoved { group, nick } => {
+                if let Some(g) = self.groups.get_mut(group) {
+                    g.admins.remove(nick);
+                }
+            }
+            Mutation::GroupDeleted { group } => {
+                self.groups.remove(group);
+            }
+            Mutation::GroupRenamed { group, new_name } => {
+                if let Some(g) = self.groups.remove(group) {
+                    self.groups.insert(new_name.clone(), g);
+                }
+                for invite in self.invites.values_mut() {
+                    if invite.group == *group {
+                        invite.group = new_name.clone();
+                    }
+                }
+            }
+            Mutation::InviteCreated { code, invite } => {
+                self.invites.insert(code.clone(), invite.clone());
+            }
+            Mutation::InviteUsed { code } => {
+                if let Some(invite) = self.invites.get_mut(code) {
+                    invite.uses += 1;
+                }
+            }
+            Mutation::InviteRemoved { code } => {
+                self.invites.remove(code);
+            }
+        }
+    }
+
+    // Applica la modifica e ne accoda la scrittura; un errore di scrittura non blocca la chat
+    pub fn commit(&mut self, m: Mutation) {
+        self.apply(&m);
+        match self.storage.append(&m) {
+            Ok(true) => self.save_snapshot(),
+            Ok(false) => {}
+            Err(e) => warn!("Impossibile serializzare la modifica: {}", e),
+        }
+    }
+
+    pub fn save_snapshot(&mut self) {
+        // il numero dell'ultima modifica lo imposta lo storage
+        let snapshot = Snapshot {
+            seq: 0,
+            accounts: self.accounts.values().cloned().collect(),
+            groups: self
+                .groups
+                .iter()
+                .map(|(name, g)| (name.clone(), g.members.iter().cloned().collect()))
+                .collect(),
+            invites: self.invites.clone(),
+            owners: self
+                .groups
+                .iter()
+                .map(|(name, g)| (name.clone(), g.owner.clone()))
+                .collect(),
+            admins: self
+                .groups
+                .iter()
+                .filter(|(_, g)| !g.admins.is_empty())
+                .map(|(name, g)| (name.clone(), g.admins.iter().cloned().collect()))
+                .collect(),
+        };
+        self.storage.write_snapshot(snapshot);
+    }
+
+    // Nickname della sessione con questo id
+    pub fn nick_of(&self, id: &Uuid) -> Option<String> {
+        self.nicks_by_id.get(id).cloned()
+    }
+
+    // Nickname canonico dell'account (ricerca case-insensitive)
+    pub fn account_nick(&self, nick: &str) -> Option<String> {
+        self.accounts
+            .get(&nick.to_ascii_lowercase())
+            .map(|a| a.nick.clone())
+    }
+
+    // Canale di invio dell'utente, se al momento è connesso
+    pub fn tx_of_nick(&self, nick: &str) -> Option<&Tx> {
+        self.users_by_nick
+            .get(nick)
+            .and_then(|id| self.clients.get(id))
+    }
+
+    // Chiude definitivamente una sessione. Gruppi e inviti appartengono all'account e restano:
+    // si esce da un gruppo solo con LeaveGroup o con un'espulsione
+    pub fn end_session(&mut self, id: &Uuid) {
+        self.clients.remove(id);
+        self.sessions.remove(id);
+        self.detached.remove(id);
+        self.peers.remove(id);
+        if let Some(nick) = self.nicks_by_id.remove(id) {
+            self.users_by_nick.remove(&nick);
+        }
+    }
+
+    // Chiude la sessione dal lato del server: la connessione riceve `code` e viene chiusa,
+    // senza periodo di grazia. Gruppi e inviti restano, come per una disconnessione
+    pub fn disconnect(&mut self, id: &Uuid, code: ErrorCode) {
+        if let Some(tx) = self.clients.get(id) {
+            tx.close(code);
+        }
+        self.end_session(id);
+    }
+
+    pub fn is_operator(&self, nick: &str) -> bool {
+        self.operators.contains(&nick.to_ascii_lowercase())
+    }
+
+    // Secondi di silenzio ancora da scontare (arrotondati per eccesso), se l'utente è silenziato
+    pub fn muted_for(&self, nick: &str) -> Option<u64> {
+        let left = self
+            .muted
+            .get(nick)?
+            .checked_duration_since(Instant::now())?;
+        Some(left.as_secs() + u64::from(left.subsec_nanos() > 0))
+    }
+
+    // Rimuove un invito ancora valido ricordando il motivo, per i tentativi successivi di usarlo
+    pub fn retire_invite(&mut self, code: &str, end: InviteEnd) {
+        self.commit(Mutation::InviteRemoved {
+            code: code.to_string(),
+        });
+        self.retired
+            .insert(code.to_string(), (end, crate::invites::now_ms()));
+    }
+
+    // Errore per un codice che non corrisponde a un invito valido
+    pub fn invite_error(&self, code: &str) -> ErrorCode {
+        match self.retired.get(code) {
+            Some((end, _)) => end.error(),
+            None => ErrorCode::InvalidInvite,
+        }
+    }
+
+    // Rimuove un membro dal gruppo e cancella il gruppo (e la sua cronologia) se resta vuoto.
+    // Se esce il proprietario, la proprietà passa a un amministratore o, in mancanza, a un membro
+    pub fn remove_member(&mut self, group: &str, nick: &str) {
+        self.commit(Mutation::MemberRemoved {
+            group: group.to_string(),
+            nick: nick.to_string(),
+        });
+        let (empty, successor) = match self.groups.get(group) {
+            Some(g) if g.owner == nick => (g.members.is_empty(), g.successor()),
+            Some(g) => (g.members.is_empty(), None),
+            None => return,
+        };
+        if empty {
+            self.delete_group(group);
+        } else if let Some(new_owner) = successor {
+            self.commit(Mutation::OwnerChanged {
+                group: group.to_string(),
+                nick: new_owner.clone(),
+            });
+            self.notify_group(group, &t!("group.new_owner", new_owner, group));
+        }
+    }
+
+    // Elimina il gruppo con la sua cronologia e gli inviti non ancora usati
+    pub fn delete_group(&mut self, group: &str) {
+        self.commit(Mutation::GroupDeleted {
+            group: group.to_string(),
+        });
+        self.history.purge(group);
+        let to_remove: Vec<String> = self
+            .invites
+            .iter()
+            .filter(|(_, invite)| invite.group == group)
+            .map(|(code, _)| code.clone())
+            .collect();
+        for code in to_remove {
+            self.commit(Mutation::InviteRemoved { code });
+        }
+    }
+
+    // Cambia nome al gruppo; la cronologia su disco viene spostata nel file del nuovo nome
+    pub fn rename_group(&mut self, group: &str, new_name: &str) {
+        self.commit(Mutation::GroupRenamed {
+            group: group.to_string(),
+            new_name: new_name.to_string(),
+        });
+        self.history.rename(group, new_name);
+    }
+
+    // Ruoli di chi esegue un comando di gestione e dell'utente su cui agisce.
+    // Restituisce (ruolo del chiamante, nickname canonico del destinatario, ruolo del destinatario)
+    pub fn roles_in(
+        &self,
+        group: &str,
+        caller: &str,
+        target: &str,
+    ) -> Result<(Role, String, Role), ErrorCode> {
+        let g = self
+            .groups
+            .get(group)
+            .ok_or_else(|| ErrorCode::GroupNotFound {
+                group: group.to_string(),
+            })?;
+        let caller_role = g.role_of(caller).ok_or_else(|| ErrorCode::NotMember {
+            group: group.to_string(),
+        })?;
+        let target_nick = self
+            .account_nick(target)
+            .unwrap_or_else(|| target.to_string());
+        let target_role = g
+            .role_of(&target_nick)
+            .ok_or_else(|| ErrorCode::UserNotMember {
+                nick: target.to_string(),
+                group: group.to_string(),
+            })?;
+        Ok((caller_role, target_nick, target_role))
+    }
+
+    // Verifica che il chiamante sia proprietario o amministratore del gruppo, come richiesto per gli inviti
+    pub fn require_manager(
+        &self,
+        group: &str,
+        caller: &str,
+        action: GroupAction,
+    ) -> Result<(), ErrorCode> {
+        let g = self
+            .groups
+            .get(group)
+            .ok_or_else(|| ErrorCode::GroupNotFound {
+                group: group.to_string(),
+            })?;
+        match g.role_of(caller) {
+            None => Err(ErrorCode::NotMember {
+                group: group.to_string(),
+            }),
+            Some(Role::Member) => Err(ErrorCode::NotAllowed {
+                group: group.to_string(),
+                action,
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+
+    // Avvisa con un messaggio del server tutti i membri connessi del gruppo
+    pub fn notify_group(&self, group: &str, text: &str) {
+        if let Some(g) = self.groups.get(group) {
+            for member in &g.members {
+                if let Some(txm) = self.tx_of_nick(member) {
+                    let _ = txm.send(ServerToClient::MessageServer {
+                        text: text.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(owner: &str, admins: &[&str], members: &[&str]) -> Group {
+        Group {
+            members: members.iter().map(|n| n.to_string()).collect(),
+            owner: owner.to_string(),
+            admins: admins.iter().map(|n| n.to_string()).collect(),
+        }
+    }
+
+    fn state_with(g: Group) -> State {
+        let dir = std::env::temp_dir().join(format!("ruggine-state-{}", Uuid::new_v4()));
+        let mut st = State {
+            history: HistoryStore::new(dir, 10),
+            ..State::default()
+        };
+        st.groups.insert("team".into(), g);
+        st
+    }
+
+    #[test]
+    fn roles_follow_owner_and_admins() {
+        let g = group("olga", &["ada"], &["olga", "ada", "max"]);
+        assert_eq!(g.role_of("olga"), Some(Role::Owner));
+        assert_eq!(g.role_of("ada"), Some(Role::Admin));
+        assert_eq!(g.role_of("max"), Some(Role::Member));
+        assert_eq!(g.role_of("zoe"), None);
+        assert!(Role::Member < Role::Admin && Role::Admin < Role::Owner);
+    }
+
+    #[test]
+    fn manager_commands_need_an_admin() {
+        let st = state_with(group("olga", &["ada"], &["olga", "ada", "max"]));
+        let action = GroupAction::Rename;
+        assert!(st.require_manager("team", "olga", action).is_ok());
+        assert!(st.require_manager("team", "ada", action).is_ok());
+        assert!(matches!(
+            st.require_manager("team", "max", action),
+            Err(ErrorCode::NotAllowed { .. })
+        ));
+        assert!(matches!(
+            st.require_manager("team", "zoe", action),
+            Err(ErrorCode::NotMember { .. })
+        ));
+        assert!(matches!(
+            st.require_manager("altro", "olga", action),
+            Err(ErrorCode::GroupNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn owner_leaving_passes_the_group_to_the_first_admin() {
+        let mut st = state_with(group(
+            "olga",
+            &["bea", "ada"],
+            &["olga", "ada", "bea", "max"],
+        ));
+        st.remove_member("team", "olga");
+        let g = &st.groups["team"];
+        assert_eq!(g.owner, "ada");
+        assert!(!g.admins.contains("ada") && g.admins.contains("bea"));
+        assert!(!g.members.contains("olga"));
+    }
+
+    #[test]
+    fn without_admins_the_first_member_inherits() {
+        let mut st = state_with(group("olga", &[], &["olga", "zoe", "max"]));
+        st.remove_member("team", "olga");
+        assert_eq!(st.groups["team"].owner, "max");
+
+        // un membro semplice che esce non cambia il proprietario
+        st.remove_member("team", "zoe");
+        assert_eq!(st.groups["team"].owner, "max");
+    }
+
+    #[test]
+    fn last_member_leaving_deletes_the_group() {
+        let mut st = state_with(group("olga", &[], &["olga"]));
+        st.remove_member("team", "olga");
+        assert!(!st.groups.contains_key("team"));
+    }
 }
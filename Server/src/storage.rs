@@ -0,0 +1,376 @@
+/*
+Modulo Storage: rende persistente lo stato del server (account, gruppi, membri, inviti).
+Ogni modifica viene scritta in un journal NDJSON in append; periodicamente lo stato completo
+viene salvato in uno snapshot e il journal viene svuotato. All'avvio si carica lo snapshot e si
+riapplicano le modifiche del journal più recenti dello snapshot (ogni modifica ha un numero progressivo).
+Come per la cronologia, i file sono scritti da un thread dedicato: chi modifica lo stato non
+aspetta il disco tenendo il lock.
+*/
+
+use crate::invites::Invite;
+use crate::state::Account;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+pub const DEFAULT_DATA_DIR: &str = "data";
+// numero di modifiche nel journal dopo cui viene scritto un nuovo snapshot
+pub const DEFAULT_SNAPSHOT_EVERY: usize = 500;
+
+const JOURNAL_FILE: &str = "journal.ndjson";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+// Singola modifica dello stato persistente, così come viene scritta nel journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op")]
+pub enum Mutation {
+    AccountCreated {
+        nick: String,
+        password_hash: String,
+    },
+    GroupCreated {
+        group: String,
+    },
+    MemberAdded {
+        group: String,
+        nick: String,
+    },
+    MemberRemoved {
+        group: String,
+        nick: String,
+    },
+    GroupDeleted {
+        group: String,
+    },
+    // ruoli e inviti in attesa seguono il gruppo con il nuovo nome
+    GroupRenamed {
+        group: String,
+        new_name: String,
+    },
+    OwnerChanged {
+        group: String,
+        nick: String,
+    },
+    AdminAdded {
+        group: String,
+        nick: String,
+    },
+    AdminRemoved {
+        group: String,
+        nick: String,
+    },
+    InviteCreated {
+        code: String,
+        #[serde(flatten)]
+        invite: Invite,
+    },
+    // un ingresso con un link aperto
+    InviteUsed {
+        code: String,
+    },
+    InviteRemoved {
+        code: String,
+    },
+}
+
+// Fotografia completa dello stato persistente
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub seq: u64,               // numero dell'ultima modifica contenuta nello snapshot
+    pub accounts: Vec<Account>, // account con hash della password
+    pub groups: HashMap<String, Vec<String>>, // nome gruppo -> nickname dei membri
+    pub invites: HashMap<String, Invite>, // codice -> invito
+    #[serde(default)]
+    pub owners: HashMap<String, String>, // nome gruppo -> nickname del proprietario
+    #[serde(default)]
+    pub admins: HashMap<String, Vec<String>>, // nome gruppo -> nickname degli amministratori
+}
+
+// Riga del journal: la modifica con il suo numero progressivo
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    seq: u64,
+    #[serde(flatten)]
+    mutation: Mutation,
+}
+
+// Operazione per il thread di scrittura
+enum DiskOp {
+    Append(String),
+    // scrive lo snapshot e poi svuota il journal
+    Snapshot(Snapshot),
+    // risponde quando tutte le operazioni precedenti sono state eseguite
+    Flush(oneshot::Sender<()>),
+}
+
+// Se `disk` è None lo storage è disattivato e lo stato resta solo in memoria
+#[derive(Default)]
+pub struct Storage {
+    disk: Option<mpsc::Sender<DiskOp>>, // canale verso il thread che scrive i file
+    seq: u64,                           // numero dell'ultima modifica
+    pending: usize,                     // modifiche scritte nel journal dall'ultimo snapshot
+    snapshot_every: usize,
+}
+
+impl Storage {
+    // Apre (o crea) la cartella dati e restituisce lo snapshot e le modifiche da riapplicare.
+    // Le modifiche già contenute nello snapshot (numero <= `Snapshot::seq`) vengono saltate:
+    // restano nel journal se il server si è fermato tra lo snapshot e lo svuotamento del journal
+    pub fn open(
+        dir: impl Into<PathBuf>,
+        snapshot_every: usize,
+    ) -> std::io::Result<(Self, Snapshot, Vec<Mutation>)> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let snapshot: Snapshot = match fs::read_to_string(dir.join(SNAPSHOT_FILE)) {
+            Ok(s) => serde_json::from_str(&s)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Snapshot::default(),
+            Err(e) => return Err(e),
+        };
+        let entries: Vec<JournalEntry> = read_journal(&dir.join(JOURNAL_FILE))?
+            .into_iter()
+            .filter(|e| e.seq > snapshot.seq)
+            .collect();
+        let seq = entries.last().map_or(snapshot.seq, |e| e.seq);
+
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))?;
+        let (disk, ops) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("state-writer".into())
+            .spawn(move || write_loop(&dir, journal, ops))?;
+        let storage = Self {
+            disk: Some(disk),
+            seq,
+            pending: entries.len(),
+            snapshot_every: snapshot_every.max(1),
+        };
+        let mutations = entries.into_iter().map(|e| e.mutation).collect();
+        Ok((storage, snapshot, mutations))
+    }
+
+    fn write(&self, op: DiskOp) {
+        if let Some(disk) = &self.disk {
+            if disk.send(op).is_err() {
+                warn!("Thread di scrittura dello stato terminato");
+            }
+        }
+    }
+
+    // Accoda una modifica per il journal; restituisce true se è ora di fare uno snapshot
+    pub fn append(&mut self, m: &Mutation) -> std::io::Result<bool> {
+        if self.disk.is_none() {
+            return Ok(false);
+        }
+        self.seq += 1;
+        let entry = JournalEntry {
+            seq: self.seq,
+            mutation: m.clone(),
+        };
+        self.write(DiskOp::Append(serde_json::to_string(&entry)? + "\n"));
+        self.pending += 1;
+        Ok(self.pending >= self.snapshot_every)
+    }
+
+    // Accoda lo snapshot, che contiene tutte le modifiche fin qui; il journal verrà svuotato
+    pub fn write_snapshot(&mut self, mut snapshot: Snapshot) {
+        snapshot.seq = self.seq;
+        self.write(DiskOp::Snapshot(snapshot));
+        self.pending = 0;
+    }
+
+    // Si completa quando le scritture accodate finora sono su disco (usato all'arresto)
+    pub fn flush(&self) -> oneshot::Receiver<()> {
+        let (done, rx) = oneshot::channel();
+        match &self.disk {
+            Some(_) => self.write(DiskOp::Flush(done)),
+            None => {
+                let _ = done.send(());
+            }
+        }
+        rx
+    }
+}
+
+// Esegue le scritture nell'ordine di arrivo, fuori dal lock sullo stato e dal runtime asincrono
+fn write_loop(dir: &Path, mut journal: File, ops: mpsc::Receiver<DiskOp>) {
+    for op in ops {
+        match op {
+            DiskOp::Append(line) => {
+                if let Err(e) = journal.write_all(line.as_bytes()) {
+                    warn!("Impossibile scrivere nel journal: {}", e);
+                }
+            }
+            DiskOp::Snapshot(snapshot) => match write_snapshot(dir, &snapshot) {
+                // lo snapshot contiene già tutte le modifiche: si riparte con un journal vuoto
+                Ok(()) => match File::create(dir.join(JOURNAL_FILE)) {
+                    Ok(file) => journal = file,
+                    Err(e) => warn!("Impossibile svuotare il journal: {}", e),
+                },
+                Err(e) => warn!("Impossibile salvare lo snapshot dello stato: {}", e),
+            },
+            DiskOp::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+// Salva lo snapshot in modo atomico (file temporaneo + rename)
+fn write_snapshot(dir: &Path, snapshot: &Snapshot) -> std::io::Result<()> {
+    let tmp = dir.join(format!("{SNAPSHOT_FILE}.tmp"));
+    let mut file = File::create(&tmp)?;
+    file.write_all(serde_json::to_string_pretty(snapshot)?.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, dir.join(SNAPSHOT_FILE))
+}
+
+fn read_journal(path: &Path) -> std::io::Result<Vec<JournalEntry>> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut out = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<JournalEntry>(&line) {
+            Ok(m) => out.push(m),
+            // una riga troncata (es. crash durante la scrittura) viene ignorata
+            Err(e) => warn!("Riga {} del journal non valida: {}", n + 1, e),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("ruggine-storage-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn created(group: &str) -> Mutation {
+        Mutation::GroupCreated {
+            group: group.into(),
+        }
+    }
+
+    fn groups(mutations: &[Mutation]) -> Vec<String> {
+        mutations
+            .iter()
+            .map(|m| match m {
+                Mutation::GroupCreated { group } => group.clone(),
+                other => panic!("modifica inattesa: {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn snapshot_and_journal_round_trip() {
+        let dir = temp_dir();
+        let (mut storage, snapshot, journal) = Storage::open(&dir, 100).unwrap();
+        assert!(snapshot.accounts.is_empty() && journal.is_empty());
+
+        storage.append(&created("a")).unwrap();
+        let mut snapshot = Snapshot::default();
+        snapshot.groups.insert("a".into(), vec!["anna".into()]);
+        storage.write_snapshot(snapshot);
+        storage.append(&created("b")).unwrap();
+        storage.append(&created("c")).unwrap();
+        storage.flush().blocking_recv().unwrap();
+        drop(storage);
+
+        let (mut storage, snapshot, journal) = Storage::open(&dir, 100).unwrap();
+        assert_eq!(snapshot.seq, 1);
+        assert_eq!(snapshot.groups["a"], vec!["anna".to_string()]);
+        assert_eq!(groups(&journal), ["b", "c"]);
+        // la numerazione riprende dall'ultima modifica del journal
+        storage.append(&created("d")).unwrap();
+        storage.flush().blocking_recv().unwrap();
+        let (_, _, journal) = Storage::open(&dir, 100).unwrap();
+        assert_eq!(groups(&journal), ["b", "c", "d"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn truncated_last_line_is_skipped() {
+        let dir = temp_dir();
+        let (mut storage, _, _) = Storage::open(&dir, 100).unwrap();
+        storage.append(&created("a")).unwrap();
+        storage.append(&created("b")).unwrap();
+        storage.flush().blocking_recv().unwrap();
+        drop(storage);
+
+        // crash a metà della scrittura dell'ultima riga
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))
+            .unwrap();
+        journal.write_all(br#"{"seq":3,"op":"GroupCrea"#).unwrap();
+        drop(journal);
+
+        let (_, _, journal) = Storage::open(&dir, 100).unwrap();
+        assert_eq!(groups(&journal), ["a", "b"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn snapshot_compacts_the_journal() {
+        let dir = temp_dir();
+        let (mut storage, _, _) = Storage::open(&dir, 2).unwrap();
+        assert!(!storage.append(&created("a")).unwrap());
+        assert!(storage.append(&created("b")).unwrap());
+        storage.write_snapshot(Snapshot::default());
+        assert!(!storage.append(&created("c")).unwrap());
+        storage.flush().blocking_recv().unwrap();
+
+        let journal = fs::read_to_string(dir.join(JOURNAL_FILE)).unwrap();
+        assert_eq!(journal.lines().count(), 1);
+        let (_, snapshot, journal) = Storage::open(&dir, 2).unwrap();
+        assert_eq!(snapshot.seq, 2);
+        assert_eq!(groups(&journal), ["c"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn entries_already_in_the_snapshot_are_not_replayed() {
+        let dir = temp_dir();
+        fs::create_dir_all(&dir).unwrap();
+        // crash tra la scrittura dello snapshot e lo svuotamento del journal
+        let snapshot = Snapshot {
+            seq: 2,
+            ..Snapshot::default()
+        };
+        write_snapshot(&dir, &snapshot).unwrap();
+        let lines: String = [
+            r#"{"seq":1,"op":"InviteUsed","code":"x"}"#,
+            r#"{"seq":2,"op":"InviteUsed","code":"x"}"#,
+            r#"{"seq":3,"op":"InviteUsed","code":"x"}"#,
+        ]
+        .map(|l| format!("{l}\n"))
+        .concat();
+        fs::write(dir.join(JOURNAL_FILE), lines).unwrap();
+
+        let (mut storage, _, journal) = Storage::open(&dir, 100).unwrap();
+        assert_eq!(journal.len(), 1);
+        assert!(matches!(&journal[0], Mutation::InviteUsed { code } if code == "x"));
+        storage.append(&created("a")).unwrap();
+        storage.flush().blocking_recv().unwrap();
+        let last = fs::read_to_string(dir.join(JOURNAL_FILE)).unwrap();
+        assert!(last.lines().last().unwrap().contains(r#""seq":4"#));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
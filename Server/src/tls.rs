@@ -0,0 +1,220 @@
+/*
+Modulo Tls: carica certificato e chiave privata del server (formato PEM) e prepara l'acceptor TLS.
+Se il TLS non è configurato il server accetta connessioni TCP in chiaro come prima.
+*/
+
+use anyhow::Context;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+pub fn load_acceptor(cert_path: &str, key_path: &str) -> anyhow::Result<TlsAcceptor> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(cert_path).with_context(|| format!("apertura certificato {cert_path}"))?,
+    ))
+    .collect::<Result<Vec<_>, _>>()
+    .with_context(|| format!("lettura certificato {cert_path}"))?;
+    if certs.is_empty() {
+        anyhow::bail!("nessun certificato trovato in {cert_path}");
+    }
+
+    let key = rustls_pemfile::private_key(&mut BufReader::new(
+        File::open(key_path).with_context(|| format!("apertura chiave {key_path}"))?,
+    ))
+    .with_context(|| format!("lettura chiave {key_path}"))?
+    .with_context(|| format!("nessuna chiave privata trovata in {key_path}"))?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("certificato o chiave non validi")?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::{handle_conn, ConnConfig};
+    use crate::history::HistoryStore;
+    use crate::state::State;
+    use ruggine_common::{
+        ClientToServer, Envelope, FrameReader, FrameWriter, ServerToClient, MAX_FRAME_LEN,
+        PROTOCOL_VERSION,
+    };
+    use std::net::SocketAddr;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use tokio::io::{ReadHalf, WriteHalf};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::RwLock;
+    use tokio_rustls::client;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    // Cartella temporanea con certificato autofirmato per "localhost" e relativa chiave, in PEM
+    fn self_signed(dir: &Path) -> CertificateDer<'static> {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("cert.pem"), cert.pem()).unwrap();
+        std::fs::write(dir.join("key.pem"), key_pair.serialize_pem()).unwrap();
+        cert.der().clone()
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("ruggine-tls-{}", uuid::Uuid::new_v4()))
+    }
+
+    // Server TLS vero su una porta libera: ogni connessione passa per handle_conn
+    async fn start_server(
+        acceptor: TlsAcceptor,
+        history: PathBuf,
+    ) -> (SocketAddr, Arc<RwLock<State>>) {
+        let state = Arc::new(RwLock::new(State {
+            history: HistoryStore::new(history, 10),
+            ..State::default()
+        }));
+        let conf = Arc::new(ConnConfig::for_tests());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, peer)) = listener.accept().await {
+                let (acceptor, state, conf) = (acceptor.clone(), state.clone(), conf.clone());
+                tokio::spawn(async move {
+                    if let Ok(stream) = acceptor.accept(socket).await {
+                        let _ = handle_conn(stream, peer.ip(), state, conf).await;
+                    }
+                });
+            }
+        });
+        (addr, server_state)
+    }
+
+    fn connector(root: CertificateDer<'static>) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots.add(root).unwrap();
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    }
+
+    type TlsReader = FrameReader<ReadHalf<client::TlsStream<TcpStream>>>;
+    type TlsWriter = FrameWriter<WriteHalf<client::TlsStream<TcpStream>>>;
+
+    fn acceptor_in(dir: &Path) -> TlsAcceptor {
+        load_acceptor(
+            dir.join("cert.pem").to_str().unwrap(),
+            dir.join("key.pem").to_str().unwrap(),
+        )
+        .unwrap()
+    }
+
+    // Connessione TLS che ha già concordato il protocollo con Hello/Welcome
+    async fn tls_client(ca: CertificateDer<'static>, addr: SocketAddr) -> (TlsReader, TlsWriter) {
+        let name = ServerName::try_from("localhost").unwrap();
+        let stream = connector(ca)
+            .connect(name, TcpStream::connect(addr).await.unwrap())
+            .await
+            .unwrap();
+        let (r, w) = tokio::io::split(stream);
+        let mut reader = FrameReader::new(r, MAX_FRAME_LEN);
+        let mut writer = FrameWriter::new(w, MAX_FRAME_LEN);
+        let hello = ClientToServer::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            features: vec![],
+        };
+        writer.send(&Envelope::new(None, hello)).await.unwrap();
+        match reader.next::<ServerToClient>().await.unwrap() {
+            Some(ServerToClient::Welcome {
+                protocol_version, ..
+            }) => assert_eq!(protocol_version, PROTOCOL_VERSION),
+            other => panic!("atteso Welcome, ottenuto {other:?}"),
+        }
+        (reader, writer)
+    }
+
+    // Invia la richiesta di accesso e restituisce l'esito di Registered
+    async fn register(reader: &mut TlsReader, writer: &mut TlsWriter, msg: ClientToServer) -> bool {
+        writer.send(&Envelope::new(None, msg)).await.unwrap();
+        loop {
+            match reader.next::<ServerToClient>().await.unwrap() {
+                Some(ServerToClient::Registered { ok, .. }) => return ok,
+                Some(_) => continue,
+                None => panic!("connessione chiusa prima di Registered"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn hello_over_tls() {
+        let dir = temp_dir();
+        let ca = self_signed(&dir);
+        let (addr, _) = start_server(acceptor_in(&dir), dir.join("history")).await;
+
+        tls_client(ca, addr).await;
+
+        // un client che non conosce la CA rifiuta il certificato del server
+        let other = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let name = ServerName::try_from("localhost").unwrap();
+        let res = connector(other.cert.der().clone())
+            .connect(name, TcpStream::connect(addr).await.unwrap())
+            .await;
+        assert!(res.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn abrupt_close_ends_session() {
+        let dir = temp_dir();
+        let ca = self_signed(&dir);
+        let (addr, state) = start_server(acceptor_in(&dir), dir.join("history")).await;
+        let credentials = |create: bool| {
+            let (nick, password) = ("alice".to_string(), "password1".to_string());
+            match create {
+                true => ClientToServer::CreateAccount { nick, password },
+                false => ClientToServer::Login { nick, password },
+            }
+        };
+
+        let (mut reader, mut writer) = tls_client(ca.clone(), addr).await;
+        assert!(register(&mut reader, &mut writer, credentials(true)).await);
+        // il socket si chiude senza close_notify: il server legge un UnexpectedEof
+        drop((reader, writer));
+
+        // senza periodo di grazia la sessione deve sparire, non restare "fantasma"
+        let mut left = false;
+        for _ in 0..100 {
+            if state.read().await.users_by_nick.is_empty() {
+                left = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(
+            left,
+            "sessione ancora presente dopo la chiusura della connessione"
+        );
+
+        let (mut reader, mut writer) = tls_client(ca, addr).await;
+        assert!(register(&mut reader, &mut writer, credentials(false)).await);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_key_rejected() {
+        let dir = temp_dir();
+        self_signed(&dir);
+        let cert = dir.join("cert.pem");
+        // il file del certificato non contiene una chiave privata
+        assert!(load_acceptor(cert.to_str().unwrap(), cert.to_str().unwrap()).is_err());
+        assert!(load_acceptor(dir.join("nope.pem").to_str().unwrap(), "").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
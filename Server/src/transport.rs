@@ -0,0 +1,65 @@
+/*
+Modulo Transport: separa la gestione di una connessione dal mezzo su cui viaggiano i messaggi.
+Il ciclo di `connection::serve` legge frame da un `FrameSource` e scrive con un `FrameSink`:
+gli stream di byte (TCP o TLS) usano FrameReader/FrameWriter di ruggine_common,
+le connessioni WebSocket gli adattatori del modulo websocket.
+*/
+
+use ruggine_common::{Codec, Envelope, FrameError, FrameReader, FrameWriter, ServerToClient};
+use std::future::Future;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+// Metà di lettura di una connessione: un messaggio codificato alla volta
+pub trait FrameSource: Send {
+    fn codec(&self) -> Codec;
+
+    fn set_codec(&mut self, codec: Codec);
+
+    // Prossimo frame; None quando il client ha chiuso la connessione
+    fn next_frame(&mut self) -> impl Future<Output = Result<Option<Vec<u8>>, FrameError>> + Send;
+}
+
+// Metà di scrittura di una connessione, usata dal task di scrittura
+pub trait FrameSink: Send + 'static {
+    fn set_codec(&mut self, codec: Codec);
+
+    // Un messaggio oltre il limite restituisce TooLarge senza chiudere la connessione;
+    // altrimenti la dimensione del messaggio codificato
+    fn send(
+        &mut self,
+        msg: &Envelope<ServerToClient>,
+    ) -> impl Future<Output = Result<usize, FrameError>> + Send;
+
+    fn shutdown(&mut self) -> impl Future<Output = std::io::Result<()>> + Send;
+}
+
+impl<R: AsyncRead + Unpin + Send> FrameSource for FrameReader<R> {
+    fn codec(&self) -> Codec {
+        FrameReader::codec(self)
+    }
+
+    fn set_codec(&mut self, codec: Codec) {
+        FrameReader::set_codec(self, codec)
+    }
+
+    fn next_frame(&mut self) -> impl Future<Output = Result<Option<Vec<u8>>, FrameError>> + Send {
+        FrameReader::next_frame(self)
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send + 'static> FrameSink for FrameWriter<W> {
+    fn set_codec(&mut self, codec: Codec) {
+        FrameWriter::set_codec(self, codec)
+    }
+
+    fn send(
+        &mut self,
+        msg: &Envelope<ServerToClient>,
+    ) -> impl Future<Output = Result<usize, FrameError>> + Send {
+        FrameWriter::send(self, msg)
+    }
+
+    fn shutdown(&mut self) -> impl Future<Output = std::io::Result<()>> + Send {
+        FrameWriter::shutdown(self)
+    }
+}
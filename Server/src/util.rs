@@ -1,11 +1,24 @@
 /*
 Modulo Util: contiene funzioni di utilità generali
-in questo caso contiene solo la funzione per generare codici invito a 6 caratteri
+in questo caso la funzione per generare codici invito (6 caratteri salvo --invite-code-len)
+e il confronto dei token segreti (API di amministrazione e ripresa della sessione)
 */
 
-// codice invito per il gruppo (6 caratteri alfanumerici)
-pub fn short_code() -> String {
+pub const DEFAULT_CODE_LEN: usize = 6;
+
+// codice invito per il gruppo (`len` caratteri alfanumerici)
+pub fn short_code(len: usize) -> String {
     use rand::{distributions::Alphanumeric, Rng};
     let mut rng = rand::thread_rng();
-    (0..6).map(|_| rng.sample(Alphanumeric) as char).collect()
+    (0..len).map(|_| rng.sample(Alphanumeric) as char).collect()
+}
+
+// Confronto a tempo costante: la durata non rivela quanti caratteri iniziali sono corretti
+pub fn same_token(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
 }
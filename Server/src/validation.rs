@@ -3,70 +3,83 @@ Modulo Validation: implementa le regole di validazione per nickname e nomi dei g
 Garantisce che gli identificatori rispettino le regole di sintassi e unicità.
 */
 
-// Tipo di identificatore che stiamo validando, se nickname oppure nome del gruppo
-pub enum NameKind {
-    Nick,
-    Group,
-}
+use ruggine_common::{ErrorCode, NameRule, NameTarget};
 
-impl NameKind {
-    //restituisce una stringa descrittiva in minuscolo ("nickname" o "nome del gruppo"), 
-    //usata nei messaggi di errore.
-    fn label(&self) -> &'static str {
-        match self {
-            Self::Nick => "nickname",
-            Self::Group => "nome del gruppo",
-        }
-    }
-}
+use crate::config::Limits;
+
+// Lunghezza massima di default di nickname e nomi dei gruppi (--max-name-len)
+pub const DEFAULT_MAX_NAME_LEN: usize = 32;
+// Nomi riservati di default (--reserved-name), confrontati senza distinguere maiuscole e minuscole
+pub const DEFAULT_RESERVED_NAMES: &[&str] = &["server", "client"];
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 128;
 
 // Valida un identificatore generico usato come nickname o nome gruppo.
-pub fn validate_identifier(kind: NameKind, s: &str) -> Result<(), String> {
+// L'errore indica la regola violata; il testo lo produce ErrorCode
+pub fn validate_identifier(target: NameTarget, s: &str, limits: &Limits) -> Result<(), ErrorCode> {
+    let invalid = |rule| Err(ErrorCode::InvalidName { target, rule });
     if s.is_empty() {
-        return Err(format!("Il {} non può essere vuoto.", kind.label()));
+        return invalid(NameRule::Empty);
     }
-    if s.len() > 32 {
-        return Err(format!("{} troppo lungo (max 32).", kind.label()));
+    if s.len() > limits.max_name_len {
+        return invalid(NameRule::TooLong {
+            max: limits.max_name_len,
+        });
     }
     if !s.is_ascii() {
-        return Err("Sono consentiti solo caratteri ASCII.".into());
+        return invalid(NameRule::NotAscii);
     }
-    let lowered = s.to_ascii_lowercase();
-    if lowered == "server" || lowered == "client" {
-        return Err(format!(
-            "Il {} non può chiamarsi 'server' o 'client'",
-            kind.label()
-        ));
+    if limits
+        .reserved_names
+        .iter()
+        .any(|r| r.eq_ignore_ascii_case(s))
+    {
+        return invalid(NameRule::Reserved);
     }
     if s.chars().any(|c| c.is_whitespace()) {
-        return Err(format!(
-            "Il {} non può contenere spazi o caratteri di whitespace.",
-            kind.label()
-        ));
+        return invalid(NameRule::Whitespace);
     }
     if let Some(first) = s.chars().next() {
         if !first.is_ascii_alphabetic() {
-            return Err(format!(
-                "Il {} deve iniziare con una lettera (A-Z o a-z).",
-                kind.label()
-            ));
+            return invalid(NameRule::FirstNotLetter);
         }
     }
     if !s.chars().all(|c| c.is_ascii_alphanumeric()) {
-        return Err(format!(
-            "Il {} può contenere solo lettere e numeri (niente simboli).",
-            kind.label()
-        ));
+        return invalid(NameRule::NotAlphanumeric);
     }
     Ok(())
 }
 
 //validazione specifica per nickname
-pub fn validate_nick_syntax(s: &str) -> Result<(), String> {
-    validate_identifier(NameKind::Nick, s)
+pub fn validate_nick_syntax(s: &str, limits: &Limits) -> Result<(), ErrorCode> {
+    validate_identifier(NameTarget::Nick, s, limits)
 }
 
 //validazione specifica per nomi dei gruppi
-pub fn validate_group_name_syntax(s: &str) -> Result<(), String> {
-    validate_identifier(NameKind::Group, s)
+pub fn validate_group_name_syntax(s: &str, limits: &Limits) -> Result<(), ErrorCode> {
+    validate_identifier(NameTarget::Group, s, limits)
+}
+
+//validazione del testo di un messaggio (gruppo, globale o privato): lunghezza massima in byte
+pub fn validate_text(s: &str, limits: &Limits) -> Result<(), ErrorCode> {
+    let max = limits.max_text_len();
+    if s.len() > max {
+        return Err(ErrorCode::TextTooLong { max });
+    }
+    Ok(())
+}
+
+//validazione della password: lunghezza minima e massima
+pub fn validate_password(s: &str) -> Result<(), ErrorCode> {
+    if s.chars().count() < MIN_PASSWORD_LEN {
+        return Err(ErrorCode::PasswordTooShort {
+            min: MIN_PASSWORD_LEN,
+        });
+    }
+    if s.len() > MAX_PASSWORD_LEN {
+        return Err(ErrorCode::PasswordTooLong {
+            max: MAX_PASSWORD_LEN,
+        });
+    }
+    Ok(())
 }
@@ -0,0 +1,331 @@
+/*
+Modulo WebSocket: permette ai client web di collegarsi al server su un secondo indirizzo (--ws-bind).
+Ogni messaggio WebSocket porta un messaggio del protocollo: frame di testo con lo stesso JSON
+delle connessioni TCP, oppure frame binari in MessagePack se concordato con Hello.
+Dopo l'handshake HTTP la connessione viene gestita da `connection::serve` come quelle TCP,
+con lo stesso stato, gli stessi comandi e gli stessi limiti.
+*/
+
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use ruggine_common::{Codec, Envelope, FrameError, ServerToClient};
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::RwLock;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::WebSocketStream;
+
+use crate::connection::{serve, ConnConfig};
+use crate::state::State;
+use crate::transport::{FrameSink, FrameSource};
+
+// Handshake WebSocket sullo stream già accettato (TCP o TLS), poi gestione come una connessione TCP
+pub async fn handle_ws<S>(
+    stream: S,
+    peer: IpAddr,
+    state: Arc<RwLock<State>>,
+    conf: Arc<ConnConfig>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // il limite vale per il messaggio intero, anche se il client lo divide in più frame
+    let ws_conf = WebSocketConfig {
+        max_message_size: Some(conf.max_frame),
+        max_frame_size: Some(conf.max_frame),
+        ..Default::default()
+    };
+    let ws = tokio_tungstenite::accept_async_with_config(stream, Some(ws_conf)).await?;
+    let (sink, stream) = ws.split();
+    let reader = WsReader {
+        inner: stream,
+        codec: Codec::Json,
+        max_len: conf.max_frame,
+    };
+    let writer = WsWriter {
+        inner: sink,
+        codec: Codec::Json,
+        max_len: conf.max_frame,
+    };
+    serve(reader, writer, peer, state, conf).await
+}
+
+struct WsReader<S> {
+    inner: SplitStream<WebSocketStream<S>>,
+    codec: Codec,
+    max_len: usize,
+}
+
+struct WsWriter<S> {
+    inner: SplitSink<WebSocketStream<S>, Message>,
+    codec: Codec,
+    max_len: usize,
+}
+
+impl<S> FrameSource for WsReader<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    // Ping e Pong sono gestiti dalla libreria; un Close equivale alla fine dello stream TCP
+    async fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        loop {
+            let msg = match self.inner.next().await {
+                Some(Ok(msg)) => msg,
+                Some(Err(e)) => return Err(ws_error(e, self.max_len)),
+                None => return Ok(None),
+            };
+            match msg {
+                Message::Text(text) => return Ok(Some(text.into_bytes())),
+                Message::Binary(data) => return Ok(Some(data)),
+                Message::Close(_) => return Ok(None),
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+            }
+        }
+    }
+}
+
+impl<S> FrameSink for WsWriter<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    // JSON nei frame di testo, MessagePack nei frame binari (senza prefisso di lunghezza)
+    async fn send(&mut self, msg: &Envelope<ServerToClient>) -> Result<usize, FrameError> {
+        let data = self.codec.encode(msg)?;
+        let len = data.len();
+        if len > self.max_len {
+            return Err(FrameError::TooLarge { max: self.max_len });
+        }
+        let msg = match self.codec {
+            Codec::Json => Message::Text(
+                String::from_utf8(data).map_err(|e| FrameError::Encode(e.to_string()))?,
+            ),
+            Codec::MsgPack => Message::Binary(data),
+        };
+        self.inner
+            .send(msg)
+            .await
+            .map_err(|e| ws_error(e, self.max_len))?;
+        Ok(len)
+    }
+
+    async fn shutdown(&mut self) -> std::io::Result<()> {
+        self.inner
+            .close()
+            .await
+            .map_err(|e| std::io::Error::new(ErrorKind::ConnectionAborted, e))
+    }
+}
+
+// Gli errori del protocollo WebSocket chiudono la connessione come un reset TCP
+fn ws_error(e: WsError, max_len: usize) -> FrameError {
+    match e {
+        WsError::Capacity(_) => FrameError::TooLarge { max: max_len },
+        WsError::Io(e) => FrameError::Io(e),
+        e => FrameError::Io(std::io::Error::new(ErrorKind::ConnectionAborted, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::handle_conn;
+    use crate::history::HistoryStore;
+    use ruggine_common::{
+        ClientToServer, FrameReader, FrameWriter, MAX_FRAME_LEN, PROTOCOL_VERSION,
+    };
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_secs(10);
+
+    // Gateway WebSocket e indirizzo TCP sullo stesso stato, come con --ws-bind
+    async fn start_server(history: std::path::PathBuf) -> (SocketAddr, SocketAddr) {
+        let state = Arc::new(RwLock::new(State {
+            history: HistoryStore::new(history, 10),
+            ..State::default()
+        }));
+        let conf = Arc::new(ConnConfig::for_tests());
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addrs = (tcp.local_addr().unwrap(), ws.local_addr().unwrap());
+        let (st, cf) = (state.clone(), conf.clone());
+        tokio::spawn(async move {
+            while let Ok((socket, peer)) = tcp.accept().await {
+                tokio::spawn(handle_conn(socket, peer.ip(), st.clone(), cf.clone()));
+            }
+        });
+        tokio::spawn(async move {
+            while let Ok((socket, peer)) = ws.accept().await {
+                tokio::spawn(handle_ws(socket, peer.ip(), state.clone(), conf.clone()));
+            }
+        });
+        addrs
+    }
+
+    fn hello() -> ClientToServer {
+        ClientToServer::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            features: vec![],
+        }
+    }
+
+    fn create_account(nick: &str) -> ClientToServer {
+        ClientToServer::CreateAccount {
+            nick: nick.to_string(),
+            password: "password1".to_string(),
+        }
+    }
+
+    // Client web: un frame di testo JSON per messaggio
+    struct WsClient(WebSocketStream<TcpStream>);
+
+    impl WsClient {
+        async fn send(&mut self, msg: ClientToServer) {
+            let text = serde_json::to_string(&Envelope::new(None, msg)).unwrap();
+            self.0.send(Message::text(text)).await.unwrap();
+        }
+
+        // Primo messaggio del server che soddisfa `want`, scartando gli altri
+        async fn expect<T>(&mut self, want: impl Fn(ServerToClient) -> Option<T>) -> T {
+            loop {
+                let msg = timeout(WAIT, self.0.next())
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .unwrap();
+                if let Message::Text(text) = msg {
+                    if let Some(found) = want(serde_json::from_str(&text).unwrap()) {
+                        return found;
+                    }
+                }
+            }
+        }
+    }
+
+    // Client TCP: NDJSON sullo stream
+    struct TcpClient {
+        reader: FrameReader<tokio::net::tcp::OwnedReadHalf>,
+        writer: FrameWriter<tokio::net::tcp::OwnedWriteHalf>,
+    }
+
+    impl TcpClient {
+        async fn send(&mut self, msg: ClientToServer) {
+            self.writer.send(&Envelope::new(None, msg)).await.unwrap();
+        }
+
+        async fn expect<T>(&mut self, want: impl Fn(ServerToClient) -> Option<T>) -> T {
+            loop {
+                let msg = timeout(WAIT, self.reader.next())
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .unwrap();
+                if let Some(found) = want(msg) {
+                    return found;
+                }
+            }
+        }
+    }
+
+    fn registered(msg: ServerToClient) -> Option<()> {
+        match msg {
+            ServerToClient::Registered { ok, reason, .. } => {
+                assert!(ok, "registrazione rifiutata: {reason:?}");
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn websocket_and_tcp_share_groups() {
+        let dir = std::env::temp_dir().join(format!("ruggine-ws-{}", uuid::Uuid::new_v4()));
+        let (tcp_addr, ws_addr) = start_server(dir.clone()).await;
+
+        let socket = TcpStream::connect(ws_addr).await.unwrap();
+        let url = format!("ws://{ws_addr}/");
+        let (ws, _) = tokio_tungstenite::client_async(url, socket).await.unwrap();
+        let mut alice = WsClient(ws);
+        alice.send(hello()).await;
+        alice
+            .expect(|m| matches!(m, ServerToClient::Welcome { .. }).then_some(()))
+            .await;
+        alice.send(create_account("alice")).await;
+        alice.expect(registered).await;
+
+        let (r, w) = TcpStream::connect(tcp_addr).await.unwrap().into_split();
+        let mut bob = TcpClient {
+            reader: FrameReader::new(r, MAX_FRAME_LEN),
+            writer: FrameWriter::new(w, MAX_FRAME_LEN),
+        };
+        bob.send(hello()).await;
+        bob.expect(|m| matches!(m, ServerToClient::Welcome { .. }).then_some(()))
+            .await;
+        bob.send(create_account("bob")).await;
+        bob.expect(registered).await;
+
+        // alice crea il gruppo dal browser e invita bob, che entra dal client TCP
+        alice
+            .send(ClientToServer::CreateGroup {
+                group: "team".into(),
+            })
+            .await;
+        alice
+            .expect(|m| matches!(m, ServerToClient::GroupCreated { .. }).then_some(()))
+            .await;
+        alice
+            .send(ClientToServer::Invite {
+                group: "team".into(),
+                nick: "bob".into(),
+                ttl: None,
+            })
+            .await;
+        let code = bob
+            .expect(|m| match m {
+                ServerToClient::InviteCode { code, .. } => Some(code),
+                _ => None,
+            })
+            .await;
+        bob.send(ClientToServer::JoinGroup {
+            group: "team".into(),
+            invite_code: code,
+        })
+        .await;
+        bob.expect(|m| matches!(m, ServerToClient::Joined { .. }).then_some(()))
+            .await;
+
+        alice
+            .send(ClientToServer::SendMessage {
+                group: "team".into(),
+                text: "ciao dal browser".into(),
+            })
+            .await;
+        let (from, text) = bob
+            .expect(|m| match m {
+                ServerToClient::Message { from, text, .. } => Some((from, text)),
+                _ => None,
+            })
+            .await;
+        assert_eq!(from, "alice");
+        assert_eq!(text, "ciao dal browser");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,139 @@
+/*
+Modulo Catalog: testi dei codici di errore (ErrorCode) in italiano e inglese, comuni a client e server.
+Ogni voce è (chiave, italiano, inglese); `{}` indica i parametri, nell'ordine.
+Si usa con la macro t!(lingua, "chiave", parametri...): a differenza di quelle di client e server
+la lingua si indica ogni volta, perché `ErrorCode::text` può produrre il testo in una lingua qualsiasi.
+*/
+
+use crate::i18n::Catalog;
+
+pub static CATALOG: Catalog = &[
+    // connessione e protocollo
+    ("error.bad_json", "JSON errato", "Malformed JSON"),
+    ("error.missing_kind", "JSON errato: manca il campo 'kind'", "Malformed JSON: missing 'kind' field"),
+    ("error.unknown_message", "Messaggio '{}' non valido o non supportato dal protocollo v{}", "Message '{}' is invalid or not supported by protocol v{}"),
+    ("error.hello_required", "Negoziazione mancante: il primo messaggio deve essere Hello (protocollo v{})", "Negotiation missing: the first message must be Hello (protocol v{})"),
+    ("error.unsupported_version", "Versione del protocollo {} non supportata (il server accetta dalla {} alla {}): {}", "Protocol version {} not supported (the server accepts {} to {}): {}"),
+    ("error.update_client", "aggiorna il client", "please update the client"),
+    ("error.update_server", "il server non è aggiornato", "the server is out of date"),
+    ("error.already_negotiated", "Negoziazione del protocollo già effettuata", "Protocol already negotiated"),
+    ("error.rate_limited", "Troppi messaggi ({}): rallenta", "Too many messages ({}): slow down"),
+    ("error.flood", "Disconnesso: troppi messaggi in poco tempo", "Disconnected: too many messages in a short time"),
+    ("error.idle_timeout", "Disconnesso per inattività", "Disconnected for inactivity"),
+    ("error.queue_full", "Disconnesso: troppi messaggi non letti (oltre {})", "Disconnected: too many unread messages (over {})"),
+    ("error.frame_too_large", "Disconnesso: messaggio oltre il limite di {} byte", "Disconnected: message larger than the {}-byte limit"),
+    ("error.reply_too_large", "Risposta non inviata: supera il limite di {} byte", "Reply not sent: larger than the {}-byte limit"),
+    ("error.text_too_long", "Messaggio troppo lungo, non inviato (massimo {} byte)", "Message too long, not sent (max {} bytes)"),
+    ("error.kicked_by_admin", "Disconnesso dall'amministratore del server", "Disconnected by the server administrator"),
+    ("error.internal", "Errore interno del server", "Internal server error"),
+    // account e sessioni
+    ("error.not_registered", "Non registrato", "Not logged in"),
+    ("error.already_authenticated", "Sei già autenticato", "You are already logged in"),
+    ("error.invalid_name", "Il {} {}", "The {} {}"),
+    ("error.password_too_short", "La password deve contenere almeno {} caratteri.", "The password must be at least {} characters long."),
+    ("error.password_too_long", "Password troppo lunga (max {}).", "Password too long (max {})."),
+    ("error.nick_taken", "Esiste già un utente con il nome '{}' (già registrato come '{}')", "A user named '{}' already exists (registered as '{}')"),
+    ("error.group_taken", "Esiste già un gruppo con il nome '{}' (già registrato come '{}')", "A group named '{}' already exists (registered as '{}')"),
+    ("error.name_used_by_user", "Il nome '{}' è già usato da un utente", "The name '{}' is already used by a user"),
+    ("error.wrong_credentials", "Nickname o password errati", "Wrong nickname or password"),
+    ("error.already_connected", "L'utente '{}' è già connesso", "User '{}' is already connected"),
+    ("error.session_expired", "Sessione scaduta o non valida", "Session expired or invalid"),
+    ("error.session_replaced", "Sessione ripresa da un'altra connessione", "Session resumed from another connection"),
+    // gruppi e utenti
+    ("error.group_not_found", "Gruppo {} inesistente", "Group {} does not exist"),
+    ("error.not_member", "Non sei membro del gruppo {}", "You are not a member of group {}"),
+    ("error.already_member", "Sei già membro del gruppo {}", "You are already a member of group {}"),
+    ("error.no_groups", "Nessun gruppo di appartenenza", "You are not in any group"),
+    ("error.user_not_found", "Utente {} inesistente", "User {} does not exist"),
+    ("error.user_not_member", "Utente {} non è membro del gruppo {}", "User {} is not a member of group {}"),
+    ("error.user_already_member", "Utente {} già membro del gruppo {}", "User {} is already a member of group {}"),
+    ("error.user_offline", "L'utente {} non è connesso", "User {} is not connected"),
+    ("error.dm_to_self", "Non puoi inviare un messaggio privato a te stesso", "You cannot send a direct message to yourself"),
+    // inviti
+    ("error.invalid_invite", "Invito non valido", "Invalid invite"),
+    ("error.invite_wrong_group", "Invito non per il gruppo {}", "The invite is not for group {}"),
+    ("error.invite_for_other", "Invito destinato a {}", "The invite is meant for {}"),
+    ("error.invite_expired", "Invito scaduto", "The invite has expired"),
+    ("error.invite_revoked", "Invito revocato", "The invite has been revoked"),
+    ("error.invite_used", "Invito già usato", "The invite has already been used"),
+    ("error.invite_used_up", "Invito esaurito (già usato {} volte)", "The invite has been used up ({} uses)"),
+    ("error.invalid_invite_limit", "Durata e numero di usi dell'invito devono essere maggiori di zero", "Invite duration and number of uses must be greater than zero"),
+    // ruoli
+    ("error.not_allowed", "{} nel gruppo {}", "{} in group {}"),
+    ("error.kick_self", "Per uscire dal gruppo usa /leave", "Use /leave to leave the group"),
+    ("error.already_admin", "{} è già amministratore del gruppo {}", "{} is already an admin of group {}"),
+    ("error.not_admin", "{} non è amministratore del gruppo {}", "{} is not an admin of group {}"),
+    ("error.already_owner", "Sei già il proprietario del gruppo {}", "You already own group {}"),
+    // moderazione
+    ("error.not_operator", "Solo gli operatori del server possono moderare", "Only server operators can moderate"),
+    ("error.target_is_operator", "{} è un operatore del server", "{} is a server operator"),
+    ("error.kicked_by_operator", "Disconnesso dall'operatore {}", "Disconnected by operator {}"),
+    ("error.banned", "Sei stato bandito dal server", "You are banned from this server"),
+    ("error.with_reason", "{} (motivo: {})", "{} (reason: {})"),
+    ("error.muted", "Non puoi inviare messaggi per altri {} secondi", "You cannot send messages for another {} seconds"),
+    ("error.invalid_address", "'{}' non è un indirizzo IP né un utente connesso", "'{}' is neither an IP address nor a connected user"),
+    ("error.not_banned", "{} non è bandito", "{} is not banned"),
+    ("error.not_muted", "{} non è silenziato", "{} is not muted"),
+    // nomi non validi (NameTarget e NameRule)
+    ("name.nick", "nickname", "nickname"),
+    ("name.group", "nome del gruppo", "group name"),
+    ("rule.empty", "non può essere vuoto.", "cannot be empty."),
+    ("rule.too_long", "è troppo lungo (max {}).", "is too long (max {})."),
+    ("rule.not_ascii", "può contenere solo caratteri ASCII.", "may only contain ASCII characters."),
+    ("rule.reserved", "è un nome riservato dal server.", "is a name reserved by the server."),
+    ("rule.whitespace", "non può contenere spazi o caratteri di whitespace.", "cannot contain spaces or whitespace."),
+    ("rule.first_not_letter", "deve iniziare con una lettera (A-Z o a-z).", "must start with a letter (A-Z or a-z)."),
+    ("rule.not_alphanumeric", "può contenere solo lettere e numeri (niente simboli).", "may only contain letters and digits (no symbols)."),
+    // permessi mancanti (GroupAction)
+    ("action.invite", "Solo il proprietario e gli amministratori possono invitare", "Only the owner and admins can invite"),
+    ("action.manage_invites", "Solo il proprietario, gli amministratori e chi ha creato l'invito possono gestire gli inviti", "Only the owner, admins and the invite's creator can manage invites"),
+    ("action.kick", "Non hai i permessi per espellere questo membro", "You are not allowed to kick this member"),
+    ("action.promote", "Solo il proprietario può promuovere i membri", "Only the owner can promote members"),
+    ("action.demote", "Solo il proprietario può declassare gli amministratori", "Only the owner can demote admins"),
+    ("action.transfer_ownership", "Solo il proprietario può cedere la proprietà", "Only the owner can transfer ownership"),
+    ("action.rename", "Solo il proprietario e gli amministratori possono cambiare il nome", "Only the owner and admins can change the name"),
+];
+
+// Testo della chiave nella lingua indicata, con i parametri al posto di `{}`
+macro_rules! t {
+    ($lang:expr, $key:literal $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::translate_in(
+            $lang,
+            $crate::catalog::CATALOG,
+            $key,
+            &[$(&$arg as &dyn std::fmt::Display),*],
+        )
+    };
+}
+pub(crate) use t;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Una chiave assente verrebbe mostrata così com'è: ogni chiave usata per gli errori deve esistere
+    #[test]
+    fn every_error_key_is_in_the_catalog() {
+        let source = include_str!("error_code.rs");
+        let used: Vec<&str> = source
+            .split("t!(l, \"")
+            .skip(1)
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+            .collect();
+        assert!(used.len() > 70);
+        for key in &used {
+            assert!(
+                CATALOG.iter().any(|(k, _, _)| k == key),
+                "chiave {key} mancante"
+            );
+        }
+        for (i, (key, it, en)) in CATALOG.iter().enumerate() {
+            assert!(used.contains(key), "chiave {key} mai usata");
+            assert!(
+                CATALOG[i + 1..].iter().all(|(k, _, _)| k != key),
+                "chiave {key} ripetuta"
+            );
+            assert_eq!(it.matches("{}").count(), en.matches("{}").count(), "{key}");
+        }
+    }
+}
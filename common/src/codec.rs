@@ -0,0 +1,338 @@
+/*
+Codifica dei messaggi sulla connessione e suddivisione dello stream in frame.
+Ogni connessione parte in NDJSON (un oggetto JSON per riga); se client e server concordano la
+funzionalità "msgpack" con Hello/Welcome, dopo il Welcome si passa a frame binari: 4 byte con la
+lunghezza (big endian) seguiti dal messaggio in MessagePack. Gli enum del protocollo sono gli stessi.
+In entrambi i casi un frame non può superare `max_len` byte: chi legge si ferma senza accumulare
+altri dati, chi scrive rifiuta il messaggio.
+*/
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::FEATURE_MSGPACK;
+
+// Dimensione massima predefinita di un frame (JSON senza il newline, o MessagePack senza la lunghezza)
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    // NDJSON: un messaggio per riga
+    #[default]
+    Json,
+    // lunghezza u32 big endian + MessagePack
+    MsgPack,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FrameError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("frame too large (max {max} bytes)")]
+    TooLarge { max: usize },
+    #[error("encode error: {0}")]
+    Encode(String),
+    #[error("decode error: {0}")]
+    Decode(String),
+}
+
+impl Codec {
+    // Codec da usare dopo il Welcome, date le funzionalità concordate
+    pub fn negotiated(features: &[String]) -> Codec {
+        if features.iter().any(|f| f == FEATURE_MSGPACK) {
+            Codec::MsgPack
+        } else {
+            Codec::Json
+        }
+    }
+
+    // Messaggio codificato, senza delimitatore né lunghezza
+    pub fn encode<T: Serialize>(self, msg: &T) -> Result<Vec<u8>, FrameError> {
+        match self {
+            Codec::Json => serde_json::to_vec(msg).map_err(|e| FrameError::Encode(e.to_string())),
+            // con i nomi dei campi: gli enum con `tag` e i campi `flatten` richiedono delle mappe
+            Codec::MsgPack => {
+                rmp_serde::to_vec_named(msg).map_err(|e| FrameError::Encode(e.to_string()))
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, frame: &[u8]) -> Result<T, FrameError> {
+        match self {
+            Codec::Json => {
+                serde_json::from_slice(frame).map_err(|e| FrameError::Decode(e.to_string()))
+            }
+            Codec::MsgPack => {
+                rmp_serde::from_slice(frame).map_err(|e| FrameError::Decode(e.to_string()))
+            }
+        }
+    }
+}
+
+// Accetta "json" e "msgpack", come per --codec
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Codec::Json),
+            "msgpack" => Ok(Codec::MsgPack),
+            _ => Err(format!("codec '{s}' non supportato (json, msgpack)")),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Codec::Json => "json",
+            Codec::MsgPack => "msgpack",
+        })
+    }
+}
+
+// Metà di lettura della connessione: restituisce un frame alla volta
+pub struct FrameReader<R> {
+    inner: BufReader<R>,
+    codec: Codec,
+    max_len: usize,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    pub fn new(reader: R, max_len: usize) -> Self {
+        Self {
+            inner: BufReader::new(reader),
+            codec: Codec::Json,
+            max_len,
+        }
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    // Prossimo frame; None quando l'altra parte ha chiuso la connessione.
+    // Le righe vuote dell'NDJSON vengono saltate
+    pub async fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        match self.codec {
+            Codec::Json => loop {
+                match self.read_line().await? {
+                    Some(line) if line.trim_ascii().is_empty() => continue,
+                    other => return Ok(other),
+                }
+            },
+            Codec::MsgPack => self.read_prefixed().await,
+        }
+    }
+
+    // Prossimo frame già decodificato con il codec corrente
+    pub async fn next<T: DeserializeOwned>(&mut self) -> Result<Option<T>, FrameError> {
+        match self.next_frame().await? {
+            Some(frame) => self.codec.decode(&frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // Come `lines()`, ma senza mai tenere in memoria più di `max_len` byte
+    async fn read_line(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        let mut line = Vec::new();
+        loop {
+            let available = self.inner.fill_buf().await?;
+            if available.is_empty() {
+                // EOF: un'ultima riga senza newline vale comunque come frame
+                return Ok((!line.is_empty()).then_some(line));
+            }
+            let (chunk, found) = match available.iter().position(|&b| b == b'\n') {
+                Some(i) => (&available[..i], true),
+                None => (available, false),
+            };
+            if line.len() + chunk.len() > self.max_len {
+                return Err(FrameError::TooLarge { max: self.max_len });
+            }
+            line.extend_from_slice(chunk);
+            let used = chunk.len() + usize::from(found);
+            self.inner.consume(used);
+            if found {
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(Some(line));
+            }
+        }
+    }
+
+    async fn read_prefixed(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        if self.inner.fill_buf().await?.is_empty() {
+            return Ok(None);
+        }
+        let len = self.inner.read_u32().await? as usize;
+        if len > self.max_len {
+            return Err(FrameError::TooLarge { max: self.max_len });
+        }
+        let mut frame = vec![0; len];
+        self.inner.read_exact(&mut frame).await?;
+        Ok(Some(frame))
+    }
+}
+
+// Metà di scrittura della connessione: codifica e invia un messaggio alla volta
+pub struct FrameWriter<W> {
+    inner: W,
+    codec: Codec,
+    max_len: usize,
+}
+
+impl<W: AsyncWrite + Unpin> FrameWriter<W> {
+    pub fn new(writer: W, max_len: usize) -> Self {
+        Self {
+            inner: writer,
+            codec: Codec::Json,
+            max_len,
+        }
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    // Un messaggio oltre il limite non viene inviato e la connessione resta utilizzabile.
+    // Restituisce la dimensione del messaggio codificato (senza newline né lunghezza)
+    pub async fn send<T: Serialize>(&mut self, msg: &T) -> Result<usize, FrameError> {
+        let data = self.codec.encode(msg)?;
+        if data.len() > self.max_len {
+            return Err(FrameError::TooLarge { max: self.max_len });
+        }
+        match self.codec {
+            Codec::Json => {
+                self.inner.write_all(&data).await?;
+                self.inner.write_all(b"\n").await?;
+            }
+            Codec::MsgPack => {
+                self.inner.write_u32(data.len() as u32).await?;
+                self.inner.write_all(&data).await?;
+            }
+        }
+        // con TLS i dati restano nel buffer del record finché non si fa flush
+        self.inner.flush().await?;
+        Ok(data.len())
+    }
+
+    pub async fn shutdown(&mut self) -> std::io::Result<()> {
+        self.inner.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientToServer, Envelope};
+
+    fn send_message(id: Option<u64>) -> Envelope<ClientToServer> {
+        Envelope::new(
+            id,
+            ClientToServer::SendMessage {
+                group: "team".into(),
+                text: "ciao".into(),
+            },
+        )
+    }
+
+    fn assert_send_message(env: Envelope<ClientToServer>, id: Option<u64>) {
+        assert_eq!(env.id, id);
+        match env.body {
+            ClientToServer::SendMessage { group, text } => {
+                assert_eq!(group, "team");
+                assert_eq!(text, "ciao");
+            }
+            other => panic!("messaggio inatteso: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn msgpack_round_trip() {
+        for id in [None, Some(42)] {
+            let bytes = rmp_serde::to_vec_named(&send_message(id)).unwrap();
+            assert_send_message(rmp_serde::from_slice(&bytes).unwrap(), id);
+            // lo stesso percorso usato sulla connessione
+            let bytes = Codec::MsgPack.encode(&send_message(id)).unwrap();
+            assert_send_message(Codec::MsgPack.decode(&bytes).unwrap(), id);
+        }
+    }
+
+    #[tokio::test]
+    async fn msgpack_frames_use_length_prefix() {
+        let mut wire = Vec::new();
+        let mut writer = FrameWriter::new(&mut wire, MAX_FRAME_LEN);
+        writer.set_codec(Codec::MsgPack);
+        let first = writer.send(&send_message(Some(1))).await.unwrap();
+        let second = writer.send(&send_message(Some(2))).await.unwrap();
+        // 4 byte big endian con la lunghezza, poi il messaggio
+        assert_eq!(wire.len(), 4 + first + 4 + second);
+        assert_eq!(wire[..4], (first as u32).to_be_bytes());
+
+        let mut reader = FrameReader::new(&wire[..], MAX_FRAME_LEN);
+        reader.set_codec(Codec::MsgPack);
+        for id in [1, 2] {
+            let env = reader.next::<Envelope<ClientToServer>>().await.unwrap();
+            assert_send_message(env.unwrap(), Some(id));
+        }
+        assert!(reader.next_frame().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn oversize_prefix_rejected_before_reading() {
+        // lunghezza dichiarata di 4 GiB seguita da pochi byte: il lettore deve fermarsi
+        // subito, senza allocare il frame né aspettare dati che non arriveranno
+        let mut wire = u32::MAX.to_be_bytes().to_vec();
+        wire.extend_from_slice(b"abc");
+        let mut reader = FrameReader::new(&wire[..], MAX_FRAME_LEN);
+        reader.set_codec(Codec::MsgPack);
+        match reader.next_frame().await {
+            Err(FrameError::TooLarge { max }) => assert_eq!(max, MAX_FRAME_LEN),
+            other => panic!("atteso TooLarge, ottenuto {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn oversize_json_line_rejected() {
+        let mut wire = vec![b'a'; MAX_FRAME_LEN + 1];
+        wire.push(b'\n');
+        let mut reader = FrameReader::new(&wire[..], MAX_FRAME_LEN);
+        assert!(matches!(
+            reader.next_frame().await,
+            Err(FrameError::TooLarge { max: MAX_FRAME_LEN })
+        ));
+
+        // una riga lunga esattamente il massimo è accettata
+        let mut wire = vec![b'a'; MAX_FRAME_LEN];
+        wire.push(b'\n');
+        let mut reader = FrameReader::new(&wire[..], MAX_FRAME_LEN);
+        let frame = reader.next_frame().await.unwrap().unwrap();
+        assert_eq!(frame.len(), MAX_FRAME_LEN);
+    }
+
+    #[tokio::test]
+    async fn oversize_message_not_sent() {
+        let mut wire = Vec::new();
+        let mut writer = FrameWriter::new(&mut wire, 16);
+        assert!(matches!(
+            writer.send(&send_message(None)).await,
+            Err(FrameError::TooLarge { max: 16 })
+        ));
+        assert!(wire.is_empty());
+    }
+}
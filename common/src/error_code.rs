@@ -0,0 +1,259 @@
+/*
+Codici di errore del protocollo: ogni errore inviato dal server ha un codice con i suoi parametri,
+così i client possono riconoscerlo senza interpretare il testo.
+Il testo leggibile viene prodotto dal codice nella lingua scelta (`text`, o Display con la lingua
+del processo) a partire dal catalogo comune (catalog.rs): il server lo usa per il campo `reason`,
+il client lo ricalcola nella propria lingua.
+In JSON: {"type":"GroupNotFound","group":"rust"}
+*/
+
+use crate::catalog::t;
+use crate::i18n::{lang, Lang};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ErrorCode {
+    // Connessione e protocollo
+    BadJson,
+    MissingKind,
+    UnknownMessage { kind: String, version: u32 },
+    HelloRequired { version: u32 },
+    UnsupportedVersion { requested: u32, min: u32, max: u32 },
+    AlreadyNegotiated,
+    RateLimited { kind: String },
+    Flood,
+    IdleTimeout,
+    QueueFull { capacity: usize },
+    FrameTooLarge { max: usize },
+    // la risposta a una richiesta supera la dimensione massima dei messaggi e non è stata inviata
+    ReplyTooLarge { max: usize },
+    TextTooLong { max: usize },
+    KickedByAdmin,
+    Internal,
+
+    // Account e sessioni
+    NotRegistered,
+    AlreadyAuthenticated,
+    InvalidName { target: NameTarget, rule: NameRule },
+    PasswordTooShort { min: usize },
+    PasswordTooLong { max: usize },
+    NickTaken { name: String, existing: String },
+    GroupTaken { name: String, existing: String },
+    NameUsedByUser { name: String },
+    WrongCredentials,
+    AlreadyConnected { nick: String },
+    SessionExpired,
+    SessionReplaced,
+
+    // Gruppi e utenti
+    GroupNotFound { group: String },
+    NotMember { group: String },
+    AlreadyMember { group: String },
+    NoGroups,
+    UserNotFound { nick: String },
+    UserNotMember { nick: String, group: String },
+    UserAlreadyMember { nick: String, group: String },
+    UserOffline { nick: String },
+    DirectMessageToSelf,
+
+    // Inviti
+    InvalidInvite,
+    InviteWrongGroup { group: String },
+    InviteForOther { nick: String },
+    InviteExpired,
+    InviteRevoked,
+    InviteUsedUp { max_uses: u32 },
+    InvalidInviteLimit,
+
+    // Ruoli
+    NotAllowed { group: String, action: GroupAction },
+    KickSelf,
+    AlreadyAdmin { nick: String, group: String },
+    NotAdmin { nick: String, group: String },
+    AlreadyOwner { group: String },
+
+    // Moderazione
+    NotOperator,
+    TargetIsOperator { nick: String },
+    KickedByOperator { by: String, reason: Option<String> },
+    Banned { reason: Option<String> },
+    Muted { seconds: u64 },
+    InvalidAddress { value: String },
+    NotBanned { target: String },
+    NotMuted { nick: String },
+}
+
+impl ErrorCode {
+    // Testo dell'errore nella lingua indicata
+    pub fn text(&self, l: Lang) -> String {
+        use ErrorCode::*;
+        match self {
+            BadJson => t!(l, "error.bad_json"),
+            MissingKind => t!(l, "error.missing_kind"),
+            UnknownMessage { kind, version } => t!(l, "error.unknown_message", kind, version),
+            HelloRequired { version } => t!(l, "error.hello_required", version),
+            UnsupportedVersion {
+                requested,
+                min,
+                max,
+            } => {
+                let hint = if requested < min {
+                    t!(l, "error.update_client")
+                } else {
+                    t!(l, "error.update_server")
+                };
+                t!(l, "error.unsupported_version", requested, min, max, hint)
+            }
+            AlreadyNegotiated => t!(l, "error.already_negotiated"),
+            RateLimited { kind } => t!(l, "error.rate_limited", kind),
+            Flood => t!(l, "error.flood"),
+            IdleTimeout => t!(l, "error.idle_timeout"),
+            QueueFull { capacity } => t!(l, "error.queue_full", capacity),
+            FrameTooLarge { max } => t!(l, "error.frame_too_large", max),
+            ReplyTooLarge { max } => t!(l, "error.reply_too_large", max),
+            TextTooLong { max } => t!(l, "error.text_too_long", max),
+            KickedByAdmin => t!(l, "error.kicked_by_admin"),
+            Internal => t!(l, "error.internal"),
+
+            NotRegistered => t!(l, "error.not_registered"),
+            AlreadyAuthenticated => t!(l, "error.already_authenticated"),
+            InvalidName { target, rule } => {
+                t!(l, "error.invalid_name", target.text(l), rule.text(l))
+            }
+            PasswordTooShort { min } => t!(l, "error.password_too_short", min),
+            PasswordTooLong { max } => t!(l, "error.password_too_long", max),
+            NickTaken { name, existing } => t!(l, "error.nick_taken", name, existing),
+            GroupTaken { name, existing } => t!(l, "error.group_taken", name, existing),
+            NameUsedByUser { name } => t!(l, "error.name_used_by_user", name),
+            WrongCredentials => t!(l, "error.wrong_credentials"),
+            AlreadyConnected { nick } => t!(l, "error.already_connected", nick),
+            SessionExpired => t!(l, "error.session_expired"),
+            SessionReplaced => t!(l, "error.session_replaced"),
+
+            GroupNotFound { group } => t!(l, "error.group_not_found", group),
+            NotMember { group } => t!(l, "error.not_member", group),
+            AlreadyMember { group } => t!(l, "error.already_member", group),
+            NoGroups => t!(l, "error.no_groups"),
+            UserNotFound { nick } => t!(l, "error.user_not_found", nick),
+            UserNotMember { nick, group } => t!(l, "error.user_not_member", nick, group),
+            UserAlreadyMember { nick, group } => {
+                t!(l, "error.user_already_member", nick, group)
+            }
+            UserOffline { nick } => t!(l, "error.user_offline", nick),
+            DirectMessageToSelf => t!(l, "error.dm_to_self"),
+
+            InvalidInvite => t!(l, "error.invalid_invite"),
+            InviteWrongGroup { group } => t!(l, "error.invite_wrong_group", group),
+            InviteForOther { nick } => t!(l, "error.invite_for_other", nick),
+            InviteExpired => t!(l, "error.invite_expired"),
+            InviteRevoked => t!(l, "error.invite_revoked"),
+            InviteUsedUp { max_uses: 1 } => t!(l, "error.invite_used"),
+            InviteUsedUp { max_uses } => t!(l, "error.invite_used_up", max_uses),
+            InvalidInviteLimit => t!(l, "error.invalid_invite_limit"),
+
+            NotAllowed { group, action } => t!(l, "error.not_allowed", action.text(l), group),
+            KickSelf => t!(l, "error.kick_self"),
+            AlreadyAdmin { nick, group } => t!(l, "error.already_admin", nick, group),
+            NotAdmin { nick, group } => t!(l, "error.not_admin", nick, group),
+            AlreadyOwner { group } => t!(l, "error.already_owner", group),
+
+            NotOperator => t!(l, "error.not_operator"),
+            TargetIsOperator { nick } => t!(l, "error.target_is_operator", nick),
+            KickedByOperator { by, reason } => {
+                with_reason(l, t!(l, "error.kicked_by_operator", by), reason)
+            }
+            Banned { reason } => with_reason(l, t!(l, "error.banned"), reason),
+            Muted { seconds } => t!(l, "error.muted", seconds),
+            InvalidAddress { value } => t!(l, "error.invalid_address", value),
+            NotBanned { target } => t!(l, "error.not_banned", target),
+            NotMuted { nick } => t!(l, "error.not_muted", nick),
+        }
+    }
+}
+
+// Aggiunge al testo il motivo indicato dall'operatore, se presente
+fn with_reason(l: Lang, text: String, reason: &Option<String>) -> String {
+    match reason {
+        Some(r) => t!(l, "error.with_reason", text, r),
+        None => text,
+    }
+}
+
+// Testo nella lingua scelta per il processo (vedi i18n::set_lang)
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text(lang()))
+    }
+}
+
+impl std::error::Error for ErrorCode {}
+
+// Tipo di nome a cui si riferisce un errore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NameTarget {
+    Nick,
+    Group,
+}
+
+impl NameTarget {
+    fn text(self, l: Lang) -> String {
+        match self {
+            Self::Nick => t!(l, "name.nick"),
+            Self::Group => t!(l, "name.group"),
+        }
+    }
+}
+
+// Regola di sintassi violata da un nickname o nome di gruppo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NameRule {
+    Empty,
+    TooLong { max: usize },
+    NotAscii,
+    Reserved,
+    Whitespace,
+    FirstNotLetter,
+    NotAlphanumeric,
+}
+
+impl NameRule {
+    fn text(self, l: Lang) -> String {
+        match self {
+            Self::Empty => t!(l, "rule.empty"),
+            Self::TooLong { max } => t!(l, "rule.too_long", max),
+            Self::NotAscii => t!(l, "rule.not_ascii"),
+            Self::Reserved => t!(l, "rule.reserved"),
+            Self::Whitespace => t!(l, "rule.whitespace"),
+            Self::FirstNotLetter => t!(l, "rule.first_not_letter"),
+            Self::NotAlphanumeric => t!(l, "rule.not_alphanumeric"),
+        }
+    }
+}
+
+// Operazione di gestione del gruppo negata per mancanza di permessi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroupAction {
+    Invite,
+    ManageInvites,
+    Kick,
+    Promote,
+    Demote,
+    TransferOwnership,
+    Rename,
+}
+
+impl GroupAction {
+    fn text(self, l: Lang) -> String {
+        match self {
+            Self::Invite => t!(l, "action.invite"),
+            Self::ManageInvites => t!(l, "action.manage_invites"),
+            Self::Kick => t!(l, "action.kick"),
+            Self::Promote => t!(l, "action.promote"),
+            Self::Demote => t!(l, "action.demote"),
+            Self::TransferOwnership => t!(l, "action.transfer_ownership"),
+            Self::Rename => t!(l, "action.rename"),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+/*
+Localizzazione dei testi mostrati all'utente (italiano e inglese).
+La lingua si sceglie con --lang oppure dall'ambiente (RUGGINE_LANG, LC_ALL, LC_MESSAGES, LANG)
+e vale per tutto il processo. Client e server hanno ciascuno il proprio catalogo di testi, più quello
+comune dei codici di errore (catalog.rs):
+una tabella chiave -> (italiano, inglese) in cui `{}` indica i parametri, nell'ordine.
+*/
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    It,
+    En,
+}
+
+// Voce del catalogo: (chiave, testo italiano, testo inglese)
+pub type Catalog = &'static [(&'static str, &'static str, &'static str)];
+
+// Variabili d'ambiente consultate, in ordine di priorità
+const LANG_VARS: &[&str] = &["RUGGINE_LANG", "LC_ALL", "LC_MESSAGES", "LANG"];
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+impl Lang {
+    // Lingua indicata dall'ambiente (es. LANG=en_US.UTF-8); italiano se non riconosciuta
+    pub fn from_env() -> Lang {
+        LANG_VARS
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|v| !v.is_empty())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default()
+    }
+
+    // Sceglie tra la variante italiana e quella inglese
+    pub fn pick<T>(self, it: T, en: T) -> T {
+        match self {
+            Lang::It => it,
+            Lang::En => en,
+        }
+    }
+}
+
+// Accetta "it", "en" e le forme dei locale POSIX come "en_US.UTF-8"
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s
+            .split(['_', '-', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match code.as_str() {
+            "it" => Ok(Lang::It),
+            "en" | "c" | "posix" => Ok(Lang::En),
+            _ => Err(format!("lingua '{s}' non supportata (it, en)")),
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.pick("it", "en"))
+    }
+}
+
+// Imposta la lingua del processo, da chiamare all'avvio
+pub fn set_lang(lang: Lang) {
+    CURRENT.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match CURRENT.load(Ordering::Relaxed) {
+        1 => Lang::En,
+        _ => Lang::It,
+    }
+}
+
+// Testo della chiave nella lingua corrente, con i parametri al posto di `{}`.
+// Una chiave assente dal catalogo viene restituita così com'è, per accorgersene subito
+pub fn translate(catalog: Catalog, key: &str, args: &[&dyn fmt::Display]) -> String {
+    translate_in(lang(), catalog, key, args)
+}
+
+// Come `translate`, nella lingua indicata
+pub fn translate_in(l: Lang, catalog: Catalog, key: &str, args: &[&dyn fmt::Display]) -> String {
+    let template = match catalog.iter().find(|(k, _, _)| *k == key) {
+        Some((_, it, en)) => l.pick(*it, *en),
+        None => return key.to_string(),
+    };
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
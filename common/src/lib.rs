@@ -1,38 +1,316 @@
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+
+mod catalog;
+pub mod codec;
+mod error_code;
+pub mod i18n;
+pub use codec::{Codec, FrameError, FrameReader, FrameWriter, MAX_FRAME_LEN};
+pub use error_code::{ErrorCode, GroupAction, NameRule, NameTarget};
+pub use i18n::Lang;
+
+// Versione del protocollo parlata da questa libreria e la più vecchia ancora accettata dal server
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+// Funzionalità opzionali negoziate con Hello/Welcome
+pub const FEATURE_HISTORY: &str = "history";
+pub const FEATURE_DIRECT_MESSAGES: &str = "dm";
+pub const FEATURE_ROLES: &str = "roles";
+pub const FEATURE_RESUME: &str = "resume";
+// Dopo il Welcome i messaggi viaggiano in frame binari MessagePack invece che in NDJSON (vedi codec)
+pub const FEATURE_MSGPACK: &str = "msgpack";
+// Comandi di moderazione degli operatori del server (vedi ModAction)
+pub const FEATURE_MODERATION: &str = "moderation";
+// Inviti con scadenza, link aperti a più utenti, elenco e revoca dei codici (vedi InviteEntry)
+pub const FEATURE_INVITE_LINKS: &str = "invite_links";
+// Cambio del nome di un gruppo da parte di proprietario e amministratori (vedi GroupRenamed)
+pub const FEATURE_RENAME_GROUP: &str = "rename_group";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "kind")]
 pub enum ClientToServer {
-    Register { nick: String, client_id: Uuid },
-    CreateGroup { group: String },
-    Invite { group: String, nick: String },
-    JoinGroup { group: String, invite_code: String },
-    LeaveGroup { group: String },
-    SendMessage { group: String, text: String, nick: String },
-    GlobalMessage { text: String },
+    // Primo messaggio di ogni connessione: versione del protocollo e funzionalità supportate dal client
+    Hello {
+        protocol_version: u32,
+        features: Vec<String>,
+    },
+    CreateAccount {
+        nick: String,
+        password: String,
+    },
+    Login {
+        nick: String,
+        password: String,
+    },
+    // Ripresa di una sessione interrotta entro il periodo di grazia, con il token ricevuto in Registered
+    Resume {
+        nick: String,
+        session: String,
+    },
+    CreateGroup {
+        group: String,
+    },
+    // `ttl`: secondi di validità del codice (se assente vale il default del server)
+    Invite {
+        group: String,
+        nick: String,
+        ttl: Option<u64>,
+    },
+    // Link di invito non legato a un utente, valido per `max_uses` ingressi (se assente senza limite)
+    CreateInviteLink {
+        group: String,
+        ttl: Option<u64>,
+        max_uses: Option<u32>,
+    },
+    // Inviti ancora validi del gruppo e revoca di un codice, per proprietario e amministratori
+    ListInvites {
+        group: String,
+    },
+    RevokeInvite {
+        code: String,
+    },
+    JoinGroup {
+        group: String,
+        invite_code: String,
+    },
+    LeaveGroup {
+        group: String,
+    },
+    // Gestione dei ruoli nel gruppo: `nick` è l'utente su cui si agisce
+    Kick {
+        group: String,
+        nick: String,
+    },
+    Promote {
+        group: String,
+        nick: String,
+    },
+    Demote {
+        group: String,
+        nick: String,
+    },
+    TransferOwnership {
+        group: String,
+        nick: String,
+    },
+    // Nuovo nome del gruppo: membri, inviti e cronologia passano al nuovo nome
+    RenameGroup {
+        group: String,
+        new_name: String,
+    },
+    // Il mittente non viene indicato: il server usa l'utente autenticato sulla connessione
+    SendMessage {
+        group: String,
+        text: String,
+    },
+    GlobalMessage {
+        text: String,
+    },
+    // Messaggio privato verso un singolo utente connesso
+    DirectMessage {
+        to: String,
+        text: String,
+    },
     ListGroups,
     ListUsers,
-    Logout { reason: Option<String> },
+    Logout {
+        reason: Option<String>,
+    },
     Ping,
+    // Richiesta di una pagina di cronologia: `before` è l'id del messaggio più vecchio già visto
+    History {
+        group: String,
+        before: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Azione di moderazione, ammessa solo agli operatori del server
+    Moderate {
+        action: ModAction,
+    },
+}
+
+// Azioni degli operatori del server. In JSON: {"type":"Mute","nick":"bob","seconds":600}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum ModAction {
+    // Disconnette l'utente, che può accedere di nuovo
+    Kick {
+        nick: String,
+        reason: Option<String>,
+    },
+    // Disconnette l'utente e gli impedisce di accedere finché non viene riammesso
+    Ban {
+        nick: String,
+        reason: Option<String>,
+    },
+    Unban {
+        nick: String,
+    },
+    // `target` è un indirizzo IP oppure il nickname di un utente connesso (si usa il suo indirizzo)
+    BanIp {
+        target: String,
+        reason: Option<String>,
+    },
+    UnbanIp {
+        ip: String,
+    },
+    // Impedisce all'utente di inviare messaggi per `seconds` secondi
+    Mute {
+        nick: String,
+        seconds: u64,
+    },
+    Unmute {
+        nick: String,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "kind")]
 pub enum ServerToClient {
-    Registered { ok: bool, reason: Option<String> },
-    InviteCode { group: String, code: String, client_id: String },
-    InviteCodeForMe { group: String, code: String },
-    ListUsers {users : Vec<String>},
-    Joined { group: String },
-    Left { group: String },
-    Message { group: String, from: String, text: String },
-    MessageServer { text: String },
-    GlobalMessage { from: String, text: String },
-    Groups { groups: Vec<String> },
-    Error { reason: String },
+    // Risposta a Hello: versione concordata e funzionalità supportate da entrambe le parti
+    Welcome {
+        protocol_version: u32,
+        features: Vec<String>,
+    },
+    // `session` è il token con cui riprendere la sessione dopo una disconnessione (vedi Resume)
+    // in caso di rifiuto `error` contiene il codice dell'errore, `reason` il testo corrispondente
+    Registered {
+        ok: bool,
+        reason: Option<String>,
+        #[serde(default)]
+        session: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<ErrorCode>,
+    },
+    // `expires_at`: scadenza del codice (timestamp unix in millisecondi), se prevista
+    InviteCode {
+        group: String,
+        code: String,
+        client_id: String,
+        #[serde(default)]
+        expires_at: Option<i64>,
+    },
+    InviteCodeForMe {
+        group: String,
+        code: String,
+        #[serde(default)]
+        expires_at: Option<i64>,
+    },
+    // Risposta a CreateInviteLink
+    InviteLink {
+        group: String,
+        code: String,
+        expires_at: Option<i64>,
+        max_uses: Option<u32>,
+    },
+    // Risposta a ListInvites
+    Invites {
+        group: String,
+        invites: Vec<InviteEntry>,
+    },
+    ListUsers {
+        users: Vec<String>,
+    },
+    Joined {
+        group: String,
+    },
+    Left {
+        group: String,
+    },
+    Message {
+        group: String,
+        from: String,
+        text: String,
+    },
+    MessageServer {
+        text: String,
+    },
+    GlobalMessage {
+        from: String,
+        text: String,
+    },
+    DirectMessage {
+        from: String,
+        text: String,
+    },
+    Groups {
+        groups: Vec<String>,
+    },
+    // `code` identifica l'errore con i suoi parametri; `reason` è il testo già pronto (vedi ServerToClient::error)
+    Error {
+        code: ErrorCode,
+        reason: String,
+    },
     Pong,
-    GroupCreated { group: String },
+    GroupCreated {
+        group: String,
+    },
+    // Inviato a tutti i membri connessi: `group` è il vecchio nome, `by` chi lo ha cambiato
+    GroupRenamed {
+        group: String,
+        new_name: String,
+        by: String,
+    },
+    HistoryPage {
+        group: String,
+        messages: Vec<HistoryEntry>,
+        has_more: bool,
+    },
+    // Il server si sta arrestando e chiuderà la connessione dopo i messaggi in coda;
+    // `reconnect_after` indica dopo quanti secondi riprovare, se è previsto un riavvio
+    ServerShutdown {
+        reason: String,
+        reconnect_after: Option<u64>,
+    },
+}
+
+impl ServerToClient {
+    // Errore con il testo prodotto dal codice, nella lingua del processo
+    pub fn error(code: ErrorCode) -> Self {
+        Self::Error {
+            reason: code.to_string(),
+            code,
+        }
+    }
+}
+
+// Involucro opzionale di un messaggio con l'id della richiesta: il client può aggiungere `id`
+// a qualsiasi comando e il server lo ripete su tutte le risposte dirette a quel comando.
+// In JSON l'id sta accanto a `kind`, es. {"kind":"Invite","group":"g","nick":"n","id":7}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Envelope<T> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub body: T,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(id: Option<u64>, body: T) -> Self {
+        Self { id, body }
+    }
+}
+
+// Singolo messaggio salvato nella cronologia di un gruppo
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub from: String,
+    pub text: String,
+    pub ts: i64, // timestamp unix in millisecondi
+}
+
+// Invito in attesa, come mostrato da ListInvites e dall'API di amministrazione
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InviteEntry {
+    pub code: String,
+    pub group: String,
+    pub nick: Option<String>, // destinatario; None per un link aperto
+    pub created_by: String,
+    pub created_at: i64,         // timestamp unix in millisecondi
+    pub expires_at: Option<i64>, // None = nessuna scadenza
+    pub uses: u32,
+    pub max_uses: Option<u32>, // None = nessun limite
 }
 
 #[derive(Debug, thiserror::Error)]